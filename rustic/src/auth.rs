@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rsa::pkcs8::EncodePublicKey;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use rustic_io::encryption::SharedSecret;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+
+// What vanilla uses, clients don't accept anything else.
+const KEY_BITS: usize = 1024;
+const VERIFY_TOKEN_LEN: usize = 4;
+
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+// How long a player waits on the session server before they're told they couldn't be verified.
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The server's key pair for the encryption handshake, made fresh every time the server starts.
+pub struct Keys {
+    private: RsaPrivateKey,
+    // The public key as DER, which is what Encryption Request sends.
+    public_der: Vec<u8>,
+}
+
+impl Keys {
+    pub fn generate() -> Result<Self> {
+        let private = RsaPrivateKey::new(&mut OsRng, KEY_BITS).context("couldn't generate the server's key pair")?;
+        let public_der = RsaPublicKey::from(&private).to_public_key_der()?.into_vec();
+
+        Ok(Self { private, public_der })
+    }
+
+    pub fn public_der(&self) -> &[u8] {
+        &self.public_der
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.private.decrypt(Pkcs1v15Encrypt, data).map_err(|err| anyhow!("couldn't decrypt: {}", err))
+    }
+
+    // The shared secret from an Encryption Response, which has to be an AES-128 key.
+    pub fn decrypt_secret(&self, data: &[u8]) -> Result<SharedSecret> {
+        let secret = self.decrypt(data)?;
+
+        secret.as_slice().try_into().map_err(|_| anyhow!("the shared secret is {} bytes instead of 16", secret.len()))
+    }
+}
+
+pub fn verify_token() -> [u8; VERIFY_TOKEN_LEN] {
+    let mut token = [0; VERIFY_TOKEN_LEN];
+    OsRng.fill_bytes(&mut token);
+
+    token
+}
+
+// Java's `new BigInteger(sha1).toString(16)`: the digest as a signed two's complement number, without leading zeros.
+fn signed_hex_digest(digest: &[u8]) -> String {
+    let negative = digest[0] & 0x80 != 0;
+    let mut bytes = digest.to_vec();
+
+    if negative {
+        // Negate: flip every bit and add one.
+        let mut carry = true;
+
+        for byte in bytes.iter_mut().rev() {
+            *byte = !*byte;
+
+            if carry {
+                let (sum, overflow) = byte.overflowing_add(1);
+                *byte = sum;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let hex = hex.trim_start_matches('0');
+
+    match (negative, hex.is_empty()) {
+        (_, true) => "0".to_owned(),
+        (true, false) => format!("-{}", hex),
+        (false, false) => hex.to_owned(),
+    }
+}
+
+// The `serverId` both the client and the server send to the session server, which is how it knows they're talking to
+// each other.
+pub fn server_hash(server_id: &str, secret: &[u8], public_key: &[u8]) -> String {
+    let digest = Sha1::new().chain_update(server_id).chain_update(secret).chain_update(public_key).finalize();
+
+    signed_hex_digest(&digest)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+// A player's profile, as the session server has it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Profile {
+    pub id: Uuid,
+    pub name: String,
+    // The skin and cape, mostly.
+    #[serde(default)]
+    pub properties: Vec<ProfileProperty>,
+}
+
+// Checks that players own the account they log in with.
+#[async_trait]
+pub trait SessionService: Send + Sync {
+    // The player's profile if they told the session server they're joining with `server_hash`, `None` if not.
+    async fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<Profile>>;
+}
+
+// Mojang's session server, or anything else with the same API.
+pub struct HttpSessionService {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpSessionService {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Self::with_timeout(base_url, SESSION_TIMEOUT)
+    }
+
+    pub fn with_timeout(base_url: &str, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder().timeout(timeout).build().context("couldn't set up the session server's client")?;
+
+        Ok(Self { client, base_url: base_url.trim_end_matches('/').to_owned() })
+    }
+}
+
+#[async_trait]
+impl SessionService for HttpSessionService {
+    async fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<Profile>> {
+        let response = self
+            .client
+            .get(format!("{}/session/minecraft/hasJoined", self.base_url))
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await
+            .context("couldn't reach the session server")?;
+
+        // No content means the player didn't join.
+        match response.status() {
+            reqwest::StatusCode::OK => Ok(Some(response.json().await.context("invalid profile from the session server")?)),
+            reqwest::StatusCode::NO_CONTENT => Ok(None),
+            status => bail!("the session server answered with {}", status),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use anyhow::Result;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Stands in for the session server, with the joins a client would have told it about.
+    #[derive(Default)]
+    pub(crate) struct FakeSessionService {
+        joins: Mutex<HashMap<(String, String), Profile>>,
+        // Fails every request, like when the session server doesn't answer.
+        down: AtomicBool,
+    }
+
+    impl FakeSessionService {
+        // What the client does before sending Encryption Response.
+        pub(crate) fn join(&self, server_hash: &str, profile: Profile) {
+            self.joins.lock().unwrap().insert((profile.name.clone(), server_hash.to_owned()), profile);
+        }
+
+        pub(crate) fn go_down(&self) {
+            self.down.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[async_trait]
+    impl SessionService for FakeSessionService {
+        async fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<Profile>> {
+            if self.down.load(Ordering::Relaxed) {
+                bail!("the session server is down");
+            }
+
+            Ok(self.joins.lock().unwrap().remove(&(username.to_owned(), server_hash.to_owned())))
+        }
+    }
+
+    fn sha1_hex(name: &str) -> String {
+        signed_hex_digest(&Sha1::digest(name))
+    }
+
+    #[test]
+    fn server_hash_test() {
+        // The examples from wiki.vg.
+        assert_eq!(sha1_hex("Notch"), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+        assert_eq!(sha1_hex("jeb_"), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+        assert_eq!(sha1_hex("simon"), "88e16a1019277b15d58faf0541e11910eb756f6");
+
+        assert_eq!(signed_hex_digest(&[0; 20]), "0");
+        assert_eq!(signed_hex_digest(&[0xff; 20]), "-1");
+    }
+
+    #[test]
+    fn keys_test() -> Result<()> {
+        let keys = Keys::generate()?;
+        let public = RsaPublicKey::from(&keys.private);
+        let secret = [7; 16];
+
+        let encrypted = public.encrypt(&mut OsRng, Pkcs1v15Encrypt, &secret)?;
+        assert_eq!(keys.decrypt_secret(&encrypted)?, secret);
+
+        let encrypted = public.encrypt(&mut OsRng, Pkcs1v15Encrypt, &[7; 8])?;
+        assert!(keys.decrypt_secret(&encrypted).is_err());
+
+        Ok(())
+    }
+
+    // Answers one request with `status` and `body`, and hands back the request line.
+    async fn stand_in(status: &'static str, body: &'static str) -> Result<(String, tokio::task::JoinHandle<Result<String>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = Vec::new();
+
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).await?;
+                request.push(byte[0]);
+            }
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await?;
+
+            let request = String::from_utf8(request)?;
+            Ok(request.lines().next().unwrap_or_default().to_owned())
+        });
+
+        Ok((url, handle))
+    }
+
+    #[tokio::test]
+    async fn http_session_test() -> Result<()> {
+        let body = r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[{"name":"textures","value":"e30=","signature":"c2ln"}]}"#;
+        let (url, request) = stand_in("200 OK", body).await?;
+
+        let profile = HttpSessionService::new(&url)?.has_joined("Notch", "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1").await?.unwrap();
+
+        assert_eq!(
+            request.await??,
+            "GET /session/minecraft/hasJoined?username=Notch&serverId=-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1 HTTP/1.1"
+        );
+        assert_eq!(profile.id.to_string(), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(profile.properties[0].signature.as_deref(), Some("c2ln"));
+
+        let (url, _) = stand_in("204 No Content", "").await?;
+        assert_eq!(HttpSessionService::new(&url)?.has_joined("Notch", "0").await?, None);
+
+        let (url, _) = stand_in("500 Internal Server Error", "").await?;
+        assert!(HttpSessionService::new(&url)?.has_joined("Notch", "0").await.is_err());
+
+        // One that never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let session = HttpSessionService::with_timeout(&url, Duration::from_millis(100))?;
+
+        let started = std::time::Instant::now();
+        assert!(session.has_joined("Notch", "0").await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(listener);
+
+        Ok(())
+    }
+}
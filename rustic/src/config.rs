@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::auth::MOJANG_SESSION_SERVER;
+use crate::forwarding::Forwarding;
+
+// Read from `rustic.toml`, every setting can be left out.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub address: SocketAddr,
+    // Shown in the server list, can have § formatting codes.
+    pub motd: String,
+    pub max_players: u32,
+    // A 64x64 PNG for the server list.
+    pub favicon: Option<PathBuf>,
+    // Packets of at least this many bytes get compressed, negative turns compression off like in server.properties.
+    pub compression_threshold: i32,
+    // Checks with the session server that players own their account, and encrypts the connection.
+    pub online_mode: bool,
+    // Where `hasJoined` is asked in online mode.
+    pub session_server: String,
+    // How a proxy in front of the server tells it who the players are. The proxy does the authentication then, so
+    // `online_mode` doesn't matter.
+    pub forwarding: Forwarding,
+    // Velocity's `forwarding-secret`, or BungeeGuard's token.
+    pub forwarding_secret: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 25565)),
+            motd: "A Rustic server".to_owned(),
+            max_players: 20,
+            favicon: None,
+            compression_threshold: 256,
+            online_mode: true,
+            session_server: MOJANG_SESSION_SERVER.to_owned(),
+            forwarding: Forwarding::None,
+            forwarding_secret: String::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn compression_threshold(&self) -> Option<usize> {
+        usize::try_from(self.compression_threshold).ok()
+    }
+
+    // Whether the server itself checks players with the session server.
+    pub fn authenticates(&self) -> bool {
+        self.online_mode && self.forwarding == Forwarding::None
+    }
+
+    // The defaults if the file doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(path).with_context(|| format!("couldn't read {}", path.display()))?;
+
+        toml::from_str(&text).with_context(|| format!("{} is not a valid config", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn config_test() -> Result<()> {
+        let config: Config = toml::from_str("address = \"127.0.0.1:25566\"")?;
+        assert_eq!(config.address.port(), 25566);
+
+        let config: Config = toml::from_str("")?;
+        assert_eq!(config.address.port(), 25565);
+        assert_eq!(config.max_players, 20);
+        assert_eq!(config.compression_threshold(), Some(256));
+        assert!(config.online_mode);
+        assert_eq!(config.session_server, MOJANG_SESSION_SERVER);
+        assert_eq!(config.forwarding, Forwarding::None);
+
+        let config: Config = toml::from_str("compression_threshold = -1")?;
+        assert_eq!(config.compression_threshold(), None);
+
+        let config: Config = toml::from_str("motd = \"§aHello\"\nfavicon = \"icon.png\"")?;
+        assert_eq!(config.motd, "§aHello");
+        assert_eq!(config.favicon, Some(PathBuf::from("icon.png")));
+
+        let config: Config = toml::from_str("forwarding = \"velocity\"\nforwarding_secret = \"hunter2\"")?;
+        assert_eq!(config.forwarding, Forwarding::Velocity);
+        assert_eq!(config.forwarding_secret, "hunter2");
+
+        assert!(toml::from_str::<Config>("forwarding = \"waterfall\"").is_err());
+        assert!(toml::from_str::<Config>("adress = \"127.0.0.1:25566\"").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use hmac::{Hmac, Mac};
+use rustic_io::datatypes::var::VarInt;
+use rustic_io::packet::Packet;
+use rustic_io::scroll::Pread;
+use serde::Deserialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::auth::ProfileProperty;
+
+// The channel Velocity answers modern forwarding requests on.
+pub const VELOCITY_CHANNEL: &str = "velocity:player_info";
+// The oldest (and simplest) version of the player info, which every Velocity release sends if asked for it.
+pub const VELOCITY_VERSION: u8 = 1;
+
+const SIGNATURE_LEN: usize = 32;
+
+// BungeeGuard's property, which has to match the secret.
+const BUNGEEGUARD_TOKEN: &str = "bungeeguard-token";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Forwarding {
+    None,
+    // The player's address, UUID and skin in the handshake's server address, separated by null characters.
+    BungeeCord,
+    // A login plugin request the proxy answers with the player's details, signed with the secret.
+    Velocity,
+}
+
+// Who a proxy says the player is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forwarded {
+    pub ip: IpAddr,
+    pub uuid: Uuid,
+    // BungeeCord doesn't send the name, it's the one from Login Start.
+    pub name: Option<String>,
+    pub properties: Vec<ProfileProperty>,
+}
+
+// Reads what BungeeCord put into the handshake. Anyone can put anything there, so only the BungeeGuard token (which
+// the client never sees) tells the proxy apart from a client pretending to be one.
+pub fn bungeecord(server_address: &str, secret: &str) -> Result<Forwarded> {
+    let parts: Vec<_> = server_address.split('\0').collect();
+
+    let (ip, uuid, properties) = match parts[..] {
+        [_, ip, uuid] => (ip, uuid, None),
+        [_, ip, uuid, properties] => (ip, uuid, Some(properties)),
+        _ => bail!("the handshake has no forwarded address, is IP forwarding enabled in BungeeCord?"),
+    };
+
+    let ip = ip.parse().with_context(|| format!("invalid forwarded address {:?}", ip))?;
+    let uuid = Uuid::parse_str(uuid).with_context(|| format!("invalid forwarded UUID {:?}", uuid))?;
+
+    let mut properties: Vec<ProfileProperty> = match properties {
+        Some(json) => serde_json::from_str(json).context("invalid forwarded properties")?,
+        None => Vec::new(),
+    };
+
+    let token = properties.iter().position(|property| property.name == BUNGEEGUARD_TOKEN);
+
+    // The token is only for us, the client doesn't need to see it.
+    match token.map(|i| properties.remove(i)) {
+        Some(token) if !secret.is_empty() && token.value == secret => {}
+        _ => bail!("the forwarded BungeeGuard token is missing or wrong"),
+    }
+
+    Ok(Forwarded { ip, uuid, name: None, properties })
+}
+
+#[derive(Debug, PartialEq, Packet)]
+struct VelocityProperty {
+    name: String,
+    value: String,
+    signature: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Packet)]
+struct VelocityPlayerInfo {
+    version: VarInt,
+    address: String,
+    uuid: u128,
+    #[packet(max_len = 16)]
+    username: String,
+    properties: Vec<VelocityProperty>,
+}
+
+fn velocity_mac(secret: &str) -> Hmac<Sha256> {
+    // HMAC takes keys of any length.
+    Hmac::new_from_slice(secret.as_bytes()).unwrap()
+}
+
+// Checks the signature on the data of Velocity's login plugin response, and reads the player info after it.
+pub fn velocity(data: &[u8], secret: &str) -> Result<Forwarded> {
+    if data.len() < SIGNATURE_LEN {
+        bail!("the forwarded player info is too short to be signed");
+    }
+
+    let (signature, info) = data.split_at(SIGNATURE_LEN);
+
+    let mut mac = velocity_mac(secret);
+    mac.update(info);
+    mac.verify_slice(signature).map_err(|_| anyhow!("the forwarded player info isn't signed with our secret"))?;
+
+    let info: VelocityPlayerInfo = info.pread(0).context("invalid forwarded player info")?;
+
+    if info.version != VarInt(VELOCITY_VERSION as i32) {
+        bail!("forwarding version {} wasn't asked for", info.version.0);
+    }
+
+    let properties = info
+        .properties
+        .into_iter()
+        .map(|property| ProfileProperty { name: property.name, value: property.value, signature: property.signature })
+        .collect();
+
+    Ok(Forwarded {
+        ip: info.address.parse().with_context(|| format!("invalid forwarded address {:?}", info.address))?,
+        uuid: Uuid::from_u128(info.uuid),
+        name: Some(info.username),
+        properties,
+    })
+}
+
+// What Velocity sends back, for tests that pretend to be it.
+#[cfg(test)]
+pub(crate) fn sign_velocity(forwarded: &Forwarded, secret: &str) -> Result<Vec<u8>> {
+    use rustic_io::scroll::ctx::{MeasureWith, TryIntoCtx};
+
+    let info = VelocityPlayerInfo {
+        version: VarInt(VELOCITY_VERSION as i32),
+        address: forwarded.ip.to_string(),
+        uuid: forwarded.uuid.as_u128(),
+        username: forwarded.name.clone().unwrap_or_default(),
+        properties: forwarded
+            .properties
+            .iter()
+            .map(|property| VelocityProperty {
+                name: property.name.clone(),
+                value: property.value.clone(),
+                signature: property.signature.clone(),
+            })
+            .collect(),
+    };
+
+    let mut bytes = vec![0; info.measure_with(&())];
+    info.try_into_ctx(&mut bytes, ())?;
+
+    let mut mac = velocity_mac(secret);
+    mac.update(&bytes);
+
+    let mut data = mac.finalize().into_bytes().to_vec();
+    data.extend(bytes);
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    const UUID: &str = "069a79f444e94726a5befca90e38aaf5";
+
+    #[test]
+    fn bungeecord_test() -> Result<()> {
+        let token = r#"{"name":"bungeeguard-token","value":"hunter2"}"#;
+
+        let forwarded = bungeecord(&["localhost", "192.168.0.5", UUID, &format!("[{}]", token)].join("\0"), "hunter2")?;
+
+        assert_eq!(forwarded.ip, "192.168.0.5".parse::<IpAddr>()?);
+        assert_eq!(forwarded.uuid, Uuid::parse_str(UUID)?);
+        assert!(forwarded.properties.is_empty());
+
+        let properties = format!(r#"[{{"name":"textures","value":"e30=","signature":"c2ln"}},{}]"#, token);
+        let forwarded = bungeecord(&["localhost", "::1", UUID, &properties].join("\0"), "hunter2")?;
+
+        assert_eq!(forwarded.ip, "::1".parse::<IpAddr>()?);
+        assert_eq!(forwarded.properties[0].value, "e30=");
+
+        assert!(bungeecord("localhost", "hunter2").is_err());
+        assert!(bungeecord(&["localhost", "not an ip", UUID, &properties].join("\0"), "hunter2").is_err());
+        assert!(bungeecord(&["localhost", "127.0.0.1", "not a uuid", &properties].join("\0"), "hunter2").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bungeeguard_test() -> Result<()> {
+        let properties = r#"[{"name":"textures","value":"e30="},{"name":"bungeeguard-token","value":"hunter2"}]"#;
+        let address = ["localhost", "127.0.0.1", UUID, properties].join("\0");
+
+        let forwarded = bungeecord(&address, "hunter2")?;
+        assert_eq!(forwarded.properties.len(), 1);
+        assert_eq!(forwarded.properties[0].name, "textures");
+
+        assert!(bungeecord(&address, "hunter3").is_err());
+        assert!(bungeecord(&["localhost", "127.0.0.1", UUID].join("\0"), "hunter2").is_err());
+
+        // Without a secret nothing is trusted, not even an empty token.
+        let empty = r#"[{"name":"bungeeguard-token","value":""}]"#;
+        assert!(bungeecord(&["localhost", "127.0.0.1", UUID].join("\0"), "").is_err());
+        assert!(bungeecord(&["localhost", "127.0.0.1", UUID, empty].join("\0"), "").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn velocity_test() -> Result<()> {
+        let forwarded = Forwarded {
+            ip: "10.0.0.7".parse()?,
+            uuid: Uuid::parse_str(UUID)?,
+            name: Some("Notch".to_owned()),
+            properties: vec![ProfileProperty { name: "textures".to_owned(), value: "e30=".to_owned(), signature: None }],
+        };
+
+        let mut data = sign_velocity(&forwarded, "hunter2")?;
+        assert_eq!(velocity(&data, "hunter2")?, forwarded);
+        assert!(velocity(&data, "hunter3").is_err());
+
+        // Changing the info breaks the signature.
+        *data.last_mut().unwrap() ^= 1;
+        assert!(velocity(&data, "hunter2").is_err());
+
+        assert!(velocity(&[0; 8], "hunter2").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+use anyhow::{anyhow, bail, Result};
+use md5::{Digest, Md5};
+use rustic_io::connection::ConnectionEvent;
+use rustic_io::datatypes::var::VarInt;
+use rustic_types::protocol::login::clientbound::{Disconnect, EncryptionBegin, LoginPluginRequest};
+use rustic_types::protocol::login::serverbound::{self, LoginPluginResponse, LoginStart, Serverbound};
+use rustic_types::protocol::{ClientboundEvent, Version};
+use rustic_types::text::TextComponent;
+use uuid::{Builder, Uuid};
+
+use crate::auth::{self, Keys};
+use crate::forwarding::{self, Forwarded, Forwarding, VELOCITY_CHANNEL, VELOCITY_VERSION};
+use crate::server::{ClientConnection, Joined, Player, Server};
+
+const MIN_USERNAME_LEN: usize = 3;
+const MAX_USERNAME_LEN: usize = 16;
+
+// What vanilla gives players in offline mode, Java's `UUID.nameUUIDFromBytes` of `OfflinePlayer:<name>`.
+pub fn offline_uuid(username: &str) -> Uuid {
+    let hash = Md5::digest(format!("OfflinePlayer:{}", username));
+
+    Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
+pub fn valid_username(username: &str) -> bool {
+    (MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// The login packets that changed between versions, like Login Success, go out the way the client's version has them.
+async fn send(connection: &mut ClientConnection, version: &Version, event: ClientboundEvent) -> Result<()> {
+    let (id, data) = version.encode_clientbound(event)?;
+
+    Ok(connection.send_frame(id, data).await?)
+}
+
+// Tells the client why it can't join, the error is what ends up in the log. Without a version, it's sent the way the
+// primary version has it.
+async fn disconnect(connection: &mut ClientConnection, version: Option<&Version>, username: &str, reason: &str) -> anyhow::Error {
+    let reason_json = TextComponent::text(reason).to_json();
+
+    let sent = match version {
+        Some(version) => send(connection, version, ClientboundEvent::LoginDisconnect { reason: reason_json }).await,
+        None => connection.send(Disconnect { reason: reason_json }).await.map_err(Into::into),
+    };
+
+    match sent {
+        Ok(()) => anyhow!("{} couldn't log in: {}", username, reason),
+        Err(err) => err,
+    }
+}
+
+// What vanilla says to clients on a protocol it doesn't speak, with the versions it does speak instead of its own.
+pub async fn refuse_version(connection: &mut ClientConnection, protocol: i32) -> anyhow::Error {
+    let versions: Vec<_> = Version::all().iter().map(Version::name).collect();
+
+    let reason = match Version::latest() {
+        Some(latest) if protocol > latest.protocol() => format!("Outdated server! I'm still on {}", versions.join(", ")),
+        _ => format!("Outdated client! Please use {}", versions.join(", ")),
+    };
+
+    disconnect(connection, None, &format!("A client on protocol {}", protocol), &reason).await
+}
+
+async fn read_packet(connection: &mut ClientConnection) -> Result<Serverbound> {
+    Ok(connection.read_packet(Serverbound::decode).await?)
+}
+
+// The encryption handshake, after which the session server tells us who the player really is.
+async fn authenticate(
+    server: &Server,
+    keys: &Keys,
+    connection: &mut ClientConnection,
+    version: &Version,
+    username: &str,
+) -> Result<Player> {
+    let token = auth::verify_token();
+
+    connection
+        .send(EncryptionBegin { server_id: String::new(), public_key: keys.public_der().to_vec(), verify_token: token.to_vec() })
+        .await?;
+
+    let Serverbound::EncryptionBegin(serverbound::EncryptionBegin { shared_secret, verify_token }) = read_packet(connection).await? else {
+        return Err(anyhow!("{} sent Login Start twice", username));
+    };
+
+    if keys.decrypt(&verify_token)? != token {
+        return Err(anyhow!("{} sent back the wrong verify token", username));
+    }
+
+    let secret = keys.decrypt_secret(&shared_secret)?;
+    connection.enable_encryption(&secret);
+
+    let hash = auth::server_hash("", &secret, keys.public_der());
+
+    match server.session().has_joined(username, &hash).await {
+        Ok(Some(profile)) => {
+            Ok(Player { name: profile.name, uuid: profile.id, ip: connection.addr().ip(), properties: profile.properties })
+        }
+        Ok(None) => Err(disconnect(connection, Some(version), username, "Failed to verify username!").await),
+        // Like when it doesn't answer in time, the player hears the same as if they hadn't joined.
+        Err(err) => {
+            disconnect(connection, Some(version), username, "Failed to verify username!").await;
+            Err(err.context(format!("couldn't verify {}", username)))
+        }
+    }
+}
+
+// Asks Velocity who the player is.
+async fn velocity(server: &Server, connection: &mut ClientConnection) -> Result<Forwarded> {
+    let message_id = rand::random();
+
+    connection
+        .send(LoginPluginRequest { message_id: VarInt(message_id), channel: VELOCITY_CHANNEL.to_owned(), data: vec![VELOCITY_VERSION] })
+        .await?;
+
+    let Serverbound::LoginPluginResponse(LoginPluginResponse { message_id: VarInt(answered), data }) = read_packet(connection).await?
+    else {
+        bail!("expected an answer to the forwarding request");
+    };
+
+    if answered != message_id {
+        bail!("the answer is to another request");
+    }
+
+    // Any client that isn't a proxy doesn't know the channel.
+    let Some(data) = data else {
+        bail!("not connected through Velocity, or modern forwarding is disabled there");
+    };
+
+    forwarding::velocity(&data, &server.config().forwarding_secret)
+}
+
+// Takes the connection from Login Start to Login Success, after which it's in Play. The player stays on the server's
+// list as long as the returned guard is around. `server_address` is the one from the handshake, where BungeeCord
+// forwards the player's details, `version` the one the client speaks.
+pub async fn handle<'a>(
+    server: &'a Server,
+    connection: &mut ClientConnection,
+    server_address: &str,
+    version: &Version,
+) -> Result<Joined<'a>> {
+    let Serverbound::LoginStart(LoginStart { username }) = read_packet(connection).await? else {
+        return Err(anyhow!("expected Login Start"));
+    };
+
+    if !valid_username(&username) {
+        return Err(disconnect(connection, Some(version), &username, "Invalid username").await);
+    }
+
+    let forwarded = match server.config().forwarding {
+        Forwarding::None => None,
+        Forwarding::BungeeCord => Some(forwarding::bungeecord(server_address, &server.config().forwarding_secret)),
+        Forwarding::Velocity => Some(velocity(server, connection).await),
+    };
+
+    let player = match (forwarded, server.keys()) {
+        (Some(Ok(Forwarded { ip, uuid, name, properties })), _) => Player { name: name.unwrap_or(username), uuid, ip, properties },
+        (Some(Err(err)), _) => {
+            // The client gets the short version, the log the real reason.
+            let _ = disconnect(connection, Some(version), &username, "You have to connect through the server's proxy").await;
+            return Err(err.context(format!("{} couldn't log in", username)));
+        }
+        (None, Some(keys)) => authenticate(server, keys, connection, version, &username).await?,
+        (None, None) => Player { uuid: offline_uuid(&username), name: username, ip: connection.addr().ip(), properties: Vec::new() },
+    };
+
+    let Some(joined) = server.join(connection.id(), player.clone()) else {
+        return Err(disconnect(connection, Some(version), &player.name, "You are already logged in").await);
+    };
+
+    if let Some(threshold) = server.config().compression_threshold() {
+        send(connection, version, ClientboundEvent::SetCompression { threshold: threshold as i32 }).await?;
+        connection.set_compression(Some(threshold));
+    }
+
+    send(connection, version, ClientboundEvent::LoginSuccess { uuid: player.uuid.into(), username: player.name.clone() }).await?;
+    connection.report(ConnectionEvent::LoggedIn { id: connection.id(), username: player.name, uuid: player.uuid.into() });
+
+    Ok(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+    use rustic_io::connection::ConnectionError;
+    use rustic_io::packet::{ConnectionState, Packet};
+    use rustic_types::protocol::login::clientbound::Success;
+
+    use crate::auth::tests::FakeSessionService;
+    use crate::auth::Profile;
+    use crate::config::Config;
+    use crate::forwarding::sign_velocity;
+    use crate::server::tests::{connect, connect_to, connect_with, finish_login, login, offline, receive, start, start_with_session};
+
+    use super::*;
+
+    #[test]
+    fn offline_uuid_test() {
+        assert_eq!(offline_uuid("Notch").to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+        assert_eq!(offline_uuid("Notch").get_version_num(), 3);
+        assert_ne!(offline_uuid("notch"), offline_uuid("Notch"));
+    }
+
+    #[test]
+    fn username_test() {
+        assert!(valid_username("Notch"));
+        assert!(valid_username("a_b"));
+        assert!(valid_username("sixteen_chars_ok"));
+        assert!(!valid_username("ab"));
+        assert!(!valid_username("seventeen_chars_x"));
+        assert!(!valid_username("with space"));
+        assert!(!valid_username("ünicode"));
+    }
+
+    async fn rejection(addr: std::net::SocketAddr, username: &str) -> Result<String> {
+        let mut client = connect(addr, ConnectionState::Login).await?;
+        client.send(LoginStart { username: username.to_owned() }).await?;
+
+        let (id, data) = client.read_frame().await?;
+        assert_eq!(id, Disconnect::ID);
+
+        Ok(Disconnect::decode(&data)?.reason)
+    }
+
+    #[tokio::test]
+    async fn old_version_login_test() -> Result<()> {
+        // 1.15.2 is one of the older versions there are definitions for by default, and it had Login Success change since.
+        let old = Version::by_protocol(578).unwrap();
+        let (addr, _events) = start(offline()).await?;
+
+        let mut client = connect_with(addr, ConnectionState::Login, "localhost", old.protocol()).await?;
+        client.send(LoginStart { username: "Notch".to_owned() }).await?;
+
+        loop {
+            let (id, data) = client.read_frame().await?;
+
+            match old.decode_clientbound(ConnectionState::Login, id, &data)? {
+                Some(ClientboundEvent::SetCompression { threshold }) => client.set_compression(Some(threshold as usize)),
+                Some(ClientboundEvent::LoginSuccess { uuid, username }) => {
+                    assert_eq!((Uuid::from(uuid), username.as_str()), (offline_uuid("Notch"), "Notch"));
+                    // The UUID as a string with hyphens.
+                    assert_eq!(data.len(), 1 + 36 + 1 + 5);
+                    return Ok(());
+                }
+                event => panic!("unexpected {:?} during login", event),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_version_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let versions: Vec<_> = Version::all().iter().map(Version::name).collect();
+        let latest = Version::latest().unwrap().protocol();
+
+        // Told right after the handshake, before it even says who it is.
+        for (protocol, reason) in [(1, "Outdated client! Please use"), (latest + 1, "Outdated server! I'm still on")] {
+            let mut client = connect_with(addr, ConnectionState::Login, "localhost", protocol).await?;
+            let disconnect: Disconnect = receive(&mut client).await?;
+
+            assert_eq!(disconnect.reason, TextComponent::text(format!("{} {}", reason, versions.join(", "))).to_json());
+            assert!(matches!(client.read_frame().await, Err(ConnectionError::Closed)));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offline_login_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let mut client = connect(addr, ConnectionState::Login).await?;
+
+        let success = login(&mut client, "Notch").await?;
+        assert_eq!(Uuid::from(success.uuid), offline_uuid("Notch"));
+
+        assert_eq!(rejection(addr, "NOTCH").await?, r#"{"text":"You are already logged in"}"#);
+        assert_eq!(rejection(addr, "no").await?, r#"{"text":"Invalid username"}"#);
+
+        // Once the first one leaves, the name is free again.
+        drop(client);
+
+        for _ in 0..100 {
+            let mut client = connect(addr, ConnectionState::Login).await?;
+
+            if login(&mut client, "Notch").await.is_ok() {
+                return Ok(());
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        panic!("Notch never left");
+    }
+
+    #[tokio::test]
+    async fn uncompressed_login_test() -> Result<()> {
+        let (addr, _events) = start(Config { compression_threshold: -1, ..offline() }).await?;
+        let mut client = connect(addr, ConnectionState::Login).await?;
+
+        client.send(LoginStart { username: "Notch".to_owned() }).await?;
+
+        // Straight to Login Success, without Set Compression.
+        let (id, _) = client.read_frame().await?;
+        assert_eq!(id, Success::ID);
+
+        Ok(())
+    }
+
+    // Does what a client does in online mode, up to the session server, which is only told about the join if `join`.
+    async fn online_login(
+        addr: std::net::SocketAddr,
+        session: &FakeSessionService,
+        profile: &Profile,
+        join: bool,
+    ) -> Result<ClientConnection> {
+        let mut client = connect(addr, ConnectionState::Login).await?;
+        client.send(LoginStart { username: profile.name.clone() }).await?;
+
+        let request: EncryptionBegin = receive(&mut client).await?;
+        let public_key = RsaPublicKey::from_public_key_der(&request.public_key)?;
+        let secret = [0x42; 16];
+
+        if join {
+            session.join(&auth::server_hash(&request.server_id, &secret, &request.public_key), profile.clone());
+        }
+
+        let response = serverbound::EncryptionBegin {
+            shared_secret: public_key.encrypt(&mut rand::rngs::OsRng, Pkcs1v15Encrypt, &secret)?,
+            verify_token: public_key.encrypt(&mut rand::rngs::OsRng, Pkcs1v15Encrypt, &request.verify_token)?,
+        };
+
+        client.send(response).await?;
+        client.enable_encryption(&secret);
+
+        Ok(client)
+    }
+
+    #[tokio::test]
+    async fn online_login_test() -> Result<()> {
+        let session = std::sync::Arc::new(FakeSessionService::default());
+        let (addr, _events) = start_with_session(Config::default(), session.clone()).await?;
+
+        let profile = Profile { id: Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5), name: "Notch".to_owned(), properties: Vec::new() };
+
+        let mut client = online_login(addr, &session, &profile, true).await?;
+        let success = finish_login(&mut client).await?;
+
+        // The real UUID, not the offline one.
+        assert_eq!(Uuid::from(success.uuid), profile.id);
+        assert_eq!(success.username, "Notch");
+
+        // Someone who didn't go through the session server is turned away, over the encrypted connection.
+        let profile = Profile { name: "jeb_".to_owned(), ..profile };
+        let mut client = online_login(addr, &session, &profile, false).await?;
+
+        let disconnect: Disconnect = receive(&mut client).await?;
+        assert_eq!(disconnect.reason, r#"{"text":"Failed to verify username!"}"#);
+
+        // So is everyone while the session server is down.
+        session.go_down();
+        let profile = Profile { name: "Notch".to_owned(), ..profile };
+        let mut client = online_login(addr, &session, &profile, true).await?;
+
+        let disconnect: Disconnect = receive(&mut client).await?;
+        assert_eq!(disconnect.reason, r#"{"text":"Failed to verify username!"}"#);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bungeecord_login_test() -> Result<()> {
+        // Without BungeeGuard's token anyone could pretend to be the proxy.
+        assert!(start(Config { forwarding: Forwarding::BungeeCord, ..offline() }).await.is_err());
+
+        let config = Config { forwarding: Forwarding::BungeeCord, forwarding_secret: "hunter2".to_owned(), ..offline() };
+        let (addr, _events) = start(config).await?;
+
+        let uuid = Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5);
+        let properties = r#"[{"name":"bungeeguard-token","value":"hunter2"}]"#;
+        let address = ["localhost", "10.1.2.3", &uuid.simple().to_string(), properties].join("\0");
+        let mut client = connect_to(addr, ConnectionState::Login, &address).await?;
+
+        // The UUID BungeeCord got from the session server, instead of an offline one.
+        let success = login(&mut client, "Notch").await?;
+        assert_eq!(Uuid::from(success.uuid), uuid);
+
+        assert_eq!(rejection(addr, "jeb_").await?, r#"{"text":"You have to connect through the server's proxy"}"#);
+
+        Ok(())
+    }
+
+    // Answers the forwarding request like Velocity would, with `data` being `None` for a client that isn't Velocity.
+    async fn velocity_login(addr: std::net::SocketAddr, username: &str, data: Option<Vec<u8>>) -> Result<ClientConnection> {
+        let mut client = connect(addr, ConnectionState::Login).await?;
+        client.send(LoginStart { username: username.to_owned() }).await?;
+
+        let request: LoginPluginRequest = receive(&mut client).await?;
+        assert_eq!(request.channel, "velocity:player_info");
+
+        client.send(LoginPluginResponse { message_id: request.message_id, data }).await?;
+
+        Ok(client)
+    }
+
+    #[tokio::test]
+    async fn velocity_login_test() -> Result<()> {
+        // Without a secret anyone could pretend to be the proxy.
+        assert!(start(Config { forwarding: Forwarding::Velocity, ..offline() }).await.is_err());
+
+        let config = Config { forwarding: Forwarding::Velocity, forwarding_secret: "hunter2".to_owned(), ..offline() };
+        let (addr, _events) = start(config).await?;
+
+        let forwarded = Forwarded {
+            ip: "10.1.2.3".parse()?,
+            uuid: Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5),
+            name: Some("Notch".to_owned()),
+            properties: Vec::new(),
+        };
+
+        let mut client = velocity_login(addr, "Notch", Some(sign_velocity(&forwarded, "hunter2")?)).await?;
+        let success = finish_login(&mut client).await?;
+
+        assert_eq!(Uuid::from(success.uuid), forwarded.uuid);
+        assert_eq!(success.username, "Notch");
+
+        for data in [Some(sign_velocity(&forwarded, "hunter3")?), None] {
+            let mut client = velocity_login(addr, "jeb_", data).await?;
+
+            let disconnect: Disconnect = receive(&mut client).await?;
+            assert_eq!(disconnect.reason, r#"{"text":"You have to connect through the server's proxy"}"#);
+        }
+
+        Ok(())
+    }
+}
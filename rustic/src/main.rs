@@ -1,8 +1,55 @@
+use std::path::Path;
+
 use anyhow::Result;
+use env_logger::Env;
+use log::{debug, info};
+use rustic_io::connection::ConnectionEvent;
+use rustic_systems::network;
+use rustic_systems::tick::Game;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use config::Config;
+use server::Server;
+
+mod auth;
+mod config;
+mod forwarding;
+mod login;
+mod play;
+mod server;
+mod status;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("Hello, world!");
+    // `RUST_LOG=debug` shows every connection going through its states.
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+
+    let config = Config::load(Path::new("rustic.toml"))?;
+    let listener = TcpListener::bind(config.address).await?;
+
+    let (events, mut received) = mpsc::unbounded_channel();
+
+    // Players reach the game through the network handle, this is only for the log.
+    tokio::spawn(async move {
+        while let Some(event) = received.recv().await {
+            match event {
+                ConnectionEvent::LoggedIn { id, username, uuid } => {
+                    info!("{} logged in with UUID {} (connection {})", username, uuid, id)
+                }
+                ConnectionEvent::Opened { id, addr } => debug!("connection {} opened from {}", id, addr),
+                ConnectionEvent::StateChanged { id, from, to } => debug!("connection {} went from {:?} to {:?}", id, from, to),
+                ConnectionEvent::Closed { id } => debug!("connection {} closed", id),
+            }
+        }
+    });
+
+    // The game ticks on the same runtime as the connections, which only talk to it through the network handle.
+    let mut game = Game::new();
+    let network = network::install(&mut game);
+    tokio::spawn(game.run());
+
+    info!("Listening on {}", listener.local_addr()?);
 
-    Ok(())
+    Server::new(config, events, network)?.run(listener).await
 }
@@ -0,0 +1,208 @@
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use rustic_io::packet::ConnectionState;
+use rustic_systems::network::Link;
+use rustic_types::protocol::{ClientboundEvent, TranslateError, Version};
+
+use crate::server::{ClientConnection, Player, Server};
+
+// Whichever of the two came first.
+enum Next {
+    Frame(i32, Bytes),
+    Send(ClientboundEvent),
+}
+
+// Hands whatever the player sends to the game as events, and sends back what the game has for them, until the
+// connection closes. `version` is the one from the handshake, it decides how packets are read and written.
+pub async fn handle(server: &Server, connection: &mut ClientConnection, player: &Player, version: &Version) -> Result<()> {
+    let network = server.network();
+    let id = connection.id();
+
+    let mut link = network.join(id, player.name.clone(), player.uuid).await;
+    let result = relay(connection, version, &mut link).await;
+    network.leave(id).await;
+
+    result
+}
+
+async fn relay(connection: &mut ClientConnection, version: &Version, link: &mut Link) -> Result<()> {
+    loop {
+        // Reading a frame can be cancelled halfway without losing any of it, so what the game sends doesn't have to wait
+        // for the client to say something.
+        let next = tokio::select! {
+            frame = connection.read_frame() => {
+                let (id, data) = frame?;
+                Next::Frame(id, data)
+            }
+            event = link.recv() => match event {
+                Some(event) => Next::Send(event),
+                // The game kicked the player, or they fell too far behind on what it sends.
+                None => bail!("{} was dropped by the game", connection.id()),
+            },
+        };
+
+        match next {
+            Next::Frame(id, data) => {
+                // Packets without an event don't concern the game.
+                if let Some(event) = version.decode_serverbound(ConnectionState::Play, id, &data)? {
+                    if link.packet(event).is_err() {
+                        bail!("{} sent more than the game could keep up with", connection.id());
+                    }
+                }
+            }
+            Next::Send(event) => match version.encode_clientbound(event) {
+                Ok((id, data)) => connection.send_frame(id, data).await?,
+                // Older clients just miss out on what their version doesn't have.
+                Err(TranslateError::Unsupported { .. }) => {}
+                Err(err) => return Err(err.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use bevy_ecs::prelude::*;
+    use rustic_io::datatypes::position::Position as BlockPosition;
+    use rustic_systems::components::{Position, Rotation, Username};
+    use rustic_systems::network::{self, BlockDig, BlockFace, BlockPlace, ChatMessage, DigStatus, Hand, PlayerMoved, SendPacket, UseItem};
+    use rustic_systems::tick::{Game, Stage};
+    use rustic_types::protocol::{ServerboundEvent, PROTOCOL_VERSION};
+    use tokio::time;
+
+    use crate::server::tests::{connect, login, offline, start_with_network};
+
+    use super::*;
+
+    fn usernames(game: &mut Game) -> Vec<String> {
+        game.world_mut().query::<&Username>().iter(game.world()).map(|username| username.0.clone()).collect()
+    }
+
+    // Ticks until the players in the game are `expected`, or gives up after a second.
+    async fn wait_for(game: &mut Game, expected: &[&str]) -> Vec<String> {
+        for _ in 0..100 {
+            game.tick();
+
+            if usernames(game) == expected {
+                break;
+            }
+
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        usernames(game)
+    }
+
+    #[tokio::test]
+    async fn join_game_test() -> Result<()> {
+        let mut game = Game::new();
+        let network = network::install(&mut game);
+
+        let (addr, _events) = start_with_network(offline(), network).await?;
+        let mut client = connect(addr, ConnectionState::Login).await?;
+        login(&mut client, "Notch").await?;
+
+        assert_eq!(wait_for(&mut game, &["Notch"]).await, ["Notch"]);
+
+        drop(client);
+        assert!(wait_for(&mut game, &[]).await.is_empty());
+
+        Ok(())
+    }
+
+    // Everything `E` that was sent in the last tick.
+    fn sent<E: Event + Clone>(game: &Game) -> Vec<E> {
+        game.world().resource::<Events<E>>().iter_current_update_events().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn relay_test() -> Result<()> {
+        // The fixture has the packets for it, even without minecraft-data.
+        let version = Version::by_protocol(PROTOCOL_VERSION).unwrap();
+
+        fn echo(mut chat: EventReader<ChatMessage>, mut packets: EventWriter<SendPacket>) {
+            for message in chat.read() {
+                packets.write(SendPacket::all(ClientboundEvent::ChatMessage { message: message.message.clone() }));
+            }
+        }
+
+        let mut game = Game::new();
+        let network = network::install(&mut game);
+        game.add_systems(Stage::GameLogic, echo);
+
+        let (addr, _events) = start_with_network(offline(), network).await?;
+        let mut client = connect(addr, ConnectionState::Login).await?;
+        login(&mut client, "Notch").await?;
+
+        wait_for(&mut game, &["Notch"]).await;
+        let notch = game.world_mut().query_filtered::<Entity, With<Username>>().single(game.world())?;
+        let location = BlockPosition::new(1, -2, 3).unwrap();
+
+        let packets = [
+            ServerboundEvent::ChatMessage { message: "hi".to_owned() },
+            ServerboundEvent::PlayerPositionAndRotation { x: 0.5, y: 64.0, z: -0.5, yaw: 90.0, pitch: -45.0, on_ground: true },
+            ServerboundEvent::PlayerRotation { yaw: 180.0, pitch: 10.0, on_ground: false },
+            ServerboundEvent::PlayerOnGround { on_ground: true },
+            ServerboundEvent::BlockDig { status: 2, location, face: 1 },
+            ServerboundEvent::BlockPlace { hand: 1, location, face: 5, cursor_x: 1.0, cursor_y: 0.5, cursor_z: 0.0, inside_block: true },
+            ServerboundEvent::UseItem { hand: 0 },
+        ];
+
+        for packet in packets {
+            let (id, data) = version.encode_serverbound(packet)?;
+            client.send_frame(id, data).await?;
+        }
+
+        let (mut moved, mut digs, mut places, mut uses) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+
+        // The use comes last, by then the game has everything.
+        for _ in 0..100 {
+            game.tick();
+            moved.extend(sent::<PlayerMoved>(&game));
+            digs.extend(sent::<BlockDig>(&game));
+            places.extend(sent::<BlockPlace>(&game));
+            uses.extend(sent::<UseItem>(&game));
+
+            if !uses.is_empty() {
+                break;
+            }
+
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let rotation = |yaw, pitch| Some(Rotation { yaw, pitch });
+        let position = Some(Position::new(0.5, 64.0, -0.5));
+        assert_eq!(
+            moved,
+            [
+                PlayerMoved { player: notch, position, rotation: rotation(90.0, -45.0), on_ground: true },
+                PlayerMoved { player: notch, position: None, rotation: rotation(180.0, 10.0), on_ground: false },
+                PlayerMoved { player: notch, position: None, rotation: None, on_ground: true },
+            ]
+        );
+        assert_eq!(digs, [BlockDig { player: notch, status: DigStatus::Finished, location, face: BlockFace::Top }]);
+        assert_eq!(
+            places,
+            [BlockPlace { player: notch, hand: Hand::Off, location, face: BlockFace::East, cursor: [1.0, 0.5, 0.0], inside_block: true }]
+        );
+        assert_eq!(uses, [UseItem { player: notch, hand: Hand::Main }]);
+
+        let block_change = ClientboundEvent::BlockChange { location, block_state: 1 };
+        let teleport = ClientboundEvent::EntityTeleport { entity_id: 1, x: 0.5, y: 64.0, z: -0.5, yaw: 64, pitch: -32, on_ground: true };
+        game.world_mut().send_event(SendPacket::to(notch, block_change.clone()));
+        game.world_mut().send_event(SendPacket::all(teleport.clone()));
+        game.tick();
+
+        let expected = [ClientboundEvent::ChatMessage { message: "hi".to_owned() }, block_change, teleport];
+
+        for expected in expected {
+            let (id, data) = time::timeout(Duration::from_secs(1), client.read_frame()).await??;
+            assert_eq!(version.decode_clientbound(ConnectionState::Play, id, &data)?, Some(expected));
+        }
+
+        Ok(())
+    }
+}
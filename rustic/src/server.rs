@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use log::warn;
+use rustic_io::connection::{Connection, ConnectionError, ConnectionEvent, ConnectionId};
+use bytes::Bytes;
+use rustic_io::datatypes::var::VarInt;
+use rustic_io::decode::{DecodeError, Reader};
+use rustic_io::packet::{ConnectionState, Packet, MAX_STRING_LEN};
+use rustic_systems::network::NetworkHandle;
+use rustic_types::protocol::handshaking::serverbound::SetProtocol;
+use rustic_types::protocol::Version;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time;
+use uuid::Uuid;
+
+use crate::auth::{HttpSessionService, Keys, ProfileProperty, SessionService};
+use crate::config::Config;
+use crate::forwarding::Forwarding;
+use crate::{login, play, status};
+
+// What vanilla allows for the server address in the handshake.
+const MAX_SERVER_HOST_LEN: usize = 255;
+
+// Like vanilla, clients that aren't in Play yet have 30 seconds for each packet. Once they're playing, it's up to the
+// game to notice if they stop answering.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type ClientConnection = Connection<OwnedReadHalf, OwnedWriteHalf>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub name: String,
+    pub uuid: Uuid,
+    // The player's own address, even behind a proxy that forwards it.
+    pub ip: IpAddr,
+    // From the session server in online mode or the proxy, empty otherwise.
+    pub properties: Vec<ProfileProperty>,
+}
+
+pub struct Server {
+    config: Config,
+    // The favicon as a data URL, ready for the status response.
+    favicon: Option<String>,
+    // Only generated if the server authenticates players itself.
+    keys: Option<Keys>,
+    session: Arc<dyn SessionService>,
+    events: UnboundedSender<ConnectionEvent>,
+    // Where players in Play go.
+    network: NetworkHandle,
+    next_id: AtomicU64,
+    // Everyone who logged in.
+    players: Mutex<HashMap<ConnectionId, Player>>,
+}
+
+impl Server {
+    pub fn new(config: Config, events: UnboundedSender<ConnectionEvent>, network: NetworkHandle) -> Result<Arc<Self>> {
+        let session = Arc::new(HttpSessionService::new(&config.session_server)?);
+
+        Self::with_session(config, events, network, session)
+    }
+
+    pub fn with_session(
+        config: Config,
+        events: UnboundedSender<ConnectionEvent>,
+        network: NetworkHandle,
+        session: Arc<dyn SessionService>,
+    ) -> Result<Arc<Self>> {
+        // Without a secret anyone could pretend to be the proxy.
+        match config.forwarding {
+            Forwarding::Velocity if config.forwarding_secret.is_empty() => bail!("Velocity forwarding needs a forwarding_secret"),
+            Forwarding::BungeeCord if config.forwarding_secret.is_empty() => {
+                bail!("BungeeCord forwarding needs BungeeGuard's token as the forwarding_secret")
+            }
+            _ => {}
+        }
+
+        let favicon = config.favicon.as_deref().map(status::load_favicon).transpose()?;
+        let keys = if config.authenticates() { Some(Keys::generate()?) } else { None };
+
+        let next_id = AtomicU64::new(0);
+
+        Ok(Arc::new(Self { config, favicon, keys, session, events, network, next_id, players: Mutex::default() }))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn favicon(&self) -> Option<&str> {
+        self.favicon.as_deref()
+    }
+
+    // `None` in offline mode or behind a proxy.
+    pub fn keys(&self) -> Option<&Keys> {
+        self.keys.as_ref()
+    }
+
+    pub fn session(&self) -> &dyn SessionService {
+        &*self.session
+    }
+
+    pub fn network(&self) -> &NetworkHandle {
+        &self.network
+    }
+
+    pub fn players(&self) -> Vec<Player> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    // Adds the player to the list until the returned guard is dropped, unless someone with the same name is on already.
+    pub fn join(&self, id: ConnectionId, player: Player) -> Option<Joined<'_>> {
+        let mut players = self.players.lock().unwrap();
+
+        // Names are unique regardless of case.
+        if players.values().any(|other| other.name.eq_ignore_ascii_case(&player.name)) {
+            return None;
+        }
+
+        players.insert(id, player);
+
+        Some(Joined { server: self, id })
+    }
+
+    pub async fn run(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            let server = self.clone();
+
+            tokio::spawn(async move {
+                match server.handle(stream, addr).await {
+                    Ok(()) => {}
+                    // Clients just close the connection once they're done, that's not worth mentioning.
+                    Err(err) if matches!(err.downcast_ref(), Some(ConnectionError::Closed)) => {}
+                    Err(err) => warn!("{}: {:#}", addr, err),
+                }
+            });
+        }
+    }
+
+    fn next_id(&self) -> ConnectionId {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    async fn handle(&self, mut stream: TcpStream, addr: SocketAddr) -> Result<()> {
+        stream.set_nodelay(true)?;
+
+        // Clients from before 1.7 don't send a handshake, they start with 0xFE.
+        let mut start = [0; 2];
+        let read = time::timeout(READ_TIMEOUT, stream.peek(&mut start)).await.map_err(|_| ConnectionError::TimedOut)??;
+
+        if read > 0 && start[0] == status::LEGACY_PING {
+            return status::handle_legacy(self, &mut stream, &start[..read]).await;
+        }
+
+        let (reader, writer) = stream.into_split();
+        let mut connection = Connection::new(self.next_id(), addr, reader, writer, self.events.clone());
+        connection.set_read_timeout(Some(READ_TIMEOUT));
+
+        // BungeeCord's forwarding appends the player's address, UUID and skin to the server address, vanilla doesn't
+        // allow anything that long.
+        let max_host_len = if self.config.forwarding == Forwarding::BungeeCord { MAX_STRING_LEN } else { MAX_SERVER_HOST_LEN };
+        let handshake = connection.read_with(|id, reader| read_handshake(id, reader, max_host_len)).await?;
+
+        match next_state(&handshake) {
+            Some(ConnectionState::Status) => {
+                connection.set_state(ConnectionState::Status)?;
+                status::handle(self, &mut connection).await
+            }
+            Some(ConnectionState::Login) => {
+                connection.set_state(ConnectionState::Login)?;
+
+                // Without definitions for its version, nothing the client sends could be understood.
+                let Some(version) = Version::by_protocol(handshake.protocol_version.0) else {
+                    return Err(login::refuse_version(&mut connection, handshake.protocol_version.0).await);
+                };
+
+                let joined = login::handle(self, &mut connection, &handshake.server_host, version).await?;
+
+                connection.set_state(ConnectionState::Play)?;
+                connection.set_read_timeout(None);
+                play::handle(self, &mut connection, &joined.player(), version).await
+            }
+            _ => Err(anyhow!("invalid next state {:?} in handshake", handshake.next_state)),
+        }
+    }
+}
+
+// The generated packet would take any address up to `MAX_STRING_LEN`, this throws out longer ones before reading them.
+fn read_handshake(id: i32, reader: &mut Reader<Bytes>, max_host_len: usize) -> Result<Option<SetProtocol>, DecodeError> {
+    if id != SetProtocol::ID {
+        return Ok(None);
+    }
+
+    Ok(Some(SetProtocol {
+        protocol_version: VarInt(reader.var_int("protocol version")?),
+        server_host: reader.string("server address", max_host_len)?.as_str().to_owned(),
+        server_port: reader.u16("server port")?,
+        next_state: VarInt(reader.var_int("next state")?),
+    }))
+}
+
+// The state the client wants to continue in, `None` if it's neither Status (1) nor Login (2).
+fn next_state(handshake: &SetProtocol) -> Option<ConnectionState> {
+    match handshake.next_state {
+        VarInt(1) => Some(ConnectionState::Status),
+        VarInt(2) => Some(ConnectionState::Login),
+        _ => None,
+    }
+}
+
+// Takes the player off the list again once the connection is over, however that happened.
+pub struct Joined<'a> {
+    server: &'a Server,
+    id: ConnectionId,
+}
+
+impl Joined<'_> {
+    pub fn player(&self) -> Player {
+        self.server.players.lock().unwrap()[&self.id].clone()
+    }
+}
+
+impl Drop for Joined<'_> {
+    fn drop(&mut self) {
+        self.server.players.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use anyhow::Result;
+    use bytes::Bytes;
+    use rustic_io::packet::Packet;
+    use rustic_systems::network;
+    use rustic_types::protocol::login::clientbound::{Compress, Success};
+    use rustic_types::protocol::login::serverbound::LoginStart;
+    use rustic_types::protocol::PROTOCOL_VERSION;
+    use rustic_systems::tick::Game;
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    use crate::auth::tests::FakeSessionService;
+
+    use super::*;
+
+    // Most tests don't care about authentication.
+    pub(crate) fn offline() -> Config {
+        Config { online_mode: false, ..Config::default() }
+    }
+
+    // A server on a random port, and a way to connect to it.
+    pub(crate) async fn start(config: Config) -> Result<(SocketAddr, UnboundedReceiver<ConnectionEvent>)> {
+        start_with_session(config, Arc::new(FakeSessionService::default())).await
+    }
+
+    pub(crate) async fn start_with_session(
+        config: Config,
+        session: Arc<dyn SessionService>,
+    ) -> Result<(SocketAddr, UnboundedReceiver<ConnectionEvent>)> {
+        // A game of its own for tests that don't look at it. It has to keep running, players are dropped once it's gone.
+        let mut game = Game::new();
+        let network = network::install(&mut game);
+        tokio::spawn(game.run());
+
+        start_with(config, network, session).await
+    }
+
+    pub(crate) async fn start_with_network(
+        config: Config,
+        network: NetworkHandle,
+    ) -> Result<(SocketAddr, UnboundedReceiver<ConnectionEvent>)> {
+        start_with(config, network, Arc::new(FakeSessionService::default())).await
+    }
+
+    async fn start_with(
+        config: Config,
+        network: NetworkHandle,
+        session: Arc<dyn SessionService>,
+    ) -> Result<(SocketAddr, UnboundedReceiver<ConnectionEvent>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (events, received) = mpsc::unbounded_channel();
+
+        tokio::spawn(Server::with_session(config, events, network, session)?.run(listener));
+
+        Ok((addr, received))
+    }
+
+    pub(crate) async fn connect(addr: SocketAddr, next_state: ConnectionState) -> Result<ClientConnection> {
+        connect_to(addr, next_state, "localhost").await
+    }
+
+    // Connects with a server address of our choosing in the handshake, like a proxy that forwards the player's.
+    pub(crate) async fn connect_to(addr: SocketAddr, next_state: ConnectionState, server_address: &str) -> Result<ClientConnection> {
+        connect_with(addr, next_state, server_address, PROTOCOL_VERSION).await
+    }
+
+    pub(crate) async fn connect_with(
+        addr: SocketAddr,
+        next_state: ConnectionState,
+        server_address: &str,
+        protocol: i32,
+    ) -> Result<ClientConnection> {
+        let stream = TcpStream::connect(addr).await?;
+        let (reader, writer) = stream.into_split();
+        // Nobody cares about the client's own events.
+        let (events, _) = mpsc::unbounded_channel();
+        let mut client = Connection::new(0, addr, reader, writer, events);
+
+        let handshake = SetProtocol {
+            protocol_version: VarInt(protocol),
+            server_host: server_address.to_owned(),
+            server_port: addr.port(),
+            next_state: VarInt(if next_state == ConnectionState::Status { 1 } else { 2 }),
+        };
+
+        client.send(handshake).await?;
+        client.set_state(next_state)?;
+
+        Ok(client)
+    }
+
+    pub(crate) async fn receive<P: Packet>(client: &mut ClientConnection) -> Result<P> {
+        let (id, data) = client.read_frame().await?;
+
+        if id != P::ID {
+            return Err(anyhow!("expected packet {:#04x}, got {:#04x}", P::ID, id));
+        }
+
+        Ok(P::decode(&data)?)
+    }
+
+    // Logs in like a client would, following Set Compression.
+    pub(crate) async fn login(client: &mut ClientConnection, username: &str) -> Result<Success> {
+        client.send(LoginStart { username: username.to_owned() }).await?;
+        finish_login(client).await
+    }
+
+    // The rest of the login, once the client is done with encryption.
+    pub(crate) async fn finish_login(client: &mut ClientConnection) -> Result<Success> {
+        loop {
+            match client.read_frame().await? {
+                (Compress::ID, data) => {
+                    let VarInt(threshold) = Compress::decode(&data)?.threshold;
+                    client.set_compression(Some(threshold as usize));
+                }
+                (Success::ID, data) => return Ok(Success::decode(&data)?),
+                (id, _) => return Err(anyhow!("unexpected packet {:#04x} during login", id)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn join_test() -> Result<()> {
+        let (addr, mut events) = start(offline()).await?;
+        let mut client = connect(addr, ConnectionState::Login).await?;
+
+        let success = login(&mut client, "Notch").await?;
+        assert_eq!(success.username, "Notch");
+
+        let mut states = Vec::new();
+
+        while states.len() < 2 {
+            match events.recv().await {
+                Some(ConnectionEvent::StateChanged { to, .. }) => states.push(to),
+                Some(ConnectionEvent::LoggedIn { username, .. }) => assert_eq!(username, "Notch"),
+                _ => {}
+            }
+        }
+
+        assert_eq!(states, [ConnectionState::Login, ConnectionState::Play]);
+
+        drop(client);
+        while !matches!(events.recv().await, Some(ConnectionEvent::Closed { .. })) {}
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_address_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let mut client = connect_to(addr, ConnectionState::Status, &"a".repeat(256)).await?;
+
+        // Only BungeeCord's forwarding needs more than 255 characters.
+        assert!(matches!(client.read_frame().await, Err(ConnectionError::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+
+        // Doesn't even send the handshake.
+        let started = time::Instant::now();
+        let mut stream = TcpStream::connect(addr).await?;
+        let mut rest = Vec::new();
+        time::timeout(READ_TIMEOUT * 2, stream.read_to_end(&mut rest)).await??;
+
+        assert!(rest.is_empty());
+        assert!(started.elapsed() >= READ_TIMEOUT);
+
+        // Stops halfway through the login.
+        let started = time::Instant::now();
+        let mut client = connect(addr, ConnectionState::Login).await?;
+
+        assert!(matches!(time::timeout(READ_TIMEOUT * 2, client.read_frame()).await?, Err(ConnectionError::Closed)));
+        assert!(started.elapsed() >= READ_TIMEOUT);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unexpected_packet_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let mut client = connect(addr, ConnectionState::Status).await?;
+
+        // There's no packet 0x05 in Status, so the server hangs up.
+        client.send_frame(0x05, Bytes::new()).await?;
+        assert!(matches!(client.read_frame().await, Err(ConnectionError::Closed)));
+
+        Ok(())
+    }
+}
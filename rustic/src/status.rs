@@ -0,0 +1,254 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use rustic_types::protocol::status::clientbound::{self, ServerInfo};
+use rustic_types::protocol::status::serverbound::{Ping, Serverbound};
+use rustic_types::protocol::{MINECRAFT_VERSION, PROTOCOL_VERSION};
+use rustic_types::text::TextComponent;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::server::{ClientConnection, Server};
+
+// The first byte of a server list ping from before 1.7, and of the answer to it.
+pub const LEGACY_PING: u8 = 0xfe;
+const LEGACY_KICK: u8 = 0xff;
+
+// Not a real protocol number, so old clients show the version name in red instead of pretending they could join.
+const LEGACY_PROTOCOL: i32 = 127;
+
+// Vanilla shows at most 12 names when hovering over the player count.
+const SAMPLE_SIZE: usize = 12;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Reads a 64x64 PNG into the data URL the status response wants.
+pub fn load_favicon(path: &Path) -> Result<String> {
+    let png = std::fs::read(path).with_context(|| format!("couldn't read the favicon {}", path.display()))?;
+
+    // The IHDR chunk always comes first, and starts with the width and height.
+    if png.len() < 24 || !png.starts_with(PNG_SIGNATURE) || &png[12..16] != b"IHDR" {
+        bail!("the favicon {} is not a PNG", path.display());
+    }
+
+    let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+
+    if (width, height) != (64, 64) {
+        bail!("the favicon {} is {}x{}, but it has to be 64x64", path.display(), width, height);
+    }
+
+    Ok(format!("data:image/png;base64,{}", base64::encode(&png)))
+}
+
+fn response(server: &Server) -> Value {
+    let players = server.players();
+    let sample: Vec<_> = players
+        .iter()
+        .take(SAMPLE_SIZE)
+        .map(|player| json!({ "name": player.name, "id": player.uuid.hyphenated().to_string() }))
+        .collect();
+
+    let mut response = json!({
+        "version": { "name": MINECRAFT_VERSION, "protocol": PROTOCOL_VERSION },
+        "players": { "max": server.config().max_players, "online": players.len(), "sample": sample },
+        // The MOTD is configured with `§` codes, like in vanilla's server.properties.
+        "description": TextComponent::from_legacy(&server.config().motd),
+    });
+
+    if let Some(favicon) = server.favicon() {
+        response["favicon"] = favicon.into();
+    }
+
+    response
+}
+
+// Answers the server list: a status request, then a ping after which the client hangs up. Like vanilla, there's only
+// one answer to the request, which can be rather big with the favicon. Asking again closes the connection.
+pub async fn handle(server: &Server, connection: &mut ClientConnection) -> Result<()> {
+    let mut answered = false;
+
+    loop {
+        match connection.read_packet(Serverbound::decode).await? {
+            Serverbound::PingStart(_) if answered => bail!("asked for the status twice"),
+            Serverbound::PingStart(_) => {
+                connection.send(ServerInfo { response: response(server).to_string() }).await?;
+                answered = true;
+            }
+            Serverbound::Ping(Ping { time }) => return Ok(connection.send(clientbound::Ping { time }).await?),
+        }
+    }
+}
+
+// Formatting codes would get mixed up with the separators of the oldest format.
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped
+}
+
+fn legacy_response(server: &Server, start: &[u8]) -> String {
+    let config = server.config();
+    let online = server.player_count();
+
+    // 1.4 to 1.6 follow the 0xFE with 0x01 and understand the longer answer, Beta 1.8 to 1.3 only send the 0xFE.
+    if start.get(1) == Some(&0x01) {
+        format!("§1\0{}\0{}\0{}\0{}\0{}", LEGACY_PROTOCOL, MINECRAFT_VERSION, config.motd, online, config.max_players)
+    } else {
+        format!("{}§{}§{}", strip_formatting(&config.motd), online, config.max_players)
+    }
+}
+
+// Answers a server list ping from before 1.7 with a kick packet, which is how those versions did it.
+pub async fn handle_legacy(server: &Server, stream: &mut TcpStream, start: &[u8]) -> Result<()> {
+    let text: Vec<u16> = legacy_response(server, start).encode_utf16().collect();
+
+    let mut response = vec![LEGACY_KICK];
+    response.extend((text.len() as u16).to_be_bytes());
+    response.extend(text.iter().flat_map(|unit| unit.to_be_bytes()));
+
+    stream.write_all(&response).await?;
+    stream.shutdown().await?;
+
+    // Closing with the rest of the ping still unread would reset the connection, maybe before the answer arrives.
+    let mut rest = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut rest)).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rustic_io::connection::ConnectionError;
+    use rustic_io::packet::ConnectionState;
+    use rustic_types::protocol::status::serverbound::PingStart;
+    use uuid::Uuid;
+
+    use crate::config::Config;
+    use crate::server::tests::{connect, login, offline, receive, start};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn status_test() -> Result<()> {
+        let config = Config { motd: "§aHello".to_owned(), max_players: 5, ..offline() };
+        let (addr, _events) = start(config).await?;
+        let mut client = connect(addr, ConnectionState::Status).await?;
+
+        client.send(PingStart {}).await?;
+        let response: ServerInfo = receive(&mut client).await?;
+        let response: Value = serde_json::from_str(&response.response)?;
+
+        assert_eq!(response["version"]["protocol"], PROTOCOL_VERSION);
+        assert_eq!(response["players"]["max"], 5);
+        assert_eq!(response["players"]["online"], 0);
+        assert_eq!(response["description"], json!({ "text": "", "extra": [{ "text": "Hello", "color": "green" }] }));
+        assert!(response.get("favicon").is_none());
+
+        client.send(Ping { time: 0x0123456789 }).await?;
+        let pong: clientbound::Ping = receive(&mut client).await?;
+        assert_eq!(pong.time, 0x0123456789);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn status_twice_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let mut client = connect(addr, ConnectionState::Status).await?;
+
+        client.send(PingStart {}).await?;
+        let _: ServerInfo = receive(&mut client).await?;
+
+        client.send(PingStart {}).await?;
+        assert!(matches!(client.read_frame().await, Err(ConnectionError::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sample_test() -> Result<()> {
+        let (addr, _events) = start(offline()).await?;
+        let mut player = connect(addr, ConnectionState::Login).await?;
+
+        // The player is on the list before it gets Login Success.
+        let success = login(&mut player, "Notch").await?;
+
+        let mut client = connect(addr, ConnectionState::Status).await?;
+        client.send(PingStart {}).await?;
+
+        let response: ServerInfo = receive(&mut client).await?;
+        let response: Value = serde_json::from_str(&response.response)?;
+
+        assert_eq!(response["players"]["online"], 1);
+        assert_eq!(response["players"]["sample"][0]["name"], "Notch");
+        assert_eq!(response["players"]["sample"][0]["id"], Uuid::from(success.uuid).hyphenated().to_string());
+
+        Ok(())
+    }
+
+    async fn legacy_ping(addr: std::net::SocketAddr, ping: &[u8]) -> Result<String> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(ping).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        assert_eq!(response[0], LEGACY_KICK);
+        assert_eq!(u16::from_be_bytes([response[1], response[2]]) as usize * 2, response.len() - 3);
+
+        let text: Vec<u16> = response[3..].chunks(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+
+        Ok(String::from_utf16(&text)?)
+    }
+
+    #[tokio::test]
+    async fn legacy_ping_test() -> Result<()> {
+        let config = Config { motd: "§aHello".to_owned(), ..offline() };
+        let (addr, _events) = start(config).await?;
+
+        let expected = ["§1", "127", "1.18.2", "§aHello", "0", "20"].join("\0");
+
+        assert_eq!(legacy_ping(addr, &[0xfe, 0x01, 0xfa]).await?, expected);
+        assert_eq!(legacy_ping(addr, &[0xfe]).await?, "Hello§0§20");
+
+        Ok(())
+    }
+
+    #[test]
+    fn favicon_test() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rustic-favicon-{}.png", std::process::id()));
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend([0, 0, 0, 13]);
+        png.extend(b"IHDR");
+        png.extend(64u32.to_be_bytes());
+        png.extend(64u32.to_be_bytes());
+
+        std::fs::write(&path, &png)?;
+        assert_eq!(load_favicon(&path)?, format!("data:image/png;base64,{}", base64::encode(&png)));
+
+        png[19] = 32;
+        std::fs::write(&path, &png)?;
+        assert!(load_favicon(&path).is_err());
+
+        std::fs::write(&path, b"GIF89a")?;
+        assert!(load_favicon(&path).is_err());
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rustic_io::datatypes::var::{VarInt, VarLong};
+use rustic_io::encode::PacketWriter;
+use rustic_io::packet::Packet;
+use rustic_io::scroll::Pwrite;
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Handshaking)]
+struct Handshake {
+    protocol_version: VarInt,
+    #[packet(max_len = 255)]
+    server_address: String,
+    server_port: u16,
+    next_state: VarInt,
+}
+
+fn handshake() -> Handshake {
+    Handshake {
+        protocol_version: VarInt(758),
+        server_address: "mc.example.com".to_owned(),
+        server_port: 25565,
+        next_state: VarInt(2),
+    }
+}
+
+// Spread over every length a VarInt or VarLong can have, negative ones included.
+fn values() -> Vec<i64> {
+    (0..64).map(|shift| (1i64 << shift) - 1).chain([-1, i64::MIN]).collect()
+}
+
+fn var(c: &mut Criterion) {
+    let ints: Vec<i32> = values().into_iter().map(|value| value as i32).collect();
+    let longs = values();
+
+    let mut group = c.benchmark_group("var_int");
+    group.throughput(Throughput::Bytes(ints.iter().map(|value| VarInt(*value).encoded_len() as u64).sum()));
+
+    group.bench_function("encoded_len", |b| {
+        b.iter(|| ints.iter().map(|value| VarInt(black_box(*value)).encoded_len()).sum::<usize>())
+    });
+    group.bench_function("pwrite", |b| {
+        let mut bytes = vec![0; ints.len() * VarInt::MAX_LEN];
+
+        b.iter(|| {
+            let mut offset = 0;
+
+            for value in &ints {
+                offset += bytes.pwrite(VarInt(black_box(*value)), offset).unwrap();
+            }
+
+            offset
+        })
+    });
+    group.bench_function("writer", |b| {
+        let mut bytes = Vec::with_capacity(ints.len() * VarInt::MAX_LEN);
+
+        b.iter(|| {
+            bytes.clear();
+            let mut writer = PacketWriter::new(&mut bytes);
+
+            for value in &ints {
+                writer.var_int(black_box(*value));
+            }
+
+            writer.len()
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("var_long");
+    group.throughput(Throughput::Bytes(longs.iter().map(|value| VarLong(*value).encoded_len() as u64).sum()));
+
+    group.bench_function("writer", |b| {
+        let mut bytes = Vec::with_capacity(longs.len() * VarLong::MAX_LEN);
+
+        b.iter(|| {
+            bytes.clear();
+            let mut writer = PacketWriter::new(&mut bytes);
+
+            for value in &longs {
+                writer.var_long(black_box(*value));
+            }
+
+            writer.len()
+        })
+    });
+    group.finish();
+}
+
+fn packet(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handshake");
+    group.throughput(Throughput::Bytes(handshake().encode().unwrap().len() as u64));
+
+    // Measuring first, then writing into a buffer of exactly that size.
+    group.bench_function("encode", |b| b.iter_batched(handshake, |packet| packet.encode().unwrap(), BatchSize::SmallInput));
+
+    // Straight into a frame in a reused buffer, like a connection's write buffer.
+    group.bench_function("writer_frame", |b| {
+        let mut bytes = Vec::with_capacity(64);
+
+        b.iter_batched(
+            handshake,
+            |packet| {
+                bytes.clear();
+                PacketWriter::packet(&mut bytes, packet).unwrap().len()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("writer_fields", |b| {
+        let mut bytes = Vec::with_capacity(64);
+
+        b.iter(|| {
+            bytes.clear();
+
+            let mut writer = PacketWriter::frame(&mut bytes, 0x00);
+            writer.var_int(black_box(758)).string(black_box("mc.example.com")).u16(25565).var_int(2);
+            writer.finish().unwrap().len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, var, packet);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use rustic_io::codec::FrameCodec;
+use tokio_util::codec::{Decoder, Encoder};
+
+// The first byte picks the compression threshold, the rest is what a client sent. Every frame that comes out has to
+// go through the codec again unchanged.
+fuzz_target!(|data: &[u8]| {
+    let Some((threshold, data)) = data.split_first() else { return };
+
+    let mut codec = FrameCodec::new();
+    codec.set_compression_threshold(match threshold {
+        0 => None,
+        threshold => Some(*threshold as usize - 1),
+    });
+
+    let mut src = BytesMut::from(data);
+
+    while let Ok(Some((id, payload))) = codec.decode(&mut src) {
+        let mut encoded = BytesMut::new();
+        codec.encode((id, payload.clone()), &mut encoded).unwrap();
+
+        assert_eq!(codec.decode(&mut encoded).unwrap(), Some((id, payload)));
+        assert!(encoded.is_empty());
+    }
+});
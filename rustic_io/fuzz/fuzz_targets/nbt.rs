@@ -0,0 +1,41 @@
+#![no_main]
+
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use libfuzzer_sys::fuzz_target;
+use rustic_io::nbt::{Flavor, Nbt, NbtError};
+
+// A lot less than `MAX_INFLATED_LEN`, so inputs that go over it are easy to find.
+const LIMIT: usize = 64 * 1024;
+
+// How much comes out of the compressed data, reading at most one byte past the limit. `None` if it isn't compressed
+// or breaks off before that.
+fn inflated_len(data: &[u8]) -> Option<usize> {
+    let decoder: Box<dyn Read> = match data {
+        [0x1f, 0x8b, ..] => Box::new(GzDecoder::new(data)),
+        [0x78, ..] => Box::new(ZlibDecoder::new(data)),
+        _ => return None,
+    };
+
+    let mut inflated = Vec::new();
+    decoder.take(LIMIT as u64 + 1).read_to_end(&mut inflated).ok()?;
+
+    Some(inflated.len())
+}
+
+// Duplicate keys collapse into one, so the input itself doesn't come back, but anything written once has to come back
+// byte for byte. Comparing bytes keeps NaNs out of the way.
+fuzz_target!(|data: &[u8]| {
+    for flavor in [Flavor::File, Flavor::Network] {
+        if let Ok(nbt) = Nbt::read(data, flavor) {
+            let bytes = nbt.write(flavor).unwrap();
+
+            assert_eq!(Nbt::read(&bytes, flavor).unwrap().write(flavor).unwrap(), bytes);
+        }
+    }
+
+    // Compressed files are refused exactly when they would inflate past the limit.
+    let too_large = matches!(Nbt::read_file_limited(data, LIMIT), Err(NbtError::TooLarge(LIMIT)));
+    assert_eq!(too_large, inflated_len(data).is_some_and(|len| len > LIMIT));
+});
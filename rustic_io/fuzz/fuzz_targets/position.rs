@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustic_io::datatypes::position::Position;
+use scroll::{Pread, Pwrite};
+
+// Every long is a valid position, which has to write back to the same long.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(position) = data.pread::<Position>(0) {
+        assert!(position.is_valid());
+
+        let mut bytes = [0; 8];
+        bytes.pwrite(position, 0).unwrap();
+        assert_eq!(bytes[..], data[..8]);
+    }
+});
@@ -0,0 +1,42 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use rustic_io::decode::{DecodeError, Reader};
+
+// A bit of everything the reader has, in the order a handshake and a login start would have it.
+fn read(reader: &mut Reader<Bytes>) -> Result<(), DecodeError> {
+    reader.var_int("protocol version")?;
+    reader.string("server address", 255)?;
+    reader.u16("server port")?;
+    reader.var_int("next state")?;
+    reader.string("username", 16)?;
+    reader.option("uuid", |reader| reader.u128("uuid"))?;
+    reader.array("properties", |reader| reader.byte_array("property"))?;
+    reader.position("position")?;
+    reader.var_long("time")?;
+
+    Ok(())
+}
+
+// Errors can't point past the data, and a cut off packet is only ever incomplete.
+fuzz_target!(|data: &[u8]| {
+    let frame = Bytes::copy_from_slice(data);
+    let mut reader = Reader::new(frame.clone());
+
+    match read(&mut reader) {
+        Ok(()) => {
+            let read = reader.offset();
+
+            for len in 0..read {
+                let error = read_error(frame.slice(..len));
+                assert!(error.is_incomplete(), "{}", error);
+            }
+        }
+        Err(error) => assert!(error.offset() <= data.len()),
+    }
+});
+
+fn read_error(frame: Bytes) -> DecodeError {
+    read(&mut Reader::new(frame)).unwrap_err()
+}
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustic_io::nbt::snbt;
+
+// Whatever parses has to print to something that parses to the same thing, compact and pretty.
+fuzz_target!(|text: &str| {
+    if let Ok(tag) = snbt::parse(text) {
+        let compact = tag.to_string();
+        assert_eq!(snbt::parse(&compact).unwrap().to_string(), compact);
+
+        let pretty = format!("{:#}", tag);
+        assert_eq!(snbt::parse(&pretty).unwrap().to_string(), compact);
+    }
+});
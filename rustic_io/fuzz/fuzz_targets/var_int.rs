@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustic_io::datatypes::var::VarInt;
+use rustic_io::decode::Reader;
+use scroll::{ctx::MeasureWith, Pread, Pwrite};
+
+// Whatever decodes has to encode to at most as many bytes, and decode to the same value again.
+fuzz_target!(|data: &[u8]| {
+    let read = data.pread::<VarInt>(0);
+    assert_eq!(read.as_ref().ok().map(|value| value.0), Reader::new(data).var_int("value").ok());
+
+    if let Ok(VarInt(value)) = read {
+        let mut bytes = [0; 5];
+        let len = bytes.pwrite(VarInt(value), 0).unwrap();
+
+        assert_eq!(len, VarInt(value).measure_with(&()));
+        assert_eq!(bytes[..len].pread::<VarInt>(0).unwrap(), VarInt(value));
+    }
+});
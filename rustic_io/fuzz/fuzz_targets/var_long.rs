@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustic_io::datatypes::var::VarLong;
+use rustic_io::decode::Reader;
+use scroll::{ctx::MeasureWith, Pread, Pwrite};
+
+// Whatever decodes has to encode to at most as many bytes, and decode to the same value again.
+fuzz_target!(|data: &[u8]| {
+    let read = data.pread::<VarLong>(0);
+    assert_eq!(read.as_ref().ok().map(|value| value.0), Reader::new(data).var_long("value").ok());
+
+    if let Ok(VarLong(value)) = read {
+        let mut bytes = [0; 10];
+        let len = bytes.pwrite(VarLong(value), 0).unwrap();
+
+        assert_eq!(len, VarLong(value).measure_with(&()));
+        assert_eq!(bytes[..len].pread::<VarLong>(0).unwrap(), VarLong(value));
+    }
+});
@@ -0,0 +1,306 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use derive_more::{Display, Error, From};
+use scroll::{ctx::TryFromCtx, Pwrite};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::compression::{Compression, CompressionError};
+use crate::datatypes::var::VarInt;
+
+// The length prefix of a frame can be at most 3 bytes long, which caps frames at 2^21 - 1 bytes.
+pub const MAX_LENGTH_PREFIX: usize = 3;
+pub const MAX_FRAME_SIZE: usize = (1 << (7 * MAX_LENGTH_PREFIX)) - 1;
+
+#[derive(Debug, Display, From, Error)]
+pub enum FrameError {
+    #[display(fmt = "I/O error: {}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "length prefix is longer than {} bytes", MAX_LENGTH_PREFIX)]
+    LengthPrefixTooLong,
+    #[display(fmt = "negative frame length {}", _0)]
+    #[from(ignore)]
+    NegativeLength(#[error(not(source))] i32),
+    #[display(fmt = "frame of {} bytes exceeds the maximum of {} bytes", size, max)]
+    #[from(ignore)]
+    FrameTooLarge { size: usize, max: usize },
+    #[display(fmt = "frame does not start with a valid packet id")]
+    MalformedPacketId,
+    #[display(fmt = "compression error: {}", _0)]
+    Compression(CompressionError),
+    #[display(fmt = "packet could not be written: {}", _0)]
+    Encode(scroll::Error),
+}
+
+// Splits a byte stream into length-prefixed frames, yielding `(packet_id, payload)` pairs.
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    max_frame_size: usize,
+    compression: Option<Compression>,
+}
+
+impl FrameCodec {
+    pub fn new() -> Self {
+        Self::with_max_frame_size(MAX_FRAME_SIZE)
+    }
+
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        Self { max_frame_size, compression: None }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    // Switches to (or back from) the compressed packet format, as Set Compression does. Takes effect on the next frame.
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression = threshold.map(Compression::new);
+    }
+
+    pub fn compression(&self) -> Option<&Compression> {
+        self.compression.as_ref()
+    }
+
+    // Returns the frame length and how many bytes its prefix took, or `None` if the prefix isn't complete yet.
+    fn peek_length(&self, src: &[u8]) -> Result<Option<(usize, usize)>, FrameError> {
+        let prefix = &src[..src.len().min(MAX_LENGTH_PREFIX)];
+
+        let (VarInt(len), read) = match VarInt::try_from_ctx(prefix, ()) {
+            Ok(res) => res,
+            // Running out of bytes before the prefix ended just means the rest hasn't arrived yet.
+            Err(_) if prefix.len() < MAX_LENGTH_PREFIX => return Ok(None),
+            Err(_) => return Err(FrameError::LengthPrefixTooLong),
+        };
+
+        if len < 0 {
+            return Err(FrameError::NegativeLength(len));
+        }
+
+        let len = len as usize;
+
+        if len > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge { size: len, max: self.max_frame_size });
+        }
+
+        Ok(Some((len, read)))
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Splits the packet id off the front of a frame.
+pub(crate) fn split_packet_id(mut frame: BytesMut) -> Result<(i32, Bytes), FrameError> {
+    let (VarInt(id), read) = VarInt::try_from_ctx(&frame[..], ())
+        .map_err(|_| FrameError::MalformedPacketId)?;
+
+    frame.advance(read);
+
+    Ok((id, frame.freeze()))
+}
+
+// Writes a VarInt to the end of `dst`.
+pub(crate) fn put_varint(dst: &mut BytesMut, value: i32) {
+    let mut bytes = [0; VarInt::MAX_LEN];
+    // A VarInt always fits in 5 bytes, so this can't fail.
+    let len = bytes.pwrite(VarInt(value), 0).unwrap();
+
+    dst.put_slice(&bytes[..len]);
+}
+
+impl Decoder for FrameCodec {
+    type Item = (i32, Bytes);
+    type Error = FrameError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let (len, read) = match self.peek_length(src)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+
+        if src.len() < read + len {
+            // Make room for the rest of the frame so the next read can fill it in one go.
+            src.reserve(read + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(read);
+        let mut frame = src.split_to(len);
+
+        if let Some(compression) = &self.compression {
+            frame = compression.decompress(frame)?;
+        }
+
+        split_packet_id(frame).map(Some)
+    }
+}
+
+impl Encoder<(i32, Bytes)> for FrameCodec {
+    type Error = FrameError;
+
+    fn encode(&mut self, (id, payload): (i32, Bytes), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Some(compression) = &self.compression {
+            let mut body = BytesMut::new();
+            compression.compress(id, &payload, &mut body)?;
+
+            if body.len() > self.max_frame_size {
+                return Err(FrameError::FrameTooLarge { size: body.len(), max: self.max_frame_size });
+            }
+
+            dst.reserve(VarInt(body.len() as i32).encoded_len() + body.len());
+            put_varint(dst, body.len() as i32);
+            dst.put_slice(&body);
+
+            return Ok(());
+        }
+
+        let len = VarInt(id).encoded_len() + payload.len();
+
+        if len > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge { size: len, max: self.max_frame_size });
+        }
+
+        dst.reserve(VarInt(len as i32).encoded_len() + len);
+        put_varint(dst, len as i32);
+        put_varint(dst, id);
+        dst.put_slice(&payload);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn frame_decode_test() -> Result<()> {
+        let mut codec = FrameCodec::new();
+        let mut src = BytesMut::from(&[0x04, 0x00, 0xde, 0xad, 0xbe][..]);
+
+        let (id, payload) = codec.decode(&mut src)?.unwrap();
+
+        assert_eq!(id, 0);
+        assert_eq!(&payload[..], &[0xde, 0xad, 0xbe]);
+        assert!(src.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_decode_partial_test() -> Result<()> {
+        let mut codec = FrameCodec::new();
+        // A 200 byte frame has a 2 byte length prefix, so split it in the middle of the prefix too.
+        let mut frame = vec![0xc8, 0x01, 0x2a];
+        frame.extend([0x11; 199]);
+
+        let mut src = BytesMut::new();
+
+        for chunk in frame.chunks(7) {
+            assert!(src.is_empty() || codec.decode(&mut src)?.is_none());
+            src.extend_from_slice(chunk);
+        }
+
+        let (id, payload) = codec.decode(&mut src)?.unwrap();
+
+        assert_eq!(id, 0x2a);
+        assert_eq!(payload.len(), 199);
+        assert!(codec.decode(&mut src)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_decode_multiple_test() -> Result<()> {
+        let mut codec = FrameCodec::new();
+        let mut src = BytesMut::from(&[0x01, 0x05, 0x03, 0x80, 0x01, 0xff, 0x02][..]);
+
+        assert_eq!(codec.decode(&mut src)?.unwrap(), (5, Bytes::new()));
+        assert_eq!(codec.decode(&mut src)?.unwrap(), (128, Bytes::from_static(&[0xff])));
+        assert!(codec.decode(&mut src)?.is_none());
+        assert_eq!(&src[..], &[0x02]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_decode_errors_test() {
+        let mut codec = FrameCodec::new();
+
+        let mut src = BytesMut::from(&[0x80, 0x80, 0x80, 0x01][..]);
+        assert!(matches!(codec.decode(&mut src), Err(FrameError::LengthPrefixTooLong)));
+
+        let mut codec = FrameCodec::with_max_frame_size(16);
+
+        let mut src = BytesMut::from(&[0x11][..]);
+        assert!(matches!(codec.decode(&mut src), Err(FrameError::FrameTooLarge { size: 17, max: 16 })));
+
+        let mut src = BytesMut::from(&[0x00][..]);
+        assert!(matches!(codec.decode(&mut src), Err(FrameError::MalformedPacketId)));
+
+        let mut src = BytesMut::from(&[0x02, 0xff, 0xff][..]);
+        assert!(matches!(codec.decode(&mut src), Err(FrameError::MalformedPacketId)));
+    }
+
+    #[test]
+    fn frame_encode_test() -> Result<()> {
+        let mut codec = FrameCodec::new();
+        let mut dst = BytesMut::new();
+
+        codec.encode((0x2a, Bytes::from_static(&[1, 2, 3])), &mut dst)?;
+        assert_eq!(&dst[..], &[0x04, 0x2a, 1, 2, 3]);
+
+        let (id, payload) = codec.decode(&mut dst)?.unwrap();
+        assert_eq!(id, 0x2a);
+        assert_eq!(&payload[..], &[1, 2, 3]);
+
+        let mut codec = FrameCodec::with_max_frame_size(2);
+        assert!(codec.encode((0x00, Bytes::from_static(&[1, 2])), &mut dst).is_err());
+
+        Ok(())
+    }
+
+    fn packets() -> impl Strategy<Value = Vec<(i32, Vec<u8>)>> {
+        prop::collection::vec((any::<i32>(), prop::collection::vec(any::<u8>(), 0..300)), 1..8)
+    }
+
+    proptest! {
+        // However the stream is cut up on the way, the same packets come out of it.
+        #[test]
+        fn frame_round_trip_test(packets in packets(), threshold in prop::option::of(0..512usize), chunk in 1..64usize) {
+            let mut codec = FrameCodec::new();
+            codec.set_compression_threshold(threshold);
+
+            let mut stream = BytesMut::new();
+            for (id, payload) in &packets {
+                codec.encode((*id, Bytes::from(payload.clone())), &mut stream)?;
+            }
+
+            let mut src = BytesMut::new();
+            let mut decoded = vec![];
+            for bytes in stream.chunks(chunk) {
+                src.extend_from_slice(bytes);
+
+                while let Some((id, payload)) = codec.decode(&mut src)? {
+                    decoded.push((id, payload.to_vec()));
+                }
+            }
+
+            prop_assert_eq!(decoded, packets);
+            prop_assert!(src.is_empty());
+        }
+
+        #[test]
+        fn frame_arbitrary_bytes_test(bytes in prop::collection::vec(any::<u8>(), 0..256), threshold in prop::option::of(0..64usize)) {
+            let mut codec = FrameCodec::new();
+            codec.set_compression_threshold(threshold);
+
+            let mut src = BytesMut::from(&bytes[..]);
+            while let Ok(Some(_)) = codec.decode(&mut src) {}
+        }
+    }
+}
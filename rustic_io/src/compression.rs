@@ -0,0 +1,265 @@
+use std::io::{Read, Write};
+
+use bytes::{Buf, BufMut, BytesMut};
+use derive_more::{Display, Error, From};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+use scroll::ctx::TryFromCtx;
+
+use crate::codec::put_varint;
+use crate::datatypes::var::VarInt;
+
+// The vanilla client and server refuse to inflate packets bigger than this.
+pub const MAX_DATA_LENGTH: usize = 8388608;
+
+#[derive(Debug, Display, From, Error)]
+pub enum CompressionError {
+    #[display(fmt = "zlib error: {}", _0)]
+    Zlib(std::io::Error),
+    #[display(fmt = "frame does not start with a valid data length")]
+    MalformedDataLength,
+    #[display(fmt = "uncompressed packet of {} bytes is not below the threshold of {} bytes", size, threshold)]
+    #[from(ignore)]
+    UncompressedTooLarge { size: usize, threshold: usize },
+    #[display(fmt = "compressed packet of {} bytes is below the threshold of {} bytes", size, threshold)]
+    #[from(ignore)]
+    BelowThreshold { size: usize, threshold: usize },
+    #[display(fmt = "data length of {} bytes exceeds the maximum of {} bytes", size, max)]
+    #[from(ignore)]
+    DataLengthTooLarge { size: usize, max: usize },
+    #[display(fmt = "packet inflated to {} bytes, expected {} bytes", actual, expected)]
+    #[from(ignore)]
+    SizeMismatch { expected: usize, actual: usize },
+}
+
+// The stage between the frame codec and packet decoding once Set Compression has been sent.
+// Every frame then starts with a `Data Length` VarInt, which is 0 for packets sent uncompressed.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    threshold: usize,
+    level: flate2::Compression,
+}
+
+impl Compression {
+    pub fn new(threshold: usize) -> Self {
+        Self { threshold, level: flate2::Compression::default() }
+    }
+
+    pub fn with_level(threshold: usize, level: u32) -> Self {
+        Self { threshold, level: flate2::Compression::new(level) }
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    // Turns a frame in the compressed format back into the packet id and data.
+    pub fn decompress(&self, mut frame: BytesMut) -> Result<BytesMut, CompressionError> {
+        let (VarInt(data_length), read) = VarInt::try_from_ctx(&frame[..], ())
+            .map_err(|_| CompressionError::MalformedDataLength)?;
+
+        if data_length < 0 {
+            return Err(CompressionError::MalformedDataLength);
+        }
+
+        frame.advance(read);
+
+        let data_length = data_length as usize;
+
+        // A data length of 0 means the rest of the frame wasn't compressed at all.
+        if data_length == 0 {
+            if frame.len() >= self.threshold {
+                return Err(CompressionError::UncompressedTooLarge { size: frame.len(), threshold: self.threshold });
+            }
+
+            return Ok(frame);
+        }
+
+        if data_length < self.threshold {
+            return Err(CompressionError::BelowThreshold { size: data_length, threshold: self.threshold });
+        }
+
+        if data_length > MAX_DATA_LENGTH {
+            return Err(CompressionError::DataLengthTooLarge { size: data_length, max: MAX_DATA_LENGTH });
+        }
+
+        // Never inflate more than one byte past the announced length, so a zip bomb can't make us allocate.
+        let mut decoder = ZlibDecoder::new(&frame[..]).take(data_length as u64 + 1);
+        // Deflate can't do much better than 1032:1, so a tiny frame claiming 8 MiB doesn't get all of it up front.
+        let mut data = Vec::with_capacity(data_length.min(frame.len().saturating_mul(1032)));
+        decoder.read_to_end(&mut data)?;
+
+        if data.len() != data_length {
+            return Err(CompressionError::SizeMismatch { expected: data_length, actual: data.len() });
+        }
+
+        Ok(BytesMut::from(&data[..]))
+    }
+
+    // Writes the `Data Length` and the (possibly compressed) packet id and data to `dst`, without the frame length.
+    pub fn compress(&self, id: i32, payload: &[u8], dst: &mut BytesMut) -> Result<(), CompressionError> {
+        let data_length = VarInt(id).encoded_len() + payload.len();
+
+        if data_length < self.threshold {
+            put_varint(dst, 0);
+            put_varint(dst, id);
+            dst.put_slice(payload);
+
+            return Ok(());
+        }
+
+        if data_length > MAX_DATA_LENGTH {
+            return Err(CompressionError::DataLengthTooLarge { size: data_length, max: MAX_DATA_LENGTH });
+        }
+
+        put_varint(dst, data_length as i32);
+
+        let mut id_bytes = BytesMut::with_capacity(5);
+        put_varint(&mut id_bytes, id);
+
+        let mut encoder = ZlibEncoder::new(dst.writer(), self.level);
+        encoder.write_all(&id_bytes)?;
+        encoder.write_all(payload)?;
+        encoder.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::Bytes;
+    use proptest::prelude::*;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::codec::{split_packet_id, FrameCodec, FrameError};
+
+    use super::*;
+
+    fn round_trip(compression: &Compression, id: i32, payload: &[u8]) -> Result<BytesMut> {
+        let mut frame = BytesMut::new();
+        compression.compress(id, payload, &mut frame)?;
+
+        let (decoded_id, decoded_payload) = split_packet_id(compression.decompress(frame.clone())?)?;
+
+        assert_eq!(decoded_id, id);
+        assert_eq!(&decoded_payload[..], payload);
+
+        Ok(frame)
+    }
+
+    #[test]
+    fn compression_round_trip_test() -> Result<()> {
+        let compression = Compression::new(64);
+
+        // Below the threshold, the data length is 0 and the packet is sent as is.
+        let frame = round_trip(&compression, 0x10, &[7; 32])?;
+        assert_eq!(&frame[..3], &[0x00, 0x10, 0x07]);
+        assert_eq!(frame.len(), 1 + 1 + 32);
+
+        // Exactly at the threshold, the packet has to be compressed.
+        let frame = round_trip(&compression, 0x10, &[7; 63])?;
+        assert_eq!(&frame[..1], &[64]);
+
+        // Way above the threshold, with a two byte data length.
+        let payload: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let frame = round_trip(&compression, 0x22, &payload)?;
+        assert_eq!(&frame[..2], &[0x81, 0x20]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compression_reject_test() -> Result<()> {
+        let compression = Compression::new(64);
+
+        // An uncompressed packet that should have been compressed.
+        let mut frame = BytesMut::new();
+        put_varint(&mut frame, 0);
+        frame.put_slice(&[0; 64]);
+        assert!(matches!(
+            compression.decompress(frame),
+            Err(CompressionError::UncompressedTooLarge { size: 64, threshold: 64 })
+        ));
+
+        // A compressed packet that announces a different length than what it inflates to.
+        let mut frame = BytesMut::new();
+        Compression::new(0).compress(0x00, &[0; 99], &mut frame)?;
+        frame[0] = 101;
+        assert!(matches!(
+            compression.decompress(frame),
+            Err(CompressionError::SizeMismatch { expected: 101, actual: 100 })
+        ));
+
+        // A compressed packet that's smaller than the threshold.
+        let mut frame = BytesMut::new();
+        Compression::new(0).compress(0x00, &[0; 9], &mut frame)?;
+        assert!(matches!(
+            compression.decompress(frame),
+            Err(CompressionError::BelowThreshold { size: 10, threshold: 64 })
+        ));
+
+        // A packet that claims to inflate to more than the protocol allows.
+        let mut frame = BytesMut::new();
+        put_varint(&mut frame, MAX_DATA_LENGTH as i32 + 1);
+        assert!(matches!(compression.decompress(frame), Err(CompressionError::DataLengthTooLarge { .. })));
+
+        // Garbage instead of a zlib stream.
+        let mut frame = BytesMut::new();
+        put_varint(&mut frame, 100);
+        frame.put_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        assert!(matches!(compression.decompress(frame), Err(CompressionError::Zlib(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn compression_codec_test() -> Result<()> {
+        let mut codec = FrameCodec::new();
+        let mut dst = BytesMut::new();
+
+        codec.set_compression_threshold(Some(16));
+
+        codec.encode((0x01, Bytes::from_static(&[1, 2, 3])), &mut dst)?;
+        codec.encode((0x02, Bytes::from(vec![9; 1000])), &mut dst)?;
+
+        // The small packet is only one byte longer than without compression, the big one is a lot smaller.
+        assert_eq!(&dst[..6], &[0x05, 0x00, 0x01, 1, 2, 3]);
+        assert!(dst.len() < 100);
+
+        assert_eq!(codec.decode(&mut dst)?.unwrap(), (0x01, Bytes::from_static(&[1, 2, 3])));
+        assert_eq!(codec.decode(&mut dst)?.unwrap(), (0x02, Bytes::from(vec![9; 1000])));
+
+        // Decoding with compression disabled sees the data length as the packet id.
+        codec.encode((0x2a, Bytes::new()), &mut dst)?;
+        codec.set_compression_threshold(None);
+        assert_eq!(codec.decode(&mut dst)?.unwrap(), (0x00, Bytes::from_static(&[0x2a])));
+
+        let mut dst = BytesMut::from(&[0x02, 0x00, 0x00][..]);
+        codec.set_compression_threshold(Some(0));
+        assert!(matches!(codec.decode(&mut dst), Err(FrameError::Compression(_))));
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn compression_property_test(id in any::<i32>(), payload in prop::collection::vec(0..4u8, 0..2048), threshold in 0..1024usize) {
+            let compression = Compression::new(threshold);
+
+            let mut frame = BytesMut::new();
+            compression.compress(id, &payload, &mut frame)?;
+
+            let (decoded_id, decoded_payload) = split_packet_id(compression.decompress(frame)?)?;
+            prop_assert_eq!(decoded_id, id);
+            prop_assert_eq!(&decoded_payload[..], &payload[..]);
+        }
+
+        #[test]
+        fn decompress_arbitrary_bytes_test(bytes in prop::collection::vec(any::<u8>(), 0..256), threshold in 0..64usize) {
+            if let Ok(packet) = Compression::new(threshold).decompress(BytesMut::from(&bytes[..])) {
+                prop_assert!(packet.len() <= MAX_DATA_LENGTH);
+            }
+        }
+    }
+}
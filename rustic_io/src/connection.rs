@@ -0,0 +1,333 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bytes::Bytes;
+use derive_more::{Display, Error, From};
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::codec::{FrameCodec, FrameError};
+use crate::datatypes::numbers::Uuid;
+use crate::decode::{DecodeError, Reader};
+use crate::encryption::{enable_read_encryption, enable_write_encryption, CipherReader, CipherWriter, SharedSecret};
+use crate::packet::{ConnectionState, Packet};
+
+pub type ConnectionId = u64;
+
+// What happens to connections, for whoever is interested (like the ECS, to spawn players once they're in Play).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    Opened { id: ConnectionId, addr: SocketAddr },
+    StateChanged { id: ConnectionId, from: ConnectionState, to: ConnectionState },
+    // Sent by whoever handles the login, right before the connection goes to Play.
+    LoggedIn { id: ConnectionId, username: String, uuid: Uuid },
+    Closed { id: ConnectionId },
+}
+
+#[derive(Debug, Display, From, Error)]
+pub enum ConnectionError {
+    #[display(fmt = "{}", _0)]
+    Frame(FrameError),
+    #[display(fmt = "malformed packet: {}", _0)]
+    Packet(scroll::Error),
+    #[display(fmt = "malformed packet: {}", _0)]
+    Decode(DecodeError),
+    #[display(fmt = "packet {:#04x} isn't valid in the {:?} state", id, state)]
+    #[from(ignore)]
+    UnexpectedPacket { state: ConnectionState, id: i32 },
+    #[display(fmt = "can't go from the {:?} state to {:?}", from, to)]
+    #[from(ignore)]
+    InvalidTransition { from: ConnectionState, to: ConnectionState },
+    #[display(fmt = "can't send a {:?} packet in the {:?} state", packet, state)]
+    #[from(ignore)]
+    WrongState { packet: ConnectionState, state: ConnectionState },
+    #[display(fmt = "connection closed")]
+    Closed,
+    #[display(fmt = "timed out waiting for a packet")]
+    TimedOut,
+}
+
+// Handshaking can only go to Status or Login, and only Login goes on to Play.
+fn valid_transition(from: ConnectionState, to: ConnectionState) -> bool {
+    use ConnectionState::*;
+
+    matches!((from, to), (Handshaking, Status) | (Handshaking, Login) | (Login, Play))
+}
+
+// One client's connection, which knows what state it's in and reports every change of it.
+pub struct Connection<R, W> {
+    id: ConnectionId,
+    addr: SocketAddr,
+    state: ConnectionState,
+    reader: FramedRead<CipherReader<R>, FrameCodec>,
+    writer: FramedWrite<CipherWriter<W>, FrameCodec>,
+    events: UnboundedSender<ConnectionEvent>,
+    read_timeout: Option<Duration>,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> Connection<R, W> {
+    pub fn new(id: ConnectionId, addr: SocketAddr, reader: R, writer: W, events: UnboundedSender<ConnectionEvent>) -> Self {
+        // Nobody listening is fine, the connection works the same.
+        let _ = events.send(ConnectionEvent::Opened { id, addr });
+
+        Self {
+            id,
+            addr,
+            state: ConnectionState::Handshaking,
+            reader: FramedRead::new(CipherReader::new(reader), FrameCodec::new()),
+            writer: FramedWrite::new(CipherWriter::new(writer), FrameCodec::new()),
+            events,
+            read_timeout: None,
+        }
+    }
+
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    // Where the connection comes from, which is the proxy's address if there is one.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, to: ConnectionState) -> Result<(), ConnectionError> {
+        let from = self.state;
+
+        if !valid_transition(from, to) {
+            return Err(ConnectionError::InvalidTransition { from, to });
+        }
+
+        self.state = to;
+        let _ = self.events.send(ConnectionEvent::StateChanged { id: self.id, from, to });
+
+        Ok(())
+    }
+
+    // Lets the server tell everyone about things only it knows, like who logged in.
+    pub fn report(&self, event: ConnectionEvent) {
+        let _ = self.events.send(event);
+    }
+
+    // Compresses (and expects compressed) packets of at least `threshold` bytes from the next one on, like after
+    // Set Compression. `None` goes back to no compression.
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.reader.decoder_mut().set_compression_threshold(threshold);
+        self.writer.encoder_mut().set_compression_threshold(threshold);
+    }
+
+    // Encrypts both directions from the next byte on, like after Encryption Response.
+    pub fn enable_encryption(&mut self, secret: &SharedSecret) {
+        enable_read_encryption(&mut self.reader, secret);
+        enable_write_encryption(&mut self.writer, secret);
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.reader.get_ref().is_encrypted()
+    }
+
+    // How long to wait for each packet before giving up with `TimedOut`. `None`, the default, waits forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    // The next packet's id and payload. `Closed` if the client hung up between two packets.
+    pub async fn read_frame(&mut self) -> Result<(i32, Bytes), ConnectionError> {
+        let frame = match self.read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.reader.next()).await.map_err(|_| ConnectionError::TimedOut)?,
+            None => self.reader.next().await,
+        };
+
+        match frame {
+            Some(frame) => Ok(frame?),
+            None => Err(ConnectionError::Closed),
+        }
+    }
+
+    // The next packet, decoded with `decode` (like the generated `Serverbound` enums), which has to know its id.
+    pub async fn read_packet<T>(
+        &mut self,
+        decode: impl FnOnce(i32, &[u8]) -> Result<Option<T>, scroll::Error>,
+    ) -> Result<T, ConnectionError> {
+        let (id, data) = self.read_frame().await?;
+
+        decode(id, &data)?.ok_or(ConnectionError::UnexpectedPacket { state: self.state, id })
+    }
+
+    // Like `read_packet`, but for packets read field by field, for when a field's limits aren't known until runtime.
+    // Strings and byte arrays point into the frame instead of being copied.
+    pub async fn read_with<T>(
+        &mut self,
+        read: impl FnOnce(i32, &mut Reader<Bytes>) -> Result<Option<T>, DecodeError>,
+    ) -> Result<T, ConnectionError> {
+        let (id, data) = self.read_frame().await?;
+        let mut reader = Reader::new(data);
+
+        let packet = read(id, &mut reader)?.ok_or(ConnectionError::UnexpectedPacket { state: self.state, id })?;
+        reader.finish()?;
+
+        Ok(packet)
+    }
+
+    pub async fn send<P: Packet>(&mut self, packet: P) -> Result<(), ConnectionError> {
+        if P::STATE != self.state {
+            return Err(ConnectionError::WrongState { packet: P::STATE, state: self.state });
+        }
+
+        self.send_frame(P::ID, packet.encode()?).await
+    }
+
+    // Sends a packet that was already encoded, e.g. one of a specific version's, without checking the state.
+    pub async fn send_frame(&mut self, id: i32, data: Bytes) -> Result<(), ConnectionError> {
+        Ok(self.writer.send((id, data)).await?)
+    }
+}
+
+impl<R, W> Drop for Connection<R, W> {
+    fn drop(&mut self) {
+        let _ = self.events.send(ConnectionEvent::Closed { id: self.id });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tokio::sync::mpsc;
+
+    use crate::datatypes::var::VarInt;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Handshaking)]
+    struct Handshake {
+        protocol_version: VarInt,
+        server_address: String,
+        server_port: u16,
+        next_state: VarInt,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Status)]
+    struct StatusRequest;
+
+    fn decode<P: Packet>(id: i32, data: &[u8]) -> Result<Option<P>, scroll::Error> {
+        if id == P::ID {
+            Ok(Some(P::decode(data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:25565".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn state_machine_test() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let (events, mut received) = mpsc::unbounded_channel();
+
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut server = Connection::new(1, addr(), server_read, server_write, events.clone());
+
+        let (client_read, client_write) = tokio::io::split(client);
+        let mut client = Connection::new(2, addr(), client_read, client_write, events);
+
+        let handshake = Handshake {
+            protocol_version: VarInt(758),
+            server_address: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+
+        client.send(handshake).await?;
+
+        let handshake: Handshake = server.read_packet(decode).await?;
+        assert_eq!(handshake.next_state, VarInt(1));
+
+        server.set_state(ConnectionState::Status)?;
+        assert!(matches!(server.set_state(ConnectionState::Play), Err(ConnectionError::InvalidTransition { .. })));
+
+        // Status packets can't be sent before the handshake.
+        assert!(matches!(client.send(StatusRequest).await, Err(ConnectionError::WrongState { .. })));
+
+        // Neither does an id that doesn't exist in Status get through.
+        client.send_frame(0x05, Bytes::new()).await?;
+        assert!(matches!(
+            server.read_packet(decode::<StatusRequest>).await,
+            Err(ConnectionError::UnexpectedPacket { state: ConnectionState::Status, id: 0x05 })
+        ));
+
+        drop(client);
+        assert!(matches!(server.read_frame().await, Err(ConnectionError::Closed)));
+        drop(server);
+
+        let mut seen = Vec::new();
+
+        while let Some(event) = received.recv().await {
+            seen.push(event);
+        }
+
+        assert_eq!(
+            seen,
+            [
+                ConnectionEvent::Opened { id: 1, addr: addr() },
+                ConnectionEvent::Opened { id: 2, addr: addr() },
+                ConnectionEvent::StateChanged { id: 1, from: ConnectionState::Handshaking, to: ConnectionState::Status },
+                ConnectionEvent::Closed { id: 2 },
+                ConnectionEvent::Closed { id: 1 },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_with_test() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let (events, _received) = mpsc::unbounded_channel();
+
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut server = Connection::new(1, addr(), server_read, server_write, events.clone());
+
+        let (client_read, client_write) = tokio::io::split(client);
+        let mut client = Connection::new(2, addr(), client_read, client_write, events);
+
+        let read = |max_len| {
+            move |id, reader: &mut Reader<Bytes>| match id {
+                0x00 => Ok(Some((reader.var_int("protocol version")?, reader.string("server address", max_len)?))),
+                _ => Ok(None),
+            }
+        };
+
+        let handshake = Handshake {
+            protocol_version: VarInt(758),
+            server_address: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+
+        // The port and next state are left over.
+        client.send(handshake).await?;
+        assert!(matches!(server.read_with(read(255)).await, Err(ConnectionError::Decode(DecodeError::Malformed { .. }))));
+
+        client.send_frame(0x00, Bytes::from_static(&[0xf6, 0x05, 0x09, b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't'])).await?;
+        let (protocol, address) = server.read_with(read(255)).await?;
+        assert_eq!((protocol, address.as_str()), (758, "localhost"));
+
+        client.send_frame(0x00, Bytes::from_static(&[0xf6, 0x05, 0x09, b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't'])).await?;
+        let error = server.read_with(read(4)).await.unwrap_err();
+        assert_eq!(error.to_string(), "malformed packet: server address at offset 2 is malformed: string is too long");
+
+        client.send_frame(0x01, Bytes::new()).await?;
+        assert!(matches!(server.read_with(read(255)).await, Err(ConnectionError::UnexpectedPacket { id: 0x01, .. })));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use scroll::{ctx, Pread, Pwrite, BE};
+
+use crate::datatypes::var::VarInt;
+use crate::packet::{byte_array_len, read_byte_array, read_length, write_byte_array, write_length};
+
+// How optional values and arrays are read and written, for the types below and for `#[derive(Packet)]`, which has
+// fields with strings or big-endian numbers in them that need to be read in their own way.
+pub fn read_optional<'a, T>(
+    src: &'a [u8],
+    offset: &mut usize,
+    read: impl FnOnce(&'a [u8], &mut usize) -> Result<T, scroll::Error>,
+) -> Result<Option<T>, scroll::Error> {
+    match src.gread::<u8>(offset)? {
+        0 => Ok(None),
+        _ => read(src, offset).map(Some),
+    }
+}
+
+pub fn write_optional<T>(
+    dst: &mut [u8],
+    offset: &mut usize,
+    value: Option<T>,
+    write: impl FnOnce(&mut [u8], &mut usize, T) -> Result<(), scroll::Error>,
+) -> Result<(), scroll::Error> {
+    dst.gwrite(value.is_some() as u8, offset)?;
+
+    match value {
+        Some(value) => write(dst, offset, value),
+        None => Ok(()),
+    }
+}
+
+pub fn optional_len<T>(value: Option<&T>, measure: impl FnOnce(&T) -> usize) -> usize {
+    1 + value.map_or(0, measure)
+}
+
+pub fn read_prefixed<'a, T>(
+    src: &'a [u8],
+    offset: &mut usize,
+    mut read: impl FnMut(&'a [u8], &mut usize) -> Result<T, scroll::Error>,
+) -> Result<Vec<T>, scroll::Error> {
+    let len = read_length(src, offset)?;
+
+    // Every value takes at least a byte, so don't trust the count for more than what's left.
+    let mut values = Vec::with_capacity(len.min(src.len() - *offset));
+
+    for _ in 0..len {
+        values.push(read(src, offset)?);
+    }
+
+    Ok(values)
+}
+
+pub fn write_prefixed<T>(
+    dst: &mut [u8],
+    offset: &mut usize,
+    values: Vec<T>,
+    mut write: impl FnMut(&mut [u8], &mut usize, T) -> Result<(), scroll::Error>,
+) -> Result<(), scroll::Error> {
+    write_length(dst, offset, values.len())?;
+
+    for value in values {
+        write(dst, offset, value)?;
+    }
+
+    Ok(())
+}
+
+pub fn prefixed_len<T>(values: &[T], measure: impl Fn(&T) -> usize) -> usize {
+    VarInt(values.len() as i32).encoded_len() + values.iter().map(measure).sum::<usize>()
+}
+
+// A value that's only there if the bool before it is true.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Optional<T>(pub Option<T>);
+
+impl<'a, T: ctx::TryFromCtx<'a, Error = scroll::Error>> ctx::TryFromCtx<'a> for Optional<T> {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let value = read_optional(src, &mut offset, |src, offset| src.gread(offset))?;
+
+        Ok((Optional(value), offset))
+    }
+}
+
+impl<T: ctx::TryIntoCtx<Error = scroll::Error>> ctx::TryIntoCtx for Optional<T> {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        write_optional(dst, &mut offset, self.0, |dst, offset, value| dst.gwrite(value, offset).map(drop))?;
+
+        Ok(offset)
+    }
+}
+
+impl<T: ctx::MeasureWith<()>> ctx::MeasureWith<()> for Optional<T> {
+    fn measure_with(&self, ctx: &()) -> usize {
+        optional_len(self.0.as_ref(), |value| value.measure_with(ctx))
+    }
+}
+
+// A VarInt count, followed by that many values.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PrefixedArray<T>(pub Vec<T>);
+
+impl<'a, T: ctx::TryFromCtx<'a, Error = scroll::Error>> ctx::TryFromCtx<'a> for PrefixedArray<T> {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let values = read_prefixed(src, &mut offset, |src, offset| src.gread(offset))?;
+
+        Ok((PrefixedArray(values), offset))
+    }
+}
+
+impl<T: ctx::TryIntoCtx<Error = scroll::Error>> ctx::TryIntoCtx for PrefixedArray<T> {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        write_prefixed(dst, &mut offset, self.0, |dst, offset, value| dst.gwrite(value, offset).map(drop))?;
+
+        Ok(offset)
+    }
+}
+
+impl<T: ctx::MeasureWith<()>> ctx::MeasureWith<()> for PrefixedArray<T> {
+    fn measure_with(&self, ctx: &()) -> usize {
+        prefixed_len(&self.0, |value| value.measure_with(ctx))
+    }
+}
+
+// Bytes with a VarInt length in front.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ByteArray(pub Vec<u8>);
+
+impl<'a> ctx::TryFromCtx<'a> for ByteArray {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let bytes = read_byte_array(src, &mut offset)?;
+
+        Ok((ByteArray(bytes), offset))
+    }
+}
+
+impl ctx::TryIntoCtx for ByteArray {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        write_byte_array(dst, &mut offset, &self.0)?;
+
+        Ok(offset)
+    }
+}
+
+impl ctx::MeasureWith<()> for ByteArray {
+    fn measure_with(&self, _: &()) -> usize {
+        byte_array_len(&self.0)
+    }
+}
+
+// Bits packed into longs, the first bit being the lowest of the first long. Sent as a VarInt count of longs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BitSet(pub Vec<u64>);
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, bit: usize) -> bool {
+        self.0.get(bit / 64).is_some_and(|long| long & (1 << (bit % 64)) != 0)
+    }
+
+    // Grows to fit the bit if it has to.
+    pub fn set(&mut self, bit: usize, value: bool) {
+        if bit / 64 >= self.0.len() {
+            if !value {
+                return;
+            }
+
+            self.0.resize(bit / 64 + 1, 0);
+        }
+
+        if value {
+            self.0[bit / 64] |= 1 << (bit % 64);
+        } else {
+            self.0[bit / 64] &= !(1 << (bit % 64));
+        }
+    }
+
+    // Set bits, lowest first.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.0.len() * 64).filter(|bit| self.get(*bit))
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a> for BitSet {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let len = read_length(src, &mut offset)?;
+
+        let mut longs = Vec::with_capacity(len.min((src.len() - offset) / 8));
+
+        for _ in 0..len {
+            longs.push(src.gread_with(&mut offset, BE)?);
+        }
+
+        Ok((BitSet(longs), offset))
+    }
+}
+
+impl ctx::TryIntoCtx for BitSet {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        write_length(dst, &mut offset, self.0.len())?;
+
+        for long in self.0 {
+            dst.gwrite_with(long, &mut offset, BE)?;
+        }
+
+        Ok(offset)
+    }
+}
+
+impl ctx::MeasureWith<()> for BitSet {
+    fn measure_with(&self, _: &()) -> usize {
+        VarInt(self.0.len() as i32).encoded_len() + self.0.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use scroll::ctx::MeasureWith;
+
+    use super::*;
+
+    #[test]
+    fn optionals_read_write_test() -> Result<()> {
+        let vals = [(None, vec![0x00]), (Some(300), vec![0x01, 0xac, 0x02]), (Some(-1), vec![0x01, 0xff, 0xff, 0xff, 0xff, 0x0f])];
+
+        for (value, expected_bytes) in vals {
+            let optional = Optional(value.map(VarInt));
+            assert_eq!(expected_bytes.pread::<Optional<VarInt>>(0)?, optional);
+            assert_eq!(optional.measure_with(&()), expected_bytes.len());
+
+            let mut bytes = vec![0; expected_bytes.len()];
+            bytes.pwrite(optional, 0)?;
+
+            assert_eq!(bytes, expected_bytes);
+        }
+
+        // The value is missing.
+        assert!([0x01].pread::<Optional<VarInt>>(0).is_err());
+        // No room to write it.
+        assert!([0u8; 1].pwrite(Optional(Some(VarInt(300))), 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefixed_arrays_read_write_test() -> Result<()> {
+        let vals = [
+            (vec![], vec![0x00]),
+            (vec![1], vec![0x01, 0x01]),
+            (vec![0, 128, 25565], vec![0x03, 0x00, 0x80, 0x01, 0xdd, 0xc7, 0x01]),
+        ];
+
+        for (values, expected_bytes) in vals {
+            let array = PrefixedArray(values.into_iter().map(VarInt).collect());
+            assert_eq!(expected_bytes.pread::<PrefixedArray<VarInt>>(0)?, array);
+            assert_eq!(array.measure_with(&()), expected_bytes.len());
+
+            let mut bytes = vec![0; expected_bytes.len()];
+            bytes.pwrite(array, 0)?;
+
+            assert_eq!(bytes, expected_bytes);
+        }
+
+        // Claims far more values than there are bytes, and then a negative count.
+        assert!([0xff, 0xff, 0xff, 0xff, 0x07, 0x01].pread::<PrefixedArray<VarInt>>(0).is_err());
+        assert!([0xff, 0xff, 0xff, 0xff, 0x0f].pread::<PrefixedArray<VarInt>>(0).is_err());
+        assert!([0u8; 2].pwrite(PrefixedArray(vec![VarInt(1), VarInt(2)]), 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn byte_arrays_read_write_test() -> Result<()> {
+        let vals = [(vec![], vec![0x00]), (vec![0xca, 0xfe], vec![0x02, 0xca, 0xfe])];
+
+        for (value, expected_bytes) in vals {
+            assert_eq!(expected_bytes.pread::<ByteArray>(0)?, ByteArray(value.clone()));
+            assert_eq!(ByteArray(value.clone()).measure_with(&()), expected_bytes.len());
+
+            let mut bytes = vec![0; expected_bytes.len()];
+            bytes.pwrite(ByteArray(value), 0)?;
+
+            assert_eq!(bytes, expected_bytes);
+        }
+
+        assert!([0x03, 0xca, 0xfe].pread::<ByteArray>(0).is_err());
+        assert!([0u8; 2].pwrite(ByteArray(vec![0xca, 0xfe]), 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bit_sets_test() -> Result<()> {
+        let mut bits = BitSet::new();
+        bits.set(0, true);
+        bits.set(65, true);
+        bits.set(200, false);
+
+        assert_eq!(bits, BitSet(vec![0x01, 0x02]));
+        assert!(bits.get(0) && bits.get(65));
+        assert!(!bits.get(1) && !bits.get(64) && !bits.get(1000));
+        assert_eq!(bits.ones().collect::<Vec<_>>(), [0, 65]);
+
+        let expected_bytes = vec![0x02, 0, 0, 0, 0, 0, 0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x02];
+        let mut bytes = vec![0; expected_bytes.len()];
+        bytes.pwrite(bits.clone(), 0)?;
+
+        assert_eq!(bytes, expected_bytes);
+        assert_eq!(bits.measure_with(&()), expected_bytes.len());
+        assert_eq!(bytes.pread::<BitSet>(0)?, bits);
+
+        bits.set(65, false);
+        assert_eq!(bits.ones().collect::<Vec<_>>(), [0]);
+
+        assert!([0x01, 0, 0, 0].pread::<BitSet>(0).is_err());
+
+        Ok(())
+    }
+}
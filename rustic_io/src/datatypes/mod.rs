@@ -2,4 +2,13 @@
 pub mod var;
 
 // Position
-pub mod position;
\ No newline at end of file
+pub mod position;
+
+// Strings with a maximum length, and identifiers
+pub mod string;
+
+// UUID, Angle and fixed-point numbers
+pub mod numbers;
+
+// Optional values, arrays with a count in front, byte arrays and bit sets
+pub mod arrays;
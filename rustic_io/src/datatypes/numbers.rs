@@ -0,0 +1,232 @@
+use std::fmt;
+
+use scroll::{ctx, Pread, Pwrite, BE};
+
+// A UUID as the two big-endian longs the protocol sends, most significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Uuid(pub u128);
+
+impl Uuid {
+    pub fn most_significant(&self) -> u64 {
+        (self.0 >> 64) as u64
+    }
+
+    pub fn least_significant(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+impl From<u128> for Uuid {
+    fn from(value: u128) -> Self {
+        Uuid(value)
+    }
+}
+
+impl From<uuid::Uuid> for Uuid {
+    fn from(value: uuid::Uuid) -> Self {
+        Uuid(value.as_u128())
+    }
+}
+
+impl From<Uuid> for uuid::Uuid {
+    fn from(value: Uuid) -> Self {
+        uuid::Uuid::from_u128(value.0)
+    }
+}
+
+// With hyphens, like `069a79f4-44e9-4726-a5be-fca90e38aaf5`.
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+
+        write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a> for Uuid {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        Ok((Uuid(src.pread_with(0, BE)?), 16))
+    }
+}
+
+impl ctx::TryIntoCtx for Uuid {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        dst.pwrite_with(self.0, 0, BE)
+    }
+}
+
+impl ctx::MeasureWith<()> for Uuid {
+    fn measure_with(&self, _: &()) -> usize {
+        16
+    }
+}
+
+// A rotation in steps of 1/256 of a full turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Angle(pub u8);
+
+impl Angle {
+    // Any angle works, it wraps around.
+    pub fn from_degrees(degrees: f32) -> Self {
+        Angle((degrees / 360.0 * 256.0).rem_euclid(256.0) as u8)
+    }
+
+    pub fn degrees(&self) -> f32 {
+        self.0 as f32 * 360.0 / 256.0
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a> for Angle {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        Ok((Angle(src.pread(0)?), 1))
+    }
+}
+
+impl ctx::TryIntoCtx for Angle {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        dst.pwrite(self.0, 0)
+    }
+}
+
+impl ctx::MeasureWith<()> for Angle {
+    fn measure_with(&self, _: &()) -> usize {
+        1
+    }
+}
+
+// Fixed-point numbers with 5 fraction bits, which positions and relative moves were sent as before 1.9.
+const FRACTION_BITS: u32 = 5;
+
+macro_rules! fixed_point {
+    ($name:ident, $int:ty) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(pub $int);
+
+        impl $name {
+            // Rounds down, like vanilla's `MathHelper.floor` did.
+            pub fn from_f64(value: f64) -> Self {
+                $name((value * (1 << FRACTION_BITS) as f64).floor() as $int)
+            }
+
+            pub fn to_f64(self) -> f64 {
+                self.0 as f64 / (1 << FRACTION_BITS) as f64
+            }
+        }
+
+        impl<'a> ctx::TryFromCtx<'a> for $name {
+            type Error = scroll::Error;
+
+            fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+                Ok(($name(src.pread_with(0, BE)?), std::mem::size_of::<$int>()))
+            }
+        }
+
+        impl ctx::TryIntoCtx for $name {
+            type Error = scroll::Error;
+
+            fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+                dst.pwrite_with(self.0, 0, BE)
+            }
+        }
+
+        impl ctx::MeasureWith<()> for $name {
+            fn measure_with(&self, _: &()) -> usize {
+                std::mem::size_of::<$int>()
+            }
+        }
+    };
+}
+
+fixed_point!(FixedPointByte, i8);
+fixed_point!(FixedPointInt, i32);
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use scroll::ctx::MeasureWith;
+
+    use super::*;
+
+    #[test]
+    fn uuids_read_write_test() -> Result<()> {
+        let vals = [
+            (0, vec![0; 16]),
+            (0x069a79f444e94726a5befca90e38aaf5, vec![
+                0x06, 0x9a, 0x79, 0xf4, 0x44, 0xe9, 0x47, 0x26, 0xa5, 0xbe, 0xfc, 0xa9, 0x0e, 0x38, 0xaa, 0xf5,
+            ]),
+        ];
+
+        for (value, expected_bytes) in vals {
+            assert_eq!(expected_bytes.pread::<Uuid>(0)?, Uuid(value));
+
+            let mut bytes = vec![0; 16];
+            bytes.pwrite(Uuid(value), 0)?;
+
+            assert_eq!(bytes, expected_bytes);
+            assert_eq!(Uuid(value).measure_with(&()), 16);
+        }
+
+        let uuid = Uuid(0x069a79f444e94726a5befca90e38aaf5);
+        assert_eq!(uuid.to_string(), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(uuid.most_significant(), 0x069a79f444e94726);
+        assert_eq!(uuid.least_significant(), 0xa5befca90e38aaf5);
+
+        assert!([0; 15].pread::<Uuid>(0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn angles_test() -> Result<()> {
+        let vals = [(0.0, 0), (90.0, 64), (180.0, 128), (270.0, 192), (-90.0, 192), (360.0, 0), (450.0, 64)];
+
+        for (degrees, expected_value) in vals {
+            let angle = Angle::from_degrees(degrees);
+            assert_eq!(angle, Angle(expected_value));
+
+            let mut bytes = vec![0; 1];
+            bytes.pwrite(angle, 0)?;
+
+            assert_eq!(bytes, [expected_value]);
+            assert_eq!(bytes.pread::<Angle>(0)?, angle);
+        }
+
+        assert_eq!(Angle(64).degrees(), 90.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fixed_points_test() -> Result<()> {
+        let vals = [(0.0, vec![0x00, 0x00, 0x00, 0x00]), (1.5, vec![0x00, 0x00, 0x00, 0x30]), (-2.0, vec![0xff, 0xff, 0xff, 0xc0])];
+
+        for (value, expected_bytes) in vals {
+            let fixed = FixedPointInt::from_f64(value);
+            assert_eq!(fixed.to_f64(), value);
+
+            let mut bytes = vec![0; 4];
+            bytes.pwrite(fixed, 0)?;
+
+            assert_eq!(bytes, expected_bytes);
+            assert_eq!(bytes.pread::<FixedPointInt>(0)?, fixed);
+            assert_eq!(fixed.measure_with(&()), 4);
+        }
+
+        // 1/32 is as precise as it gets.
+        assert_eq!(FixedPointByte::from_f64(0.04).to_f64(), 0.03125);
+        assert_eq!(FixedPointByte::from_f64(-0.01).to_f64(), -0.03125);
+        assert_eq!(FixedPointInt::from_f64(-1.99), FixedPointInt(-64));
+        assert_eq!([0xf0].pread::<FixedPointByte>(0)?, FixedPointByte(-16));
+        assert_eq!(FixedPointByte(-16).to_f64(), -0.5);
+
+        Ok(())
+    }
+}
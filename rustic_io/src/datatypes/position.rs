@@ -1,4 +1,13 @@
-#[derive(Debug, PartialEq)]
+use scroll::{ctx, Pread, Pwrite, BE};
+
+// x and z are 26 bit signed numbers, y is a 12 bit one.
+pub const MIN_XZ: i32 = -(1 << 25);
+pub const MAX_XZ: i32 = (1 << 25) - 1;
+pub const MIN_Y: i32 = -(1 << 11);
+pub const MAX_Y: i32 = (1 << 11) - 1;
+
+// A block position, packed into a long as x, z and y from the highest bits to the lowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -6,38 +15,65 @@ pub struct Position {
 }
 
 impl Position {
-    pub fn from_u64(val: u64) -> Self {
-        let mut x = (val >> 38) as i32;
-        let mut y = (val & 0xFFF) as i32;
-        let mut z = ((val >> 12) & 0x3FFFFFF) as i32;
+    // `None` if a coordinate doesn't fit into its bits.
+    pub fn new(x: i32, y: i32, z: i32) -> Option<Self> {
+        let position = Self { x, y, z };
 
-        if x >= 2 << 25-1 { 
-            x -= 2 << 26-1; 
-        }
-
-        if y >= 2 << 11-1 { 
-            y -= 2 << 12-1; 
-        }
+        position.is_valid().then_some(position)
+    }
 
-        if z >= 2 << 25-1 { 
-            z -= 2 << 26-1; 
-        };
+    pub fn is_valid(&self) -> bool {
+        (MIN_XZ..=MAX_XZ).contains(&self.x) && (MIN_Y..=MAX_Y).contains(&self.y) && (MIN_XZ..=MAX_XZ).contains(&self.z)
+    }
 
+    pub fn from_u64(val: u64) -> Self {
+        // Shifting the sign bit of each to the top of an i64 and back fills in the bits above it.
+        let x = (val as i64 >> 38) as i32;
+        let y = ((val << 52) as i64 >> 52) as i32;
+        let z = ((val << 26) as i64 >> 38) as i32;
 
         Self { x, y, z }
     }
 
+    // Coordinates that don't fit lose their upper bits.
     pub fn to_u64(&self) -> u64 {
-        let (x, y, z) = (self.x, self.y, self.z);
+        let (x, y, z) = (self.x as u64, self.y as u64, self.z as u64);
+
+        (x & 0x3FFFFFF) << 38 | (z & 0x3FFFFFF) << 12 | y & 0xFFF
+    }
+}
 
-        let val = (((x & 0x3FFFFFF) as u64) << 38) as u64 | (((z & 0x3FFFFFF) << 12 as u64)) as u64 | (y & 0xFFF) as u64;
+impl<'a> ctx::TryFromCtx<'a> for Position {
+    type Error = scroll::Error;
 
-        val
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        Ok((Position::from_u64(src.pread_with(0, BE)?), 8))
+    }
+}
+
+impl ctx::TryIntoCtx for Position {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        if !self.is_valid() {
+            return Err(scroll::Error::BadInput { size: 8, msg: "position is outside of what can be sent" });
+        }
+
+        dst.pwrite_with(self.to_u64(), 0, BE)
+    }
+}
+
+impl ctx::MeasureWith<()> for Position {
+    fn measure_with(&self, _: &()) -> usize {
+        8
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use anyhow::Result;
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -51,11 +87,69 @@ mod tests {
 
     #[test]
     fn de_ser_position_negative() {
-        // TODO: negative positions don't pass!!
         let position = Position { x: -1560, y: -333, z: -9696 };
 
         let ulong = position.to_u64();
 
         assert_eq!(position, Position::from_u64(ulong));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn position_bits_test() {
+        assert_eq!(Position { x: -1, y: -1, z: -1 }.to_u64(), u64::MAX);
+        assert_eq!(Position { x: MIN_XZ, y: 0, z: 0 }.to_u64(), 1 << 63);
+        assert_eq!(Position { x: 0, y: MIN_Y, z: 0 }.to_u64(), 1 << 11);
+        assert_eq!(Position { x: 0, y: 0, z: MIN_XZ }.to_u64(), 1 << 37);
+        assert_eq!(Position { x: MAX_XZ, y: MAX_Y, z: MAX_XZ }.to_u64(), 0x7FFFFFDFFFFFF7FF);
+
+        // The example from the protocol documentation.
+        let position = Position { x: 18357644, y: 831, z: -20882616 };
+        assert_eq!(position.to_u64(), 0b01000110000001110110001100 << 38 | 0b10110000010101101101001000 << 12 | 0b001100111111);
+    }
+
+    #[test]
+    fn checked_position_test() {
+        assert_eq!(Position::new(MAX_XZ, MIN_Y, MIN_XZ), Some(Position { x: MAX_XZ, y: MIN_Y, z: MIN_XZ }));
+        assert_eq!(Position::new(MAX_XZ + 1, 0, 0), None);
+        assert_eq!(Position::new(0, MAX_Y + 1, 0), None);
+        assert_eq!(Position::new(0, 0, MIN_XZ - 1), None);
+    }
+
+    #[test]
+    fn position_read_write_test() -> Result<()> {
+        let mut bytes = [0; 8];
+        bytes.pwrite(Position { x: -1560, y: -333, z: -9696 }, 0)?;
+        assert_eq!(bytes.pread::<Position>(0)?, Position { x: -1560, y: -333, z: -9696 });
+        assert_eq!(u64::from_be_bytes(bytes), Position { x: -1560, y: -333, z: -9696 }.to_u64());
+
+        assert!(bytes.pwrite(Position { x: 0, y: 2048, z: 0 }, 0).is_err());
+        assert!(bytes[..7].pread::<Position>(0).is_err());
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn position_round_trip_test(x in MIN_XZ..=MAX_XZ, y in MIN_Y..=MAX_Y, z in MIN_XZ..=MAX_XZ) {
+            let position = Position::new(x, y, z).unwrap();
+
+            prop_assert_eq!(Position::from_u64(position.to_u64()), position);
+        }
+
+        #[test]
+        fn long_round_trip_test(long in any::<u64>()) {
+            let position = Position::from_u64(long);
+
+            prop_assert!(position.is_valid());
+            prop_assert_eq!(position.to_u64(), long);
+        }
+
+        #[test]
+        fn out_of_range_test(x in any::<i32>(), y in any::<i32>(), z in any::<i32>()) {
+            let fits = (MIN_XZ..=MAX_XZ).contains(&x) && (MIN_Y..=MAX_Y).contains(&y) && (MIN_XZ..=MAX_XZ).contains(&z);
+
+            prop_assert_eq!(Position::new(x, y, z).is_some(), fits);
+            prop_assert_eq!([0u8; 8].pwrite(Position { x, y, z }, 0).is_ok(), fits);
+        }
+    }
+}
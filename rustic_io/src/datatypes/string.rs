@@ -0,0 +1,220 @@
+use std::fmt;
+use std::str::FromStr;
+
+use scroll::ctx;
+
+use crate::packet::{read_string, string_len, write_string, MAX_STRING_LEN};
+
+// How many UTF-16 code units a string may have, as the context for reading and writing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxLen(pub usize);
+
+impl Default for MaxLen {
+    fn default() -> Self {
+        MaxLen(MAX_STRING_LEN)
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a, MaxLen> for String {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], MaxLen(max_len): MaxLen) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let string = read_string(src, &mut offset, max_len)?;
+
+        Ok((string, offset))
+    }
+}
+
+impl ctx::TryIntoCtx<MaxLen> for String {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], MaxLen(max_len): MaxLen) -> Result<usize, Self::Error> {
+        // The client would refuse it anyway.
+        if self.encode_utf16().count() > max_len {
+            return Err(scroll::Error::BadInput { size: self.len(), msg: "string is too long" });
+        }
+
+        let mut offset = 0;
+        write_string(dst, &mut offset, &self)?;
+
+        Ok(offset)
+    }
+}
+
+pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+// A namespaced name like `minecraft:stone`, for blocks, items, channels, dimensions and so on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Identifier {
+    namespace: String,
+    path: String,
+}
+
+fn valid_namespace(namespace: &str) -> bool {
+    !namespace.is_empty() && namespace.bytes().all(|c| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_'))
+}
+
+// Paths can have slashes too, like `textures/block/stone.png`.
+fn valid_path(path: &str) -> bool {
+    !path.is_empty() && path.bytes().all(|c| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'/'))
+}
+
+impl Identifier {
+    pub fn new(namespace: &str, path: &str) -> Option<Self> {
+        if !valid_namespace(namespace) || !valid_path(path) {
+            return None;
+        }
+
+        Some(Self { namespace: namespace.to_owned(), path: path.to_owned() })
+    }
+
+    // `stone` is `minecraft:stone`, like vanilla reads it.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            Some((namespace, path)) => Self::new(namespace, path),
+            None => Self::new(DEFAULT_NAMESPACE, text),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+impl FromStr for Identifier {
+    type Err = scroll::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text).ok_or(scroll::Error::BadInput { size: text.len(), msg: "invalid identifier" })
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a> for Identifier {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let (text, offset) = String::try_from_ctx(src, MaxLen::default())?;
+
+        Ok((text.parse()?, offset))
+    }
+}
+
+impl ctx::TryIntoCtx for Identifier {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        self.to_string().try_into_ctx(dst, MaxLen::default())
+    }
+}
+
+impl ctx::MeasureWith<()> for Identifier {
+    fn measure_with(&self, _: &()) -> usize {
+        string_len(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use scroll::ctx::MeasureWith;
+    use scroll::{Pread, Pwrite};
+
+    use super::*;
+
+    fn strings() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("", vec![0x00]),
+            ("a", vec![0x01, b'a']),
+            ("Notch", vec![0x05, b'N', b'o', b't', b'c', b'h']),
+            // Two bytes in UTF-8, but a single code unit.
+            ("ü", vec![0x02, 0xc3, 0xbc]),
+        ]
+    }
+
+    #[test]
+    fn strings_read_test() -> Result<()> {
+        for (expected_value, bytes) in strings() {
+            let result: String = bytes.pread_with(0, MaxLen(16))?;
+
+            assert_eq!(expected_value, result);
+        }
+
+        let mut long = vec![0x05];
+        long.extend(b"Notch");
+        assert!(long.pread_with::<String>(0, MaxLen(4)).is_err());
+
+        // Cut off, and not UTF-8.
+        assert!([0x05, b'N', b'o'].pread_with::<String>(0, MaxLen(16)).is_err());
+        assert!([0x01, 0xff].pread_with::<String>(0, MaxLen(16)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strings_write_test() -> Result<()> {
+        for (value, expected_value) in strings() {
+            let mut bytes = vec![0; expected_value.len()];
+
+            bytes.pwrite_with(value.to_owned(), 0, MaxLen(16))?;
+
+            assert_eq!(bytes, expected_value);
+            // Not `measure_with`, scroll already has that for strings, without the length.
+            assert_eq!(string_len(value), expected_value.len());
+        }
+
+        let mut bytes = [0; 6];
+        assert!(bytes.pwrite_with("Notch".to_owned(), 0, MaxLen(4)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn identifiers_test() -> Result<()> {
+        let stone = Identifier::parse("stone").unwrap();
+
+        assert_eq!(stone.namespace(), "minecraft");
+        assert_eq!(stone.path(), "stone");
+        assert_eq!(stone.to_string(), "minecraft:stone");
+        assert_eq!("minecraft:stone".parse::<Identifier>()?, stone);
+
+        let texture: Identifier = "rustic:textures/block/stone.png".parse()?;
+        assert_eq!(texture.namespace(), "rustic");
+        assert_eq!(texture.path(), "textures/block/stone.png");
+
+        for invalid in ["", "Stone", "minecraft:", ":stone", "a:b:c", "name space:stone", "minecraft/blocks:stone"] {
+            assert!(Identifier::parse(invalid).is_none(), "{:?} shouldn't be valid", invalid);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn identifiers_read_write_test() -> Result<()> {
+        let mut expected = vec![0x0f];
+        expected.extend(b"minecraft:stone");
+
+        let mut bytes = vec![0; expected.len()];
+        bytes.pwrite(Identifier::parse("stone").unwrap(), 0)?;
+
+        assert_eq!(bytes, expected);
+        assert_eq!(Identifier::parse("stone").unwrap().measure_with(&()), expected.len());
+        assert_eq!(bytes.pread::<Identifier>(0)?, Identifier::parse("stone").unwrap());
+
+        let mut invalid = vec![0x05];
+        invalid.extend(b"Stone");
+        assert!(invalid.pread::<Identifier>(0).is_err());
+
+        Ok(())
+    }
+}
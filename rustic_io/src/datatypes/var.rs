@@ -1,46 +1,82 @@
-use scroll::{ctx, Pread, Endian, Pwrite};
+use scroll::{ctx, Endian, Pwrite};
 use anyhow::Result;
 
 const SEGMENT_BITS: u8 = 0x7F;
 const CONTINUE_BIT: u8 = 0x80;
 
+// Why a VarInt or VarLong couldn't be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VarError {
+    // The bytes ran out before the last one.
+    Incomplete,
+    // More bytes than it takes to fill the bits.
+    TooLong,
+}
+
+impl VarError {
+    fn into_scroll(self, src_len: usize, max_len: usize, msg: &'static str) -> scroll::Error {
+        match self {
+            // What reading past the end of the slice would've said.
+            VarError::Incomplete => scroll::Error::BadOffset(src_len),
+            VarError::TooLong => scroll::Error::BadInput { size: max_len, msg },
+        }
+    }
+}
+
+// Reads 7 bits at a time from `next` until a byte without the continue bit, for both VarInts (32 bits) and VarLongs
+// (64 bits). Also used by `decode::Reader`, which doesn't have a slice to read from. Returns how many bytes it took.
+pub(crate) fn read_var(mut next: impl FnMut() -> Option<u8>, bits: u32) -> Result<(u64, usize), VarError> {
+    let mut value = 0;
+    let mut shift = 0;
+    let mut len = 0;
+
+    loop {
+        let byte = next().ok_or(VarError::Incomplete)?;
+        len += 1;
+        value |= ((byte & SEGMENT_BITS) as u64) << shift;
+
+        if byte & CONTINUE_BIT == 0 {
+            return Ok((value, len));
+        }
+
+        shift += 7;
+
+        if shift >= bits {
+            return Err(VarError::TooLong);
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct VarInt(pub i32);
 
+impl VarInt {
+    pub const MAX_LEN: usize = 5;
+
+    // How many bytes writing this takes, so buffers can be sized before writing instead of guessed.
+    pub const fn encoded_len(&self) -> usize {
+        // One byte for every started group of 7 bits, the sign bit included. 0 still takes a byte.
+        match 32 - (self.0 as u32).leading_zeros() {
+            0 => 1,
+            bits => bits.div_ceil(7) as usize,
+        }
+    }
+}
+
 impl<'a> ctx::TryFromCtx<'a> for VarInt {
     type Error = scroll::Error;
 
     // the `usize` returned here is the amount of bytes read. 
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
-        let mut value = 0;
-        let mut byte_position = 0;
-        let mut offset = 0;
-        
-        loop {
-            // VarInts are always LE.
-            let byte = src.gread_with::<u8>(&mut offset, Endian::Little)?;
+        let mut bytes = src.iter().copied();
+        let (value, offset) = read_var(|| bytes.next(), 32)
+            .map_err(|error| error.into_scroll(src.len(), Self::MAX_LEN, "VarInt is longer than 5 bytes"))?;
 
-            value |= ((byte & SEGMENT_BITS) as i32) << byte_position;
-
-            if (byte & CONTINUE_BIT) == 0 {
-                break;
-            }
-
-            byte_position += 7;
-
-            if byte_position >= 32 {
-                return Err(Self::Error::TooBig {
-                    size: value as usize, 
-                    len: src.len() 
-                });
-            }
-        } 
-
-        Ok((VarInt(value), offset))
+        Ok((VarInt(value as i32), offset))
     }
 }
 
-impl<'a> ctx::TryIntoCtx for VarInt {
+impl ctx::TryIntoCtx for VarInt {
     type Error = scroll::Error;
 
     fn try_into_ctx(self, output: &mut [u8], _: ()) -> Result<usize, Self::Error> {
@@ -64,42 +100,41 @@ impl<'a> ctx::TryIntoCtx for VarInt {
         Ok(offset)
     }
 }
+
+impl ctx::MeasureWith<()> for VarInt {
+    fn measure_with(&self, _: &()) -> usize {
+        self.encoded_len()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct VarLong(pub i64);
 
+impl VarLong {
+    pub const MAX_LEN: usize = 10;
+
+    pub const fn encoded_len(&self) -> usize {
+        match 64 - (self.0 as u64).leading_zeros() {
+            0 => 1,
+            bits => bits.div_ceil(7) as usize,
+        }
+    }
+}
+
 impl<'a> ctx::TryFromCtx<'a> for VarLong {
     type Error = scroll::Error;
 
     // the `usize` returned here is the amount of bytes read. 
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
-        let mut value = 0;
-        let mut byte_position = 0;
-        let mut offset = 0;
-        
-        loop {
-            let byte = src.gread_with::<u8>(&mut offset, Endian::Little)?;
+        let mut bytes = src.iter().copied();
+        let (value, offset) = read_var(|| bytes.next(), 64)
+            .map_err(|error| error.into_scroll(src.len(), Self::MAX_LEN, "VarLong is longer than 10 bytes"))?;
 
-            value |= ((byte & SEGMENT_BITS) as i64) << byte_position;
-
-            if byte & CONTINUE_BIT == 0 {
-                break;
-            }
-
-            byte_position += 7;
-
-            if byte_position >= 64 {
-                return Err(Self::Error::TooBig {
-                    size: value as usize, 
-                    len: src.len() 
-                });
-            }
-        } 
-
-        Ok((VarLong(value), offset))
+        Ok((VarLong(value as i64), offset))
     }
 }
 
-impl<'a> ctx::TryIntoCtx for VarLong {
+impl ctx::TryIntoCtx for VarLong {
     type Error = scroll::Error;
 
     fn try_into_ctx(self, output: &mut [u8], _: ()) -> Result<usize, Self::Error> {
@@ -124,10 +159,18 @@ impl<'a> ctx::TryIntoCtx for VarLong {
     }
 }
 
+impl ctx::MeasureWith<()> for VarLong {
+    fn measure_with(&self, _: &()) -> usize {
+        self.encoded_len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use scroll::*;
+    use scroll::ctx::MeasureWith;
     use anyhow::Result;
+    use proptest::prelude::*;
 
     use super::*;
 
@@ -174,11 +217,12 @@ mod tests {
         ];
 
         for (result, expected_value) in vals {
-            let mut bytes = vec![0; expected_value.len()];
+            let mut bytes = vec![0; VarInt(result).encoded_len()];
 
             bytes.pwrite(VarInt(result), 0)?;
 
             assert_eq!(bytes, expected_value);
+            assert_eq!(VarInt(result).measure_with(&()), expected_value.len());
         }
 
         Ok(())
@@ -228,16 +272,58 @@ mod tests {
         ];
 
         for (result, expected_value) in vals {
-            let mut bytes = vec![0; expected_value.len()];
+            let mut bytes = vec![0; VarLong(result).encoded_len()];
 
             bytes.pwrite(VarLong(result), 0)?;
 
             assert_eq!(bytes, expected_value);
+            assert_eq!(VarLong(result).measure_with(&()), expected_value.len());
         }
 
         Ok(())
     }
 
+    #[test]
+    fn var_too_long_test() {
+        let bytes = [0xff; 11];
+
+        assert!(matches!(bytes.pread::<VarInt>(0), Err(scroll::Error::BadInput { size: 5, .. })));
+        assert!(matches!(bytes.pread::<VarLong>(0), Err(scroll::Error::BadInput { size: 10, .. })));
+    }
+
+    proptest! {
+        #[test]
+        fn varint_round_trip_test(value in any::<i32>()) {
+            let mut bytes = [0; 5];
+            let written = bytes.pwrite(VarInt(value), 0)?;
+
+            prop_assert_eq!(written, VarInt(value).measure_with(&()));
+            prop_assert_eq!(bytes[..written].pread::<VarInt>(0)?, VarInt(value));
+        }
 
+        #[test]
+        fn varlong_round_trip_test(value in any::<i64>()) {
+            let mut bytes = [0; 10];
+            let written = bytes.pwrite(VarLong(value), 0)?;
 
-}
\ No newline at end of file
+            prop_assert_eq!(written, VarLong(value).measure_with(&()));
+            prop_assert_eq!(bytes[..written].pread::<VarLong>(0)?, VarLong(value));
+        }
+
+        // Never reads past the end, and nothing decodes from more bytes than it'd be written with.
+        #[test]
+        fn var_arbitrary_bytes_test(bytes in prop::collection::vec(any::<u8>(), 0..12)) {
+            let mut read = 0;
+            if let Ok(value) = bytes.gread::<VarInt>(&mut read) {
+                prop_assert!(read <= bytes.len());
+                prop_assert!(value.measure_with(&()) <= read);
+            }
+
+            let mut read = 0;
+            if let Ok(value) = bytes.gread::<VarLong>(&mut read) {
+                prop_assert!(read <= bytes.len());
+                prop_assert!(value.measure_with(&()) <= read);
+            }
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt;
+use std::ops::Deref;
+
+use bytes::{Buf, Bytes};
+use derive_more::{Display, Error};
+
+use crate::datatypes::position::Position;
+use crate::datatypes::var::{read_var, VarError};
+use crate::packet::MAX_STRING_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq, Display, Error)]
+pub enum DecodeError {
+    // The data just ends too early. More of it might still arrive, it isn't necessarily malformed.
+    #[display(fmt = "{} at offset {} needs {} more bytes", field, offset, needed)]
+    Incomplete { field: &'static str, offset: usize, needed: usize },
+    // Can't be read, no matter what comes after it.
+    #[display(fmt = "{} at offset {} is malformed: {}", field, offset, reason)]
+    Malformed { field: &'static str, offset: usize, reason: &'static str },
+}
+
+impl DecodeError {
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, DecodeError::Incomplete { .. })
+    }
+
+    // The field that couldn't be read.
+    pub fn field(&self) -> &'static str {
+        match self {
+            DecodeError::Incomplete { field, .. } | DecodeError::Malformed { field, .. } => field,
+        }
+    }
+
+    // Where that field starts.
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeError::Incomplete { offset, .. } | DecodeError::Malformed { offset, .. } => *offset,
+        }
+    }
+
+    // Errors of the bytes behind a length are about the whole field.
+    fn at(self, offset: usize) -> Self {
+        match self {
+            DecodeError::Incomplete { field, needed, .. } => DecodeError::Incomplete { field, offset, needed },
+            DecodeError::Malformed { field, reason, .. } => DecodeError::Malformed { field, offset, reason },
+        }
+    }
+}
+
+// A string that shares the memory of the frame it was read from.
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct ByteStr(Bytes);
+
+impl ByteStr {
+    pub fn from_utf8(bytes: Bytes) -> Option<Self> {
+        std::str::from_utf8(&bytes).ok()?;
+
+        Some(ByteStr(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY: Besides the empty default, `from_utf8` is the only way to make one. It checks the bytes, which never change after.
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for ByteStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl PartialEq<str> for ByteStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ByteStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// Reads protocol types off the front of a buffer. Strings and byte arrays come out of `Buf::copy_to_bytes`, which
+// for `Bytes` and `BytesMut` splits them off without copying.
+//
+// What was read before an error is gone from the buffer, so keep a clone of the frame (which is cheap for `Bytes`)
+// to start over from when more data arrives.
+pub struct Reader<B> {
+    buf: B,
+    offset: usize,
+}
+
+macro_rules! read_number {
+    ($($name:ident: $ty:ty = $get:ident),* $(,)?) => {
+        $(
+            pub fn $name(&mut self, field: &'static str) -> Result<$ty, DecodeError> {
+                self.need(field, std::mem::size_of::<$ty>())?;
+                self.offset += std::mem::size_of::<$ty>();
+
+                Ok(self.buf.$get())
+            }
+        )*
+    };
+}
+
+impl<B: Buf> Reader<B> {
+    pub fn new(buf: B) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    // How many bytes have been read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.remaining()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.buf.has_remaining()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+
+    fn need(&self, field: &'static str, len: usize) -> Result<(), DecodeError> {
+        match self.buf.remaining() {
+            remaining if remaining < len => Err(DecodeError::Incomplete { field, offset: self.offset, needed: len - remaining }),
+            _ => Ok(()),
+        }
+    }
+
+    read_number! {
+        u8: u8 = get_u8,
+        i8: i8 = get_i8,
+        u16: u16 = get_u16,
+        i16: i16 = get_i16,
+        i32: i32 = get_i32,
+        i64: i64 = get_i64,
+        u64: u64 = get_u64,
+        u128: u128 = get_u128,
+        f32: f32 = get_f32,
+        f64: f64 = get_f64,
+    }
+
+    pub fn bool(&mut self, field: &'static str) -> Result<bool, DecodeError> {
+        Ok(self.u8(field)? != 0)
+    }
+
+    pub fn position(&mut self, field: &'static str) -> Result<Position, DecodeError> {
+        Ok(Position::from_u64(self.u64(field)?))
+    }
+
+    // VarInts and VarLongs, which can't take more bytes than it takes to fill their bits.
+    fn var(&mut self, field: &'static str, bits: u32, reason: &'static str) -> Result<u64, DecodeError> {
+        let start = self.offset;
+        let (buf, offset) = (&mut self.buf, &mut self.offset);
+
+        let next = || {
+            buf.has_remaining().then(|| {
+                *offset += 1;
+                buf.get_u8()
+            })
+        };
+
+        match read_var(next, bits) {
+            Ok((value, _)) => Ok(value),
+            Err(VarError::Incomplete) => Err(DecodeError::Incomplete { field, offset: start, needed: 1 }),
+            Err(VarError::TooLong) => Err(DecodeError::Malformed { field, offset: start, reason }),
+        }
+    }
+
+    pub fn var_int(&mut self, field: &'static str) -> Result<i32, DecodeError> {
+        Ok(self.var(field, 32, "VarInt is longer than 5 bytes")? as i32)
+    }
+
+    pub fn var_long(&mut self, field: &'static str) -> Result<i64, DecodeError> {
+        Ok(self.var(field, 64, "VarLong is longer than 10 bytes")? as i64)
+    }
+
+    // A VarInt count or length, which can't be negative.
+    pub fn length(&mut self, field: &'static str) -> Result<usize, DecodeError> {
+        let start = self.offset;
+
+        match self.var_int(field)? {
+            len if len < 0 => Err(DecodeError::Malformed { field, offset: start, reason: "negative length" }),
+            len => Ok(len as usize),
+        }
+    }
+
+    pub fn bytes(&mut self, field: &'static str, len: usize) -> Result<Bytes, DecodeError> {
+        self.need(field, len)?;
+        self.offset += len;
+
+        Ok(self.buf.copy_to_bytes(len))
+    }
+
+    // Bytes with a VarInt length in front.
+    pub fn byte_array(&mut self, field: &'static str) -> Result<Bytes, DecodeError> {
+        let start = self.offset;
+        let len = self.length(field)?;
+
+        self.bytes(field, len).map_err(|error| error.at(start))
+    }
+
+    // Everything that's left.
+    pub fn rest(&mut self) -> Bytes {
+        self.offset += self.buf.remaining();
+
+        self.buf.copy_to_bytes(self.buf.remaining())
+    }
+
+    // `max_len` is in UTF-16 code units, like vanilla counts them.
+    pub fn string(&mut self, field: &'static str, max_len: usize) -> Result<ByteStr, DecodeError> {
+        let start = self.offset;
+        let len = self.length(field)?;
+
+        // A code unit never takes more than 3 bytes (characters that take 4 are 2 units), so anything longer can be thrown out
+        // before reading it, like vanilla does.
+        if len > max_len.saturating_mul(3) {
+            return Err(DecodeError::Malformed { field, offset: start, reason: "string is too long" });
+        }
+
+        let bytes = self.bytes(field, len).map_err(|error| error.at(start))?;
+        let string = ByteStr::from_utf8(bytes)
+            .ok_or(DecodeError::Malformed { field, offset: start, reason: "string is not valid UTF-8" })?;
+
+        if string.encode_utf16().count() > max_len {
+            return Err(DecodeError::Malformed { field, offset: start, reason: "string is too long" });
+        }
+
+        Ok(string)
+    }
+
+    // Strings without a limit of their own still can't be longer than this.
+    pub fn any_string(&mut self, field: &'static str) -> Result<ByteStr, DecodeError> {
+        self.string(field, MAX_STRING_LEN)
+    }
+
+    // A VarInt count, then that many values read by `read`.
+    pub fn array<T>(
+        &mut self,
+        field: &'static str,
+        mut read: impl FnMut(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Vec<T>, DecodeError> {
+        let len = self.length(field)?;
+        // Every value takes at least a byte, so don't trust the count for more than what's left.
+        let mut values = Vec::with_capacity(len.min(self.buf.remaining()));
+
+        for _ in 0..len {
+            values.push(read(self)?);
+        }
+
+        Ok(values)
+    }
+
+    // A bool, then the value if it's true.
+    pub fn option<T>(
+        &mut self,
+        field: &'static str,
+        read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        match self.bool(field)? {
+            true => read(self).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    // Packets have to take up their whole frame.
+    pub fn finish(self) -> Result<(), DecodeError> {
+        match self.buf.has_remaining() {
+            true => Err(DecodeError::Malformed { field: "end of packet", offset: self.offset, reason: "packet has trailing bytes" }),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use proptest::prelude::*;
+    use scroll::Pread;
+
+    use crate::datatypes::var::{VarInt, VarLong};
+
+    use super::*;
+
+    fn handshake() -> Vec<u8> {
+        let mut bytes = vec![0xf6, 0x05, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x01]);
+        bytes
+    }
+
+    #[test]
+    fn reader_test() -> Result<()> {
+        let frame = Bytes::from(handshake());
+        let mut reader = Reader::new(frame.clone());
+
+        assert_eq!(reader.var_int("protocol version")?, 758);
+        let address = reader.string("server address", 255)?;
+        assert_eq!(address, "localhost");
+        assert_eq!(reader.offset(), 12);
+        assert_eq!(reader.u16("server port")?, 25565);
+        assert_eq!(reader.var_int("next state")?, 1);
+        reader.finish()?;
+
+        // The address points into the frame.
+        assert_eq!(address.as_ptr(), frame[3..].as_ptr());
+
+        // Same for a buffer that's still being filled.
+        let mut buf = BytesMut::from(&handshake()[..]);
+        buf.extend_from_slice(&[0xca, 0xfe]);
+        let start = buf.as_ptr();
+        let mut reader = Reader::new(buf);
+
+        reader.var_int("protocol version")?;
+        assert_eq!(reader.any_string("server address")?.as_ptr(), start.wrapping_add(3));
+        reader.bytes("port and state", 3)?;
+        assert_eq!(&reader.rest()[..], [0xca, 0xfe]);
+        assert!(reader.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn collections_test() -> Result<()> {
+        let mut reader = Reader::new(&[0x02, 0x01, 0x7f, 0x00, 0x00, 0x03, 1, 2, 3][..]);
+
+        let values = reader.array("values", |reader| reader.option("value", |reader| reader.i8("value")))?;
+        assert_eq!(values, [Some(127), None]);
+        assert!(!reader.bool("flag")?);
+        assert_eq!(&reader.byte_array("data")?[..], [1, 2, 3]);
+        reader.finish()?;
+
+        // A count that's a lot bigger than what's there doesn't allocate for all of it.
+        let mut reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0x07, 0x00][..]);
+        let error = reader.array("values", |reader| reader.u8("value")).unwrap_err();
+        assert_eq!(error, DecodeError::Incomplete { field: "value", offset: 6, needed: 1 });
+
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_test() {
+        let bytes = handshake();
+
+        for len in 0..bytes.len() {
+            let mut reader = Reader::new(&bytes[..len]);
+
+            let error = (|| {
+                reader.var_int("protocol version")?;
+                reader.string("server address", 255)?;
+                reader.u16("server port")?;
+                reader.var_int("next state")
+            })()
+            .unwrap_err();
+
+            assert!(error.is_incomplete(), "{} bytes: {}", len, error);
+        }
+
+        let mut reader = Reader::new(&bytes[..5]);
+        reader.var_int("protocol version").unwrap();
+        assert_eq!(
+            reader.string("server address", 255),
+            Err(DecodeError::Incomplete { field: "server address", offset: 2, needed: 7 })
+        );
+
+        let mut reader = Reader::new(&[0x80, 0x80][..]);
+        assert_eq!(reader.var_int("id"), Err(DecodeError::Incomplete { field: "id", offset: 0, needed: 1 }));
+    }
+
+    #[test]
+    fn malformed_test() {
+        let mut reader = Reader::new(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01][..]);
+        reader.u8("flag").unwrap();
+        let error = reader.var_int("count").unwrap_err();
+        assert_eq!(error, DecodeError::Malformed { field: "count", offset: 1, reason: "VarInt is longer than 5 bytes" });
+        assert_eq!(error.to_string(), "count at offset 1 is malformed: VarInt is longer than 5 bytes");
+
+        let mut reader = Reader::new(&[0xff; 11][..]);
+        assert!(!reader.var_long("time").unwrap_err().is_incomplete());
+
+        let mut reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0x0f][..]);
+        assert_eq!(reader.length("count").unwrap_err().to_string(), "count at offset 0 is malformed: negative length");
+
+        // Too long before and after reading it, and not UTF-8.
+        let mut reader = Reader::new(&[0x05, b'a', b'b', b'c', b'd', b'e'][..]);
+        assert!(!reader.string("name", 1).unwrap_err().is_incomplete());
+
+        let mut reader = Reader::new(&[0x02, b'a', b'b'][..]);
+        assert_eq!(reader.string("name", 1).unwrap_err().field(), "name");
+
+        // Up to 3 bytes a code unit may still be there, more is too long without waiting for it.
+        assert!(Reader::new(&[0x03][..]).string("name", 1).unwrap_err().is_incomplete());
+        assert!(!Reader::new(&[0x04][..]).string("name", 1).unwrap_err().is_incomplete());
+        assert_eq!(Reader::new(&[0x03, 0xe2, 0x82, 0xac][..]).string("name", 1).unwrap(), "€");
+        assert_eq!(Reader::new(&[0x01, b'a'][..]).string("name", usize::MAX).unwrap(), "a");
+
+        let mut reader = Reader::new(&[0x01, 0xff][..]);
+        assert_eq!(
+            reader.string("name", 16),
+            Err(DecodeError::Malformed { field: "name", offset: 0, reason: "string is not valid UTF-8" })
+        );
+
+        let mut reader = Reader::new(&[0x01, 0x02][..]);
+        reader.u8("flag").unwrap();
+        assert_eq!(reader.finish().unwrap_err().offset(), 1);
+    }
+
+    proptest! {
+        #[test]
+        fn var_agrees_with_scroll_test(bytes in prop::collection::vec(any::<u8>(), 0..12)) {
+            let mut reader = Reader::new(&bytes[..]);
+            let mut read = 0;
+            match (reader.var_int("value"), bytes.gread::<VarInt>(&mut read)) {
+                (Ok(value), Ok(VarInt(expected))) => prop_assert_eq!((value, reader.offset()), (expected, read)),
+                (value, expected) => prop_assert!(value.is_err() && expected.is_err()),
+            }
+
+            let mut reader = Reader::new(&bytes[..]);
+            let mut read = 0;
+            match (reader.var_long("value"), bytes.gread::<VarLong>(&mut read)) {
+                (Ok(value), Ok(VarLong(expected))) => prop_assert_eq!((value, reader.offset()), (expected, read)),
+                (value, expected) => prop_assert!(value.is_err() && expected.is_err()),
+            }
+        }
+
+        // A buffer in two pieces reads the same as one that isn't, wherever it was split.
+        #[test]
+        fn chained_buf_test(bytes in prop::collection::vec(any::<u8>(), 0..64), split in any::<prop::sample::Index>()) {
+            fn read<B: Buf>(reader: &mut Reader<B>) -> Result<(i32, String, i64, Bytes), DecodeError> {
+                Ok((
+                    reader.var_int("id")?,
+                    reader.string("name", 16)?.as_str().to_owned(),
+                    reader.i64("time")?,
+                    reader.byte_array("data")?,
+                ))
+            }
+
+            let (front, back) = bytes.split_at(split.index(bytes.len() + 1));
+
+            let mut whole = Reader::new(&bytes[..]);
+            let mut chained = Reader::new(front.chain(back));
+            prop_assert_eq!(read(&mut whole), read(&mut chained));
+            prop_assert_eq!(whole.offset(), chained.offset());
+        }
+    }
+}
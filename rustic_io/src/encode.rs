@@ -0,0 +1,409 @@
+use bytes::{BufMut, BytesMut};
+use scroll::ctx::{MeasureWith, TryIntoCtx};
+
+use crate::codec::{FrameError, MAX_FRAME_SIZE, MAX_LENGTH_PREFIX};
+use crate::datatypes::position::Position;
+use crate::datatypes::var::{VarInt, VarLong};
+use crate::packet::Packet;
+
+// Buffers a `PacketWriter` can grow and go back into.
+pub trait PacketBuf: BufMut {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn truncate(&mut self, len: usize);
+
+    fn as_mut_slice(&mut self) -> &mut [u8];
+}
+
+impl PacketBuf for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl PacketBuf for BytesMut {
+    fn len(&self) -> usize {
+        BytesMut::len(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        BytesMut::truncate(self, len)
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<T: PacketBuf> PacketBuf for &mut T {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        (**self).truncate(len)
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        (**self).as_mut_slice()
+    }
+}
+
+// Appends protocol types to the end of a buffer that grows as needed, so nothing has to be measured beforehand.
+//
+// Started with `frame`, it writes a whole uncompressed frame: room for the length prefix is kept in front of the
+// packet id and filled in by `finish`. Since the length isn't known until then, the prefix always takes
+// `MAX_LENGTH_PREFIX` bytes, with continuation bits on the ones it wouldn't need. Vanilla (and `FrameCodec`) read
+// those the same as the shortest form.
+//
+// Those frames are only any good until Set Compression, after which every frame needs the uncompressed length in
+// front of the packet id. Connections with compression have to go through `FrameCodec` with `new` payloads instead.
+pub struct PacketWriter<B> {
+    buf: B,
+    start: usize,
+    framed: bool,
+}
+
+macro_rules! write_number {
+    ($($name:ident: $ty:ty = $put:ident),* $(,)?) => {
+        $(
+            pub fn $name(&mut self, value: $ty) -> &mut Self {
+                self.buf.$put(value);
+                self
+            }
+        )*
+    };
+}
+
+impl<B: PacketBuf> PacketWriter<B> {
+    // Writes a packet's payload, like what goes into `FrameCodec` next to the packet id.
+    pub fn new(buf: B) -> Self {
+        Self { start: buf.len(), buf, framed: false }
+    }
+
+    pub fn frame(mut buf: B, id: i32) -> Self {
+        let start = buf.len();
+        buf.put_bytes(0, MAX_LENGTH_PREFIX);
+
+        let mut writer = Self { buf, start, framed: true };
+        writer.var_int(id);
+        writer
+    }
+
+    // Writes a whole packet as a frame, or nothing at all if it can't be written.
+    pub fn packet<P: Packet>(buf: B, packet: P) -> Result<B, FrameError> {
+        let mut writer = Self::frame(buf, P::ID);
+
+        if let Err(error) = writer.value(packet) {
+            writer.buf.truncate(writer.start);
+            return Err(error.into());
+        }
+
+        writer.finish()
+    }
+
+    // How many bytes have been written, without the length prefix.
+    pub fn len(&self) -> usize {
+        self.buf.len() - self.start - if self.framed { MAX_LENGTH_PREFIX } else { 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    write_number! {
+        u8: u8 = put_u8,
+        i8: i8 = put_i8,
+        u16: u16 = put_u16,
+        i16: i16 = put_i16,
+        i32: i32 = put_i32,
+        i64: i64 = put_i64,
+        u64: u64 = put_u64,
+        u128: u128 = put_u128,
+        f32: f32 = put_f32,
+        f64: f64 = put_f64,
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u8(value as u8)
+    }
+
+    // Coordinates that don't fit lose their upper bits, `Position::new` is where to check them.
+    pub fn position(&mut self, position: Position) -> &mut Self {
+        self.u64(position.to_u64())
+    }
+
+    // VarInts and VarLongs, written by their own `TryIntoCtx` into a buffer that always fits them.
+    fn var(&mut self, value: impl TryIntoCtx<Error = scroll::Error>) -> &mut Self {
+        let mut bytes = [0; VarLong::MAX_LEN];
+        let len = value.try_into_ctx(&mut bytes, ()).unwrap();
+
+        self.buf.put_slice(&bytes[..len]);
+        self
+    }
+
+    pub fn var_int(&mut self, value: i32) -> &mut Self {
+        self.var(VarInt(value))
+    }
+
+    pub fn var_long(&mut self, value: i64) -> &mut Self {
+        self.var(VarLong(value))
+    }
+
+    // A VarInt count or length.
+    pub fn length(&mut self, len: usize) -> &mut Self {
+        self.var_int(len as i32)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.put_slice(bytes);
+        self
+    }
+
+    // Bytes with a VarInt length in front.
+    pub fn byte_array(&mut self, bytes: &[u8]) -> &mut Self {
+        self.length(bytes.len()).bytes(bytes)
+    }
+
+    pub fn string(&mut self, string: &str) -> &mut Self {
+        self.byte_array(string.as_bytes())
+    }
+
+    // A VarInt count, then every value written by `write`.
+    pub fn array<T>(&mut self, values: impl ExactSizeIterator<Item = T>, mut write: impl FnMut(&mut Self, T)) -> &mut Self {
+        self.length(values.len());
+
+        for value in values {
+            write(self, value);
+        }
+
+        self
+    }
+
+    // A bool, then the value if there is one.
+    pub fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) -> &mut Self {
+        self.bool(value.is_some());
+
+        if let Some(value) = value {
+            write(self, value);
+        }
+
+        self
+    }
+
+    // Anything scroll can write, like `VarInt`, `BitSet` or a `#[derive(Packet)]` struct. Nothing is left of it if
+    // it fails.
+    pub fn value<T: TryIntoCtx<Error = scroll::Error> + MeasureWith<()>>(&mut self, value: T) -> Result<&mut Self, scroll::Error> {
+        let offset = self.buf.len();
+        self.buf.put_bytes(0, value.measure_with(&()));
+
+        // Not `pwrite`, which refuses to write anything at all at the very end of the buffer.
+        if let Err(error) = value.try_into_ctx(&mut self.buf.as_mut_slice()[offset..], ()) {
+            self.buf.truncate(offset);
+            return Err(error);
+        }
+
+        Ok(self)
+    }
+
+    // Fills in the length prefix of a frame, which can't be longer than `MAX_FRAME_SIZE`.
+    // A frame that's too long is taken out of the buffer again.
+    pub fn finish(mut self) -> Result<B, FrameError> {
+        if !self.framed {
+            return Ok(self.buf);
+        }
+
+        let len = self.len();
+
+        if len > MAX_FRAME_SIZE {
+            self.buf.truncate(self.start);
+            return Err(FrameError::FrameTooLarge { size: len, max: MAX_FRAME_SIZE });
+        }
+
+        let prefix = &mut self.buf.as_mut_slice()[self.start..self.start + MAX_LENGTH_PREFIX];
+        prefix[0] = (len & 0x7f) as u8 | 0x80;
+        prefix[1] = (len >> 7 & 0x7f) as u8 | 0x80;
+        prefix[2] = (len >> 14) as u8;
+
+        Ok(self.buf)
+    }
+
+    // The buffer as it is, without filling in a frame's length prefix.
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::Bytes;
+    use tokio_util::codec::Decoder;
+
+    use crate::codec::FrameCodec;
+    use crate::datatypes::arrays::BitSet;
+    use crate::datatypes::var::VarInt;
+    use crate::decode::Reader;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, crate::packet::Packet)]
+    #[packet(id = 0x00, state = Handshaking)]
+    struct Handshake {
+        protocol_version: VarInt,
+        #[packet(max_len = 255)]
+        server_address: String,
+        server_port: u16,
+        next_state: VarInt,
+    }
+
+    #[derive(Debug, PartialEq, crate::packet::Packet)]
+    #[packet(id = 0x0c, state = Play)]
+    struct BlockChange {
+        location: Position,
+        block: VarInt,
+    }
+
+    fn handshake() -> Vec<u8> {
+        let mut bytes = vec![0xf6, 0x05, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x01]);
+        bytes
+    }
+
+    #[test]
+    fn writer_test() -> Result<()> {
+        let mut writer = PacketWriter::new(Vec::new());
+        writer.var_int(758).string("localhost").u16(25565).var_int(1);
+        assert_eq!(writer.len(), 15);
+        assert_eq!(writer.finish()?, handshake());
+
+        let mut writer = PacketWriter::new(BytesMut::new());
+        writer
+            .bool(true)
+            .position(Position { x: -1560, y: -333, z: -9696 })
+            .var_long(-1)
+            .option(Some(7), |writer, value| { writer.i32(value); })
+            .option(None::<i32>, |writer, value| { writer.i32(value); })
+            .array(["a", "bc"].into_iter(), |writer, value| { writer.string(value); })
+            .byte_array(&[1, 2])
+            .f32(0.5);
+
+        let mut reader = Reader::new(writer.finish()?.freeze());
+        assert!(reader.bool("flag")?);
+        assert_eq!(reader.position("position")?, Position { x: -1560, y: -333, z: -9696 });
+        assert_eq!(reader.var_long("time")?, -1);
+        assert_eq!(reader.option("some", |reader| reader.i32("value"))?, Some(7));
+        assert_eq!(reader.option("none", |reader| reader.i32("value"))?, None);
+        assert_eq!(reader.array("strings", |reader| reader.any_string("string"))?, ["a", "bc"]);
+        assert_eq!(reader.byte_array("data")?, Bytes::from_static(&[1, 2]));
+        assert_eq!(reader.f32("angle")?, 0.5);
+        reader.finish()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn var_test() -> Result<()> {
+        let mut writer = PacketWriter::new(Vec::new());
+        writer.var_int(0).var_int(-1).var_int(i32::MIN).var_long(i64::MAX).var_long(-1);
+
+        let bytes = writer.finish()?;
+        let mut reader = Reader::new(&bytes[..]);
+        assert_eq!(reader.var_int("zero")?, 0);
+        assert_eq!(reader.offset(), 1);
+        assert_eq!(reader.var_int("minus one")?, -1);
+        assert_eq!(reader.var_int("min")?, i32::MIN);
+        assert_eq!(reader.offset(), 11);
+        assert_eq!(reader.var_long("max")?, i64::MAX);
+        assert_eq!(reader.var_long("minus one")?, -1);
+        assert_eq!(reader.offset(), 30);
+        reader.finish()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_test() -> Result<()> {
+        // Appends to what's already there, like other frames.
+        let mut stream = BytesMut::from(&[0x01, 0x00][..]);
+
+        let mut writer = PacketWriter::frame(&mut stream, 0x2a);
+        writer.bytes(&[1, 2, 3]);
+        writer.finish()?;
+
+        let packet = Handshake {
+            protocol_version: VarInt(758),
+            server_address: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+        PacketWriter::packet(&mut stream, packet)?;
+
+        assert_eq!(&stream[..9], &[0x01, 0x00, 0x84, 0x80, 0x00, 0x2a, 1, 2, 3]);
+
+        let mut codec = FrameCodec::new();
+        assert_eq!(codec.decode(&mut stream)?, Some((0x00, Bytes::new())));
+        assert_eq!(codec.decode(&mut stream)?, Some((0x2a, Bytes::from_static(&[1, 2, 3]))));
+        assert_eq!(codec.decode(&mut stream)?, Some((0x00, Bytes::from(handshake()))));
+        assert!(stream.is_empty());
+
+        // The biggest length the prefix can hold, and one more.
+        let mut writer = PacketWriter::frame(Vec::new(), 0x00);
+        writer.bytes(&vec![0; MAX_FRAME_SIZE - 1]);
+        assert_eq!(&writer.finish()?[..3], &[0xff, 0xff, 0x7f]);
+
+        let mut writer = PacketWriter::frame(Vec::new(), 0x00);
+        writer.bytes(&vec![0; MAX_FRAME_SIZE]);
+        assert!(matches!(writer.finish(), Err(FrameError::FrameTooLarge { .. })));
+
+        // Neither a packet that can't be written nor a frame that's too long leaves anything behind.
+        let mut stream = vec![0x01, 0x00];
+        let packet = BlockChange { location: Position { x: 0, y: 2048, z: 0 }, block: VarInt(1) };
+        assert!(PacketWriter::packet(&mut stream, packet).is_err());
+        assert_eq!(stream, [0x01, 0x00]);
+
+        let mut writer = PacketWriter::frame(&mut stream, 0x00);
+        writer.bytes(&vec![0; MAX_FRAME_SIZE]);
+        assert!(writer.finish().is_err());
+        assert_eq!(stream, [0x01, 0x00]);
+
+        // Once there's compression, the packet id would be read as the uncompressed length.
+        let mut writer = PacketWriter::frame(BytesMut::new(), 0x2a);
+        writer.bytes(&[1, 2, 3]);
+        let mut stream = writer.finish()?;
+
+        let mut codec = FrameCodec::new();
+        codec.set_compression_threshold(Some(0));
+        assert!(codec.decode(&mut stream).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn value_test() -> Result<()> {
+        let mut bits = BitSet::new();
+        bits.set(3, true);
+
+        let mut writer = PacketWriter::new(Vec::new());
+        writer.value(VarInt(300))?.value(bits)?;
+
+        // What can't be written doesn't leave anything behind.
+        assert!(writer.value(Position { x: 0, y: 2048, z: 0 }).is_err());
+        assert_eq!(writer.finish()?, [0xac, 0x02, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x08]);
+
+        Ok(())
+    }
+}
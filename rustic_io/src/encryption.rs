@@ -0,0 +1,270 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use aes::cipher::{inout::InOutBuf, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+pub type Encryptor = cfb8::Encryptor<aes::Aes128>;
+pub type Decryptor = cfb8::Decryptor<aes::Aes128>;
+
+// The shared secret from Encryption Response is used as both the key and the IV.
+pub type SharedSecret = [u8; 16];
+
+pub fn encryptor(secret: &SharedSecret) -> Encryptor {
+    Encryptor::new(secret.into(), secret.into())
+}
+
+pub fn decryptor(secret: &SharedSecret) -> Decryptor {
+    Decryptor::new(secret.into(), secret.into())
+}
+
+// CFB8 works on single byte blocks, so any slice can be processed in place.
+pub fn encrypt_in_place(cipher: &mut Encryptor, data: &mut [u8]) {
+    let (blocks, _) = InOutBuf::from(data).into_chunks();
+    cipher.encrypt_blocks_inout_mut(blocks);
+}
+
+pub fn decrypt_in_place(cipher: &mut Decryptor, data: &mut [u8]) {
+    let (blocks, _) = InOutBuf::from(data).into_chunks();
+    cipher.decrypt_blocks_inout_mut(blocks);
+}
+
+// Reading half of a connection that starts out in plaintext and can switch to AES/CFB8 at any point.
+pub struct CipherReader<R> {
+    inner: R,
+    cipher: Option<Decryptor>,
+}
+
+impl<R> CipherReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, cipher: None }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    // Everything read from now on is decrypted. `buffered` are the bytes that were already read past the switch
+    // point (e.g. the read buffer of a `FramedRead`), they get decrypted in place so they aren't lost.
+    pub fn enable_encryption(&mut self, secret: &SharedSecret, buffered: &mut [u8]) {
+        let mut cipher = decryptor(secret);
+        decrypt_in_place(&mut cipher, buffered);
+
+        self.cipher = Some(cipher);
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CipherReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        if let Some(cipher) = &mut self.cipher {
+            decrypt_in_place(cipher, &mut buf.filled_mut()[filled..]);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Writing half of a connection that starts out in plaintext and can switch to AES/CFB8 at any point.
+pub struct CipherWriter<W> {
+    inner: W,
+    cipher: Option<Encryptor>,
+    // Bytes that already went through the cipher but haven't been written yet. The cipher is stateful, so once a
+    // byte has been encrypted it has to be written eventually, even if the inner writer only accepts part of it.
+    pending: BytesMut,
+}
+
+impl<W> CipherWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, cipher: None, pending: BytesMut::new() }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    // Everything written from now on is encrypted. `queued` are bytes that were meant to go out before the switch
+    // point (e.g. the write buffer of a `FramedWrite`), they're still sent in plaintext.
+    pub fn enable_encryption(&mut self, secret: &SharedSecret, queued: &[u8]) {
+        self.pending.extend_from_slice(queued);
+        self.cipher = Some(encryptor(secret));
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + Unpin> CipherWriter<W> {
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+
+            self.pending.advance(written);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CipherWriter<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+
+        if this.cipher.is_none() && this.pending.is_empty() {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+
+        ready!(this.poll_write_pending(cx))?;
+
+        let start = this.pending.len();
+        this.pending.extend_from_slice(buf);
+
+        if let Some(cipher) = &mut this.cipher {
+            encrypt_in_place(cipher, &mut this.pending[start..]);
+        }
+
+        // The bytes are ours now, so try to get them out but don't make the caller wait for it.
+        if let Poll::Ready(Err(err)) = this.poll_write_pending(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// Switches a framed reading half to encryption without losing the bytes it has buffered but not decoded yet.
+pub fn enable_read_encryption<R, D>(framed: &mut FramedRead<CipherReader<R>, D>, secret: &SharedSecret) {
+    let mut buffered = framed.read_buffer_mut().split();
+
+    framed.get_mut().enable_encryption(secret, &mut buffered);
+    framed.read_buffer_mut().unsplit(buffered);
+}
+
+// Switches a framed writing half to encryption. Frames that were encoded but not flushed yet still go out in plaintext.
+pub fn enable_write_encryption<W, E>(framed: &mut FramedWrite<CipherWriter<W>, E>, secret: &SharedSecret) {
+    let queued = framed.write_buffer_mut().split();
+
+    framed.get_mut().enable_encryption(secret, &queued);
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::codec::FrameCodec;
+
+    use super::*;
+
+    const SECRET: SharedSecret = [0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c];
+
+    #[test]
+    fn cfb8_test_vector() {
+        // Taken from NIST SP 800-38A, F.3.7 (CFB8-AES128.Encrypt)
+        let iv = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f];
+        let plaintext = [0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a, 0xae, 0x2d];
+        let ciphertext = [0x3b, 0x79, 0x42, 0x4c, 0x9c, 0x0d, 0xd4, 0x36, 0xba, 0xce, 0x9e, 0x0e, 0xd4, 0x58, 0x6a, 0x4f, 0x32, 0xb9];
+
+        let mut data = plaintext;
+        encrypt_in_place(&mut Encryptor::new(&SECRET.into(), &iv.into()), &mut data);
+        assert_eq!(data, ciphertext);
+
+        // Decrypting in two goes has to give the same result as in one.
+        let mut decryptor = Decryptor::new(&SECRET.into(), &iv.into());
+        decrypt_in_place(&mut decryptor, &mut data[..5]);
+        decrypt_in_place(&mut decryptor, &mut data[5..]);
+        assert_eq!(data, plaintext);
+    }
+
+    #[tokio::test]
+    async fn cipher_stream_test() -> Result<()> {
+        let (client, server) = tokio::io::duplex(64);
+        let mut writer = CipherWriter::new(client);
+        let mut reader = CipherReader::new(server);
+
+        writer.write_all(b"plain").await?;
+        writer.enable_encryption(&SECRET, &[]);
+        writer.write_all(b"secret").await?;
+        writer.flush().await?;
+
+        let mut buf = [0; 11];
+        reader.read_exact(&mut buf[..8]).await?;
+
+        assert_eq!(&buf[..5], b"plain");
+        assert_ne!(&buf[5..8], b"sec");
+
+        // Decrypting what was read past the switch point has to line up with everything read afterwards.
+        reader.enable_encryption(&SECRET, &mut buf[5..8]);
+        reader.read_exact(&mut buf[8..]).await?;
+
+        assert_eq!(&buf[5..], b"secret");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cipher_framed_test() -> Result<()> {
+        let (client, server) = tokio::io::duplex(4096);
+        let mut writer = FramedWrite::new(CipherWriter::new(client), FrameCodec::new());
+        let mut reader = FramedRead::new(CipherReader::new(server), FrameCodec::new());
+
+        // The plaintext frame is still queued when the writer switches, the rest is encrypted.
+        writer.feed((0x01, Bytes::from_static(b"encryption response"))).await?;
+        enable_write_encryption(&mut writer, &SECRET);
+        writer.feed((0x02, Bytes::from_static(b"login success"))).await?;
+        writer.feed((0x03, Bytes::from(vec![0xab; 300]))).await?;
+        writer.flush().await?;
+
+        // All of it arrives in a single read, so the encrypted frames are in the read buffer before the switch.
+        let (id, payload) = reader.next().await.unwrap()?;
+        assert_eq!((id, &payload[..]), (0x01, &b"encryption response"[..]));
+        assert!(!reader.read_buffer().is_empty());
+
+        enable_read_encryption(&mut reader, &SECRET);
+
+        let (id, payload) = reader.next().await.unwrap()?;
+        assert_eq!((id, &payload[..]), (0x02, &b"login success"[..]));
+
+        let (id, payload) = reader.next().await.unwrap()?;
+        assert_eq!((id, payload), (0x03, Bytes::from(vec![0xab; 300])));
+
+        Ok(())
+    }
+}
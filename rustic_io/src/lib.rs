@@ -1 +1,30 @@
-pub mod datatypes;
\ No newline at end of file
+// Lets the code generated by `rustic_utils` refer to `::rustic_io` from inside this crate too.
+extern crate self as rustic_io;
+
+pub use scroll;
+
+pub mod datatypes;
+
+// Splitting the TCP stream into frames
+pub mod codec;
+
+// Compressing and decompressing frames after Set Compression
+pub mod compression;
+
+// AES/CFB8 stream encryption for online mode
+pub mod encryption;
+
+// Named Binary Tag, for chunks, items, registries and world files, with serde support
+pub mod nbt;
+
+// Reading packets straight from `Bytes`, without copying strings and byte arrays
+pub mod decode;
+
+// Writing packets and whole frames into a growable buffer
+pub mod encode;
+
+// The `Packet` trait and the helpers used by `#[derive(Packet)]`
+pub mod packet;
+
+// A client's connection and the states it goes through
+pub mod connection;
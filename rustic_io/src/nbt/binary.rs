@@ -0,0 +1,518 @@
+use scroll::{ctx, Pread, BE};
+
+use super::{id, Compound, Flavor, Nbt, NbtError, Tag, MAX_DEPTH};
+
+struct Reader<'a> {
+    src: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn number<N: ctx::TryFromCtx<'a, scroll::Endian, Error = scroll::Error>>(&mut self) -> Result<N, NbtError> {
+        Ok(self.src.gread_with(&mut self.offset, BE)?)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], NbtError> {
+        let bytes = self
+            .src
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(scroll::Error::TooBig { size: len, len: self.src.len() - self.offset.min(self.src.len()) })?;
+        self.offset += len;
+
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, NbtError> {
+        let len = self.number::<u16>()?;
+        let bytes = self.bytes(len as usize)?;
+
+        from_modified_utf8(bytes).ok_or(NbtError::InvalidString)
+    }
+
+    // Arrays and lists have an int count, which can't be trusted for more than what's left to read.
+    fn len(&mut self, min_size: usize) -> Result<(usize, usize), NbtError> {
+        let len = self.number::<i32>()?;
+
+        if len < 0 {
+            return Err(NbtError::NegativeLength(len));
+        }
+
+        let left = (self.src.len() - self.offset) / min_size.max(1);
+
+        Ok((len as usize, (len as usize).min(left)))
+    }
+
+    fn array<N: ctx::TryFromCtx<'a, scroll::Endian, Error = scroll::Error>>(&mut self) -> Result<Vec<N>, NbtError> {
+        let (len, capacity) = self.len(std::mem::size_of::<N>())?;
+        let mut values = Vec::with_capacity(capacity);
+
+        for _ in 0..len {
+            values.push(self.number()?);
+        }
+
+        Ok(values)
+    }
+
+    fn tag(&mut self, id: u8, depth: usize) -> Result<Tag, NbtError> {
+        Ok(match id {
+            id::BYTE => Tag::Byte(self.number()?),
+            id::SHORT => Tag::Short(self.number()?),
+            id::INT => Tag::Int(self.number()?),
+            id::LONG => Tag::Long(self.number()?),
+            id::FLOAT => Tag::Float(self.number()?),
+            id::DOUBLE => Tag::Double(self.number()?),
+            id::BYTE_ARRAY => Tag::ByteArray(self.array()?),
+            id::STRING => Tag::String(self.string()?),
+            id::LIST => Tag::List(self.list(depth + 1)?),
+            id::COMPOUND => Tag::Compound(self.compound(depth + 1)?),
+            id::INT_ARRAY => Tag::IntArray(self.array()?),
+            id::LONG_ARRAY => Tag::LongArray(self.array()?),
+            _ => return Err(NbtError::UnknownTag(id)),
+        })
+    }
+
+    fn list(&mut self, depth: usize) -> Result<Vec<Tag>, NbtError> {
+        if depth > MAX_DEPTH {
+            return Err(NbtError::TooDeep);
+        }
+
+        let element = self.number::<u8>()?;
+        let (len, capacity) = self.len(1)?;
+
+        // Empty lists are usually written with the end tag as their type, but that's only allowed for empty ones.
+        if element == id::END && len > 0 {
+            return Err(NbtError::UnknownTag(id::END));
+        }
+
+        let mut tags = Vec::with_capacity(capacity);
+
+        for _ in 0..len {
+            tags.push(self.tag(element, depth)?);
+        }
+
+        Ok(tags)
+    }
+
+    fn compound(&mut self, depth: usize) -> Result<Compound, NbtError> {
+        if depth > MAX_DEPTH {
+            return Err(NbtError::TooDeep);
+        }
+
+        let mut entries = Vec::new();
+
+        loop {
+            let id = self.number::<u8>()?;
+
+            if id == id::END {
+                return Ok(entries.into_iter().collect());
+            }
+
+            let name = self.string()?;
+            let tag = self.tag(id, depth)?;
+            entries.push((name, tag));
+        }
+    }
+}
+
+pub(super) fn read(src: &[u8], flavor: Flavor) -> Result<(Nbt, usize), NbtError> {
+    let mut reader = Reader { src, offset: 0 };
+
+    let id = reader.number::<u8>()?;
+
+    if id != id::COMPOUND {
+        return Err(NbtError::RootNotCompound(id));
+    }
+
+    let name = match flavor {
+        Flavor::File => reader.string()?,
+        Flavor::Network => String::new(),
+    };
+
+    let root = reader.compound(0)?;
+
+    Ok((Nbt { name, root }, reader.offset))
+}
+
+fn write_string(dst: &mut Vec<u8>, string: &str) -> Result<(), NbtError> {
+    let bytes = to_modified_utf8(string);
+
+    if bytes.len() > u16::MAX as usize {
+        return Err(NbtError::StringTooLong(bytes.len()));
+    }
+
+    dst.extend((bytes.len() as u16).to_be_bytes());
+    dst.extend(bytes);
+
+    Ok(())
+}
+
+fn write_len(dst: &mut Vec<u8>, len: usize) -> Result<(), NbtError> {
+    let len = i32::try_from(len).map_err(|_| scroll::Error::TooBig { size: len, len: i32::MAX as usize })?;
+    dst.extend(len.to_be_bytes());
+
+    Ok(())
+}
+
+fn write_tag(dst: &mut Vec<u8>, tag: &Tag, depth: usize) -> Result<(), NbtError> {
+    if depth > MAX_DEPTH {
+        return Err(NbtError::TooDeep);
+    }
+
+    match tag {
+        Tag::Byte(value) => dst.extend(value.to_be_bytes()),
+        Tag::Short(value) => dst.extend(value.to_be_bytes()),
+        Tag::Int(value) => dst.extend(value.to_be_bytes()),
+        Tag::Long(value) => dst.extend(value.to_be_bytes()),
+        Tag::Float(value) => dst.extend(value.to_be_bytes()),
+        Tag::Double(value) => dst.extend(value.to_be_bytes()),
+        Tag::ByteArray(values) => {
+            write_len(dst, values.len())?;
+            dst.extend(values.iter().map(|value| *value as u8));
+        }
+        Tag::String(value) => write_string(dst, value)?,
+        Tag::List(tags) => {
+            let element = tags.first().map_or(id::END, Tag::id);
+
+            if tags.iter().any(|tag| tag.id() != element) {
+                return Err(NbtError::MixedList);
+            }
+
+            dst.push(element);
+            write_len(dst, tags.len())?;
+
+            for tag in tags {
+                write_tag(dst, tag, depth + 1)?;
+            }
+        }
+        Tag::Compound(compound) => write_compound(dst, compound, depth + 1)?,
+        Tag::IntArray(values) => {
+            write_len(dst, values.len())?;
+            values.iter().for_each(|value| dst.extend(value.to_be_bytes()));
+        }
+        Tag::LongArray(values) => {
+            write_len(dst, values.len())?;
+            values.iter().for_each(|value| dst.extend(value.to_be_bytes()));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_compound(dst: &mut Vec<u8>, compound: &Compound, depth: usize) -> Result<(), NbtError> {
+    if depth > MAX_DEPTH {
+        return Err(NbtError::TooDeep);
+    }
+
+    for (name, tag) in compound.iter() {
+        dst.push(tag.id());
+        write_string(dst, name)?;
+        write_tag(dst, tag, depth)?;
+    }
+
+    dst.push(id::END);
+
+    Ok(())
+}
+
+pub(super) fn write(dst: &mut Vec<u8>, nbt: &Nbt, flavor: Flavor) -> Result<(), NbtError> {
+    dst.push(id::COMPOUND);
+
+    if flavor == Flavor::File {
+        write_string(dst, &nbt.name)?;
+    }
+
+    write_compound(dst, &nbt.root, 0)
+}
+
+fn string_len(string: &str) -> usize {
+    2 + modified_utf8_len(string)
+}
+
+fn tag_len(tag: &Tag) -> usize {
+    match tag {
+        Tag::Byte(_) => 1,
+        Tag::Short(_) => 2,
+        Tag::Int(_) | Tag::Float(_) => 4,
+        Tag::Long(_) | Tag::Double(_) => 8,
+        Tag::ByteArray(values) => 4 + values.len(),
+        Tag::String(value) => string_len(value),
+        Tag::List(tags) => 1 + 4 + tags.iter().map(tag_len).sum::<usize>(),
+        Tag::Compound(compound) => compound_len(compound),
+        Tag::IntArray(values) => 4 + values.len() * 4,
+        Tag::LongArray(values) => 4 + values.len() * 8,
+    }
+}
+
+fn compound_len(compound: &Compound) -> usize {
+    compound.iter().map(|(name, tag)| 1 + string_len(name) + tag_len(tag)).sum::<usize>() + 1
+}
+
+pub(super) fn len(nbt: &Nbt, flavor: Flavor) -> usize {
+    let name = match flavor {
+        Flavor::File => string_len(&nbt.name),
+        Flavor::Network => 0,
+    };
+
+    1 + name + compound_len(&nbt.root)
+}
+
+// Java's "modified UTF-8": null is two bytes so there are no zero bytes, and characters outside the BMP are written
+// as their two UTF-16 surrogates, three bytes each.
+fn modified_utf8_len(string: &str) -> usize {
+    string
+        .chars()
+        .map(|c| match c as u32 {
+            0x01..=0x7f => 1,
+            0x00 | 0x80..=0x7ff => 2,
+            0x800..=0xffff => 3,
+            _ => 6,
+        })
+        .sum()
+}
+
+pub(crate) fn to_modified_utf8(string: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(modified_utf8_len(string));
+
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7f => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7ff => bytes.extend([0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8]),
+            _ => bytes.extend([0xe0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3f) as u8, 0x80 | (unit & 0x3f) as u8]),
+        }
+    }
+
+    bytes
+}
+
+pub(crate) fn from_modified_utf8(bytes: &[u8]) -> Option<String> {
+    // Most strings are plain ASCII.
+    if bytes.iter().all(|byte| (0x01..=0x7f).contains(byte)) {
+        return Some(String::from_utf8(bytes.to_vec()).unwrap());
+    }
+
+    fn continuation(bytes: &mut impl Iterator<Item = u8>) -> Option<u16> {
+        match bytes.next() {
+            Some(byte) if byte & 0xc0 == 0x80 => Some((byte & 0x3f) as u16),
+            _ => None,
+        }
+    }
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter().copied();
+
+    while let Some(byte) = bytes.next() {
+        let unit = match byte {
+            0x01..=0x7f => byte as u16,
+            0xc0..=0xdf => ((byte & 0x1f) as u16) << 6 | continuation(&mut bytes)?,
+            0xe0..=0xef => ((byte & 0x0f) as u16) << 12 | continuation(&mut bytes)? << 6 | continuation(&mut bytes)?,
+            _ => return None,
+        };
+
+        units.push(unit);
+    }
+
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::nbt::tests::{compound, tag};
+
+    // The classic `hello_world.nbt` test file.
+    const HELLO_WORLD: [u8; 33] = [
+        0x0a, 0x00, 0x0b, b'h', b'e', b'l', b'l', b'o', b' ', b'w', b'o', b'r', b'l', b'd', 0x08, 0x00, 0x04, b'n', b'a', b'm', b'e',
+        0x00, 0x09, b'B', b'a', b'n', b'a', b'n', b'r', b'a', b'm', b'a', 0x00,
+    ];
+
+    fn everything() -> Nbt {
+        let mut nested = Compound::new();
+        nested.insert("empty", Tag::Compound(Compound::new()));
+
+        let mut root = Compound::new();
+        root.insert("byte", Tag::Byte(-1));
+        root.insert("short", Tag::Short(-300));
+        root.insert("int", Tag::Int(25565));
+        root.insert("long", Tag::Long(i64::MIN));
+        root.insert("float", Tag::Float(0.5));
+        root.insert("double", Tag::Double(-1.25));
+        root.insert("byte array", Tag::ByteArray(vec![0, -128, 127]));
+        root.insert("string", Tag::String("Ünïcödé 🎉".to_owned()));
+        root.insert("list", Tag::List(vec![Tag::Short(1), Tag::Short(2)]));
+        root.insert("empty list", Tag::List(vec![]));
+        root.insert("nested lists", Tag::List(vec![Tag::List(vec![Tag::Int(1)]), Tag::List(vec![])]));
+        root.insert("compound", Tag::Compound(nested));
+        root.insert("int array", Tag::IntArray(vec![i32::MIN, 0, i32::MAX]));
+        root.insert("long array", Tag::LongArray(vec![0x0123456789abcdef, -1]));
+
+        Nbt { name: "everything".to_owned(), root }
+    }
+
+    #[test]
+    fn hello_world_test() -> Result<()> {
+        let (nbt, offset) = read(&HELLO_WORLD, Flavor::File)?;
+
+        assert_eq!(offset, HELLO_WORLD.len());
+        assert_eq!(nbt.name, "hello world");
+        assert_eq!(nbt.root.get("name"), Some(&Tag::String("Bananrama".to_owned())));
+
+        assert_eq!(nbt.write(Flavor::File)?, HELLO_WORLD);
+        assert_eq!(len(&nbt, Flavor::File), HELLO_WORLD.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip_test() -> Result<()> {
+        let nbt = everything();
+
+        for flavor in [Flavor::File, Flavor::Network] {
+            let bytes = nbt.write(flavor)?;
+            assert_eq!(bytes.len(), len(&nbt, flavor));
+
+            let (read_nbt, offset) = read(&bytes, flavor)?;
+            assert_eq!(offset, bytes.len());
+
+            match flavor {
+                Flavor::File => assert_eq!(read_nbt, nbt),
+                Flavor::Network => assert_eq!(read_nbt, Nbt::new(nbt.root.clone())),
+            }
+
+            // Cut off anywhere, it's an error and not a panic.
+            for end in 0..bytes.len() {
+                assert!(read(&bytes[..end], flavor).is_err());
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn network_test() -> Result<()> {
+        let mut root = Compound::new();
+        root.insert("a", Tag::Byte(1));
+
+        assert_eq!(Nbt::new(root).write(Flavor::Network)?, [0x0a, 0x01, 0x00, 0x01, b'a', 0x01, 0x00]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_test() -> Result<()> {
+        // Not a compound at the root.
+        assert!(matches!(read(&[0x08, 0x00, 0x00, 0x00, 0x00], Flavor::File), Err(NbtError::RootNotCompound(8))));
+        assert!(matches!(read(&[0x0a, 0x0d, 0x00, 0x00], Flavor::Network), Err(NbtError::UnknownTag(13))));
+
+        // An int array with a negative, and then a huge length.
+        let array = |len: i32| [&[0x0a, 0x0b, 0x00, 0x00][..], &len.to_be_bytes()].concat();
+        assert!(matches!(read(&array(-1), Flavor::Network), Err(NbtError::NegativeLength(-1))));
+        assert!(read(&array(i32::MAX), Flavor::Network).is_err());
+
+        // A list of ends that isn't empty.
+        assert!(read(&[0x0a, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00], Flavor::Network).is_err());
+
+        let mut root = Compound::new();
+        root.insert("mixed", Tag::List(vec![Tag::Byte(1), Tag::Int(1)]));
+        assert!(matches!(Nbt::new(root).write(Flavor::Network), Err(NbtError::MixedList)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_names_test() -> Result<()> {
+        // A whole frame full of short names, and one of them twice.
+        let names: Vec<_> = (0..200_000).map(|i| format!("{:x}", i)).chain(["0".to_owned()]).collect();
+        let mut src = vec![id::COMPOUND, 0x00, 0x00];
+
+        for (i, name) in names.iter().enumerate() {
+            src.push(id::BYTE);
+            src.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            src.extend_from_slice(name.as_bytes());
+            src.push(i as u8);
+        }
+
+        src.push(id::END);
+
+        let (nbt, len) = read(&src, Flavor::File)?;
+        assert_eq!(len, src.len());
+        assert_eq!(nbt.root.len(), 200_000);
+        // The last one wins, but keeps the first one's place.
+        assert_eq!(nbt.root.iter().next(), Some(("0", &Tag::Byte(200_000_u32 as u8 as i8))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn depth_test() -> Result<()> {
+        let nested = |depth: usize| {
+            (0..depth).fold(Compound::new(), |compound, _| {
+                let mut parent = Compound::new();
+                parent.insert("", Tag::Compound(compound));
+                parent
+            })
+        };
+
+        let bytes = Nbt::new(nested(MAX_DEPTH)).write(Flavor::Network)?;
+        assert!(read(&bytes, Flavor::Network).is_ok());
+
+        assert!(matches!(Nbt::new(nested(MAX_DEPTH + 1)).write(Flavor::Network), Err(NbtError::TooDeep)));
+
+        // Someone else wrote it, so we have to check while reading too.
+        let mut deep = vec![0x0a];
+        (0..=MAX_DEPTH).for_each(|_| deep.extend([0x0a, 0x00, 0x00]));
+        assert!(matches!(read(&deep, Flavor::Network), Err(NbtError::TooDeep)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn modified_utf8_test() {
+        let vals: [(&str, &[u8]); 4] = [
+            ("abc", b"abc"),
+            ("\0", &[0xc0, 0x80]),
+            ("ü€", &[0xc3, 0xbc, 0xe2, 0x82, 0xac]),
+            // U+1F389, as the surrogates D83C and DF89.
+            ("🎉", &[0xed, 0xa0, 0xbc, 0xed, 0xbe, 0x89]),
+        ];
+
+        for (string, bytes) in vals {
+            assert_eq!(to_modified_utf8(string), bytes);
+            assert_eq!(modified_utf8_len(string), bytes.len());
+            assert_eq!(from_modified_utf8(bytes).as_deref(), Some(string));
+        }
+
+        // A real null byte, a cut off character and a lone surrogate.
+        for invalid in [&[0x00][..], &[0xc3], &[0xed, 0xa0, 0xbc]] {
+            assert_eq!(from_modified_utf8(invalid), None);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn nbt_property_test(name in any::<String>(), root in compound(tag())) {
+            let nbt = Nbt { name, root };
+
+            let bytes = nbt.write(Flavor::File)?;
+            prop_assert_eq!(bytes.len(), len(&nbt, Flavor::File));
+            prop_assert_eq!(read(&bytes, Flavor::File)?, (nbt.clone(), bytes.len()));
+
+            // The network flavor drops the name.
+            let bytes = nbt.write(Flavor::Network)?;
+            prop_assert_eq!(read(&bytes, Flavor::Network)?, (Nbt::new(nbt.root), bytes.len()));
+        }
+
+        #[test]
+        fn nbt_arbitrary_bytes_test(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+            for flavor in [Flavor::File, Flavor::Network] {
+                if let Ok((nbt, read)) = read(&bytes, flavor) {
+                    prop_assert!(read <= bytes.len());
+                    prop_assert_eq!(nbt.write(flavor)?.len(), len(&nbt, flavor));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::fmt::Display;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use super::{Compound, NbtError, Tag, INT_ARRAY_MARKER, LONG_ARRAY_MARKER, TAG_MARKER};
+
+impl de::Error for NbtError {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtError::Message(msg.to_string())
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an NBT tag")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Tag, E> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn visit_i16<E: de::Error>(self, v: i16) -> Result<Tag, E> {
+        Ok(Tag::Short(v))
+    }
+
+    fn visit_i32<E: de::Error>(self, v: i32) -> Result<Tag, E> {
+        Ok(Tag::Int(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tag, E> {
+        Ok(Tag::Long(v))
+    }
+
+    fn visit_u8<E: de::Error>(self, v: u8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn visit_u16<E: de::Error>(self, v: u16) -> Result<Tag, E> {
+        Ok(Tag::Short(v as i16))
+    }
+
+    fn visit_u32<E: de::Error>(self, v: u32) -> Result<Tag, E> {
+        Ok(Tag::Int(v as i32))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tag, E> {
+        Ok(Tag::Long(v as i64))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<Tag, E> {
+        Ok(Tag::Float(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Tag, E> {
+        Ok(Tag::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+        Ok(Tag::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Tag, E> {
+        Ok(Tag::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Tag, E> {
+        Ok(Tag::ByteArray(v.iter().map(|b| *b as i8).collect()))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        Tag::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+        let mut tags = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+
+        while let Some(tag) = seq.next_element()? {
+            tags.push(tag);
+        }
+
+        super::ser::list(tags).map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+        let mut entries = Vec::new();
+
+        while let Some(name) = map.next_key::<String>()? {
+            // Int and long arrays are passed on as a map with just the marker in it.
+            match name.as_str() {
+                INT_ARRAY_MARKER => return Ok(Tag::IntArray(map.next_value()?)),
+                LONG_ARRAY_MARKER => return Ok(Tag::LongArray(map.next_value()?)),
+                _ => entries.push((name, map.next_value()?)),
+            };
+        }
+
+        Ok(Tag::Compound(entries.into_iter().collect()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(TAG_MARKER, TagVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compound {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Tag::deserialize(deserializer)? {
+            Tag::Compound(compound) => Ok(compound),
+            tag => Err(de::Error::custom(format!("expected a compound, got a tag of type {}", tag.id()))),
+        }
+    }
+}
+
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, NbtError> {
+    T::deserialize(tag)
+}
+
+pub fn from_compound<T: DeserializeOwned>(compound: Compound) -> Result<T, NbtError> {
+    T::deserialize(Tag::Compound(compound))
+}
+
+impl<'de> IntoDeserializer<'de, NbtError> for Tag {
+    type Deserializer = Tag;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(visitor: V, values: impl Iterator<Item = Tag>) -> Result<V::Value, NbtError> {
+    let mut seq = SeqDeserializer::new(values);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+
+    Ok(value)
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = NbtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Byte(v) => visitor.visit_i8(v),
+            Tag::Short(v) => visitor.visit_i16(v),
+            Tag::Int(v) => visitor.visit_i32(v),
+            Tag::Long(v) => visitor.visit_i64(v),
+            Tag::Float(v) => visitor.visit_f32(v),
+            Tag::Double(v) => visitor.visit_f64(v),
+            // As tags, so unsigned numbers work for the elements too.
+            Tag::ByteArray(values) => visit_seq(visitor, values.into_iter().map(Tag::Byte)),
+            Tag::String(v) => visitor.visit_string(v),
+            Tag::List(tags) => visit_seq(visitor, tags.into_iter()),
+            Tag::Compound(compound) => {
+                let mut map = MapDeserializer::new(compound.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+
+                Ok(value)
+            }
+            Tag::IntArray(values) => visit_seq(visitor, values.into_iter().map(Tag::Int)),
+            Tag::LongArray(values) => visit_seq(visitor, values.into_iter().map(Tag::Long)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Byte(v) => visitor.visit_bool(v != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    // The other way around from serializing, so unsigned numbers come back with the same bits.
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Byte(v) => visitor.visit_u8(v as u8),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Short(v) => visitor.visit_u16(v as u16),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Int(v) => visitor.visit_u32(v as u32),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::Long(v) => visitor.visit_u64(v as u64),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::ByteArray(values) => visitor.visit_byte_buf(values.into_iter().map(|b| b as u8).collect()),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // A tag that's there is always something, missing fields are `None` without asking us.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        match (name, self) {
+            (TAG_MARKER, Tag::ByteArray(values)) => visitor.visit_byte_buf(values.into_iter().map(|b| b as u8).collect()),
+            (TAG_MARKER, tag @ Tag::IntArray(_)) => visitor.visit_map(MapDeserializer::new(std::iter::once((INT_ARRAY_MARKER, tag)))),
+            (TAG_MARKER, tag @ Tag::LongArray(_)) => visitor.visit_map(MapDeserializer::new(std::iter::once((LONG_ARRAY_MARKER, tag)))),
+            (TAG_MARKER, tag) => tag.deserialize_any(visitor),
+            (_, tag) => visitor.visit_newtype_struct(tag),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(IntoDeserializer::<NbtError>::into_deserializer(variant)),
+            Tag::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+
+                visitor.visit_enum(Variant { variant, value })
+            }
+            _ => Err(NbtError::Message("enums have to be a string or a compound with just the variant".to_owned())),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Variant {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant {
+    type Error = NbtError;
+    type Variant = Tag;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self::Variant), Self::Error> {
+        let variant: StringDeserializer<NbtError> = self.variant.into_deserializer();
+
+        Ok((seed.deserialize(variant)?, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Tag {
+    type Error = NbtError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::nbt::{from_bytes, to_bytes, to_compound, ByteArray, Flavor, IntArray, LongArray, Nbt};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Weather {
+        Clear,
+        Rain(i32),
+        Thunder { duration: i32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        #[serde(rename = "Count")]
+        count: u8,
+        tag: Option<Compound>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Player {
+        name: String,
+        health: f32,
+        position: Vec<f64>,
+        flying: bool,
+        xp: u32,
+        inventory: Vec<Item>,
+        weather: Vec<Weather>,
+        seen: ByteArray,
+        uuid: IntArray,
+        heightmap: LongArray,
+        nickname: Option<String>,
+    }
+
+    fn player() -> Player {
+        let mut tag = Compound::new();
+        tag.insert("Damage", Tag::Int(3));
+        tag.insert("Seeds", Tag::LongArray(vec![1, 2]));
+        tag.insert("Colors", Tag::IntArray(vec![]));
+        tag.insert("Data", Tag::ByteArray(vec![5]));
+        tag.insert("Lore", Tag::List(vec![Tag::String("Shiny".to_owned())]));
+
+        Player {
+            name: "Notch".to_owned(),
+            health: 20.0,
+            position: vec![0.5, 64.0, -0.5],
+            flying: false,
+            xp: u32::MAX,
+            inventory: vec![
+                Item { id: "minecraft:stone".to_owned(), count: 200, tag: None },
+                Item { id: "minecraft:diamond_sword".to_owned(), count: 1, tag: Some(tag) },
+            ],
+            // Unit variants are strings, so they can't be in the same list as the others.
+            weather: vec![Weather::Rain(5), Weather::Thunder { duration: 1 }],
+            seen: ByteArray(vec![-1, 0, 1]),
+            uuid: IntArray(vec![1, 2, 3, 4]),
+            heightmap: LongArray(vec![i64::MAX]),
+            nickname: None,
+        }
+    }
+
+    #[test]
+    fn round_trip_test() -> Result<()> {
+        let compound = to_compound(&player())?;
+        assert_eq!(compound.get("seen"), Some(&Tag::ByteArray(vec![-1, 0, 1])));
+
+        assert_eq!(from_compound::<Player>(compound.clone())?, player());
+
+        // And through the bytes.
+        let bytes = to_bytes(&player(), Flavor::Network)?;
+        assert_eq!(bytes, Nbt::new(compound).write(Flavor::Network)?);
+        assert_eq!(from_bytes::<Player>(&bytes, Flavor::Network)?, player());
+
+        Ok(())
+    }
+
+    #[test]
+    fn values_test() -> Result<()> {
+        assert_eq!(from_tag::<u8>(Tag::Byte(-1))?, 255);
+        assert_eq!(from_tag::<i64>(Tag::Int(-5))?, -5);
+        assert!(from_tag::<bool>(Tag::Byte(2))?);
+        assert_eq!(from_tag::<Vec<u8>>(Tag::ByteArray(vec![-1]))?, [255]);
+        assert_eq!(from_tag::<Vec<u32>>(Tag::IntArray(vec![-1]))?, [u32::MAX]);
+        assert_eq!(from_tag::<IntArray>(Tag::List(vec![Tag::Int(7)]))?, IntArray(vec![7]));
+
+        // Numbers don't silently get cut off.
+        assert!(from_tag::<i8>(Tag::Int(300)).is_err());
+        assert!(from_tag::<String>(Tag::Int(1)).is_err());
+        assert_eq!(from_tag::<Weather>(Tag::String("Clear".to_owned()))?, Weather::Clear);
+        assert!(from_tag::<Weather>(Tag::String("Snow".to_owned())).is_err());
+        assert!(from_tag::<Weather>(Tag::Compound(Compound::new())).is_err());
+
+        let mut missing = to_compound(&player())?;
+        missing.remove("name");
+        assert!(from_compound::<Player>(missing).is_err());
+
+        Ok(())
+    }
+}
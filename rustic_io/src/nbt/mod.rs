@@ -0,0 +1,499 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use derive_more::{Display, Error, From};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use scroll::ctx;
+
+mod binary;
+mod de;
+mod ser;
+
+// The text format commands use, like `{Damage:3,display:{Name:'"Excalibur"'}}`
+pub mod snbt;
+
+pub use de::{from_compound, from_tag};
+pub use ser::{to_compound, to_tag};
+
+// Vanilla stops reading compounds and lists nested deeper than this.
+pub const MAX_DEPTH: usize = 512;
+
+// Nothing vanilla writes comes close, but a few kilobytes of a compressed file can inflate to gigabytes.
+pub const MAX_INFLATED_LEN: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Display, From, Error)]
+pub enum NbtError {
+    #[display(fmt = "{}", _0)]
+    Scroll(scroll::Error),
+    #[display(fmt = "{}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "unknown tag type {}", _0)]
+    #[from(ignore)]
+    UnknownTag(#[error(not(source))] u8),
+    #[display(fmt = "the root tag is a {}, not a compound", _0)]
+    #[from(ignore)]
+    RootNotCompound(#[error(not(source))] u8),
+    #[display(fmt = "negative length {}", _0)]
+    #[from(ignore)]
+    NegativeLength(#[error(not(source))] i32),
+    #[display(fmt = "string is not valid modified UTF-8")]
+    InvalidString,
+    #[display(fmt = "string of {} bytes is longer than 65535", _0)]
+    #[from(ignore)]
+    StringTooLong(#[error(not(source))] usize),
+    #[display(fmt = "lists can only hold one type of tag")]
+    MixedList,
+    #[display(fmt = "nested deeper than {} levels", MAX_DEPTH)]
+    TooDeep,
+    #[display(fmt = "inflates to more than {} bytes", _0)]
+    #[from(ignore)]
+    TooLarge(#[error(not(source))] usize),
+    // Anything serde has to say.
+    #[display(fmt = "{}", _0)]
+    #[from(ignore)]
+    Message(#[error(not(source))] String),
+}
+
+// The tag type ids, which are written before every tag.
+pub(crate) mod id {
+    pub const END: u8 = 0;
+    pub const BYTE: u8 = 1;
+    pub const SHORT: u8 = 2;
+    pub const INT: u8 = 3;
+    pub const LONG: u8 = 4;
+    pub const FLOAT: u8 = 5;
+    pub const DOUBLE: u8 = 6;
+    pub const BYTE_ARRAY: u8 = 7;
+    pub const STRING: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const COMPOUND: u8 = 10;
+    pub const INT_ARRAY: u8 = 11;
+    pub const LONG_ARRAY: u8 = 12;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    // Every element has the same type, which is written once before them.
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => id::BYTE,
+            Tag::Short(_) => id::SHORT,
+            Tag::Int(_) => id::INT,
+            Tag::Long(_) => id::LONG,
+            Tag::Float(_) => id::FLOAT,
+            Tag::Double(_) => id::DOUBLE,
+            Tag::ByteArray(_) => id::BYTE_ARRAY,
+            Tag::String(_) => id::STRING,
+            Tag::List(_) => id::LIST,
+            Tag::Compound(_) => id::COMPOUND,
+            Tag::IntArray(_) => id::INT_ARRAY,
+            Tag::LongArray(_) => id::LONG_ARRAY,
+        }
+    }
+}
+
+// Named tags, which keep the order they were read or inserted in.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Compound(Vec<(String, Tag)>);
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.0.iter().find(|(key, _)| key == name).map(|(_, tag)| tag)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Tag> {
+        self.0.iter_mut().find(|(key, _)| key == name).map(|(_, tag)| tag)
+    }
+
+    // Replaces the tag that already has the name, if there is one, and gives it back.
+    pub fn insert(&mut self, name: impl Into<String>, tag: Tag) -> Option<Tag> {
+        let name = name.into();
+
+        match self.get_mut(&name) {
+            Some(old) => Some(std::mem::replace(old, tag)),
+            None => {
+                self.0.push((name, tag));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Tag> {
+        let i = self.0.iter().position(|(key, _)| key == name)?;
+
+        Some(self.0.remove(i).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Tag)> {
+        self.0.iter().map(|(name, tag)| (name.as_str(), tag))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// The same as inserting them one after the other, a later tag with the same name replaces the earlier one where it was.
+// Readers collect compounds this way, looking for the name on every insert would take forever with a lot of them.
+impl FromIterator<(String, Tag)> for Compound {
+    fn from_iter<I: IntoIterator<Item = (String, Tag)>>(iter: I) -> Self {
+        let entries: Vec<_> = iter.into_iter().collect();
+
+        let mut names = HashSet::with_capacity(entries.len());
+
+        if entries.iter().all(|(name, _)| names.insert(name.as_str())) {
+            return Compound(entries);
+        }
+
+        let mut positions = HashMap::with_capacity(entries.len());
+        let mut compound = Vec::with_capacity(entries.len());
+
+        for (name, tag) in entries {
+            match positions.get(&name) {
+                Some(&i) => compound[i] = (name, tag),
+                None => {
+                    positions.insert(name.clone(), compound.len());
+                    compound.push((name, tag));
+                }
+            }
+        }
+
+        Compound(compound)
+    }
+}
+
+impl IntoIterator for Compound {
+    type Item = (String, Tag);
+    type IntoIter = std::vec::IntoIter<(String, Tag)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flavor {
+    // Files, and packets before 1.20.2: the root compound has a name, even if it's usually empty.
+    #[default]
+    File,
+    // Packets since 1.20.2, where the root compound has no name.
+    Network,
+}
+
+// A root compound and its name, which is what an NBT file or a packet's NBT field holds.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Nbt {
+    pub name: String,
+    pub root: Compound,
+}
+
+impl Nbt {
+    pub fn new(root: Compound) -> Self {
+        Self { name: String::new(), root }
+    }
+
+    pub fn read(src: &[u8], flavor: Flavor) -> Result<Self, NbtError> {
+        Ok(binary::read(src, flavor)?.0)
+    }
+
+    pub fn write(&self, flavor: Flavor) -> Result<Vec<u8>, NbtError> {
+        let mut bytes = Vec::with_capacity(binary::len(self, flavor));
+        binary::write(&mut bytes, self, flavor)?;
+
+        Ok(bytes)
+    }
+
+    // Files can be gzipped (like level.dat), zlib compressed (like chunks in region files) or not compressed at all.
+    pub fn read_file(bytes: &[u8]) -> Result<Self, NbtError> {
+        Self::read_file_limited(bytes, MAX_INFLATED_LEN)
+    }
+
+    // Like `read_file`, but gives up once more than `max_len` bytes come out of the decompression.
+    pub fn read_file_limited(bytes: &[u8], max_len: usize) -> Result<Self, NbtError> {
+        let inflated = match bytes {
+            [0x1f, 0x8b, ..] => inflate(GzDecoder::new(bytes), max_len)?,
+            [0x78, ..] => inflate(ZlibDecoder::new(bytes), max_len)?,
+            _ => return Self::read(bytes, Flavor::File),
+        };
+
+        Self::read(&inflated, Flavor::File)
+    }
+
+    // For when the compression is known up front instead of guessed, like region files say it for every chunk.
+    pub fn read_compressed(bytes: &[u8], compression: FileCompression) -> Result<Self, NbtError> {
+        let inflated = match compression {
+            FileCompression::Gzip => inflate(GzDecoder::new(bytes), MAX_INFLATED_LEN)?,
+            FileCompression::Zlib => inflate(ZlibDecoder::new(bytes), MAX_INFLATED_LEN)?,
+            FileCompression::None => return Self::read(bytes, Flavor::File),
+        };
+
+        Self::read(&inflated, Flavor::File)
+    }
+
+    pub fn write_file(&self, compression: FileCompression) -> Result<Vec<u8>, NbtError> {
+        let bytes = self.write(Flavor::File)?;
+
+        Ok(match compression {
+            FileCompression::None => bytes,
+            FileCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()?
+            }
+            FileCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()?
+            }
+        })
+    }
+}
+
+fn inflate(decoder: impl Read, max_len: usize) -> Result<Vec<u8>, NbtError> {
+    let mut inflated = Vec::new();
+
+    // One byte more than allowed is enough to tell it's too much.
+    decoder.take(max_len as u64 + 1).read_to_end(&mut inflated)?;
+
+    if inflated.len() > max_len {
+        return Err(NbtError::TooLarge(max_len));
+    }
+
+    Ok(inflated)
+}
+
+// Serializes straight to the bytes of an unnamed root compound.
+pub fn to_bytes<T: serde::Serialize + ?Sized>(value: &T, flavor: Flavor) -> Result<Vec<u8>, NbtError> {
+    Nbt::new(to_compound(value)?).write(flavor)
+}
+
+pub fn from_bytes<T: serde::de::DeserializeOwned>(bytes: &[u8], flavor: Flavor) -> Result<T, NbtError> {
+    from_compound(Nbt::read(bytes, flavor)?.root)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCompression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+impl<'a> ctx::TryFromCtx<'a, Flavor> for Nbt {
+    type Error = NbtError;
+
+    fn try_from_ctx(src: &'a [u8], flavor: Flavor) -> Result<(Self, usize), Self::Error> {
+        binary::read(src, flavor)
+    }
+}
+
+impl ctx::TryIntoCtx<Flavor> for Nbt {
+    type Error = NbtError;
+
+    fn try_into_ctx(self, dst: &mut [u8], flavor: Flavor) -> Result<usize, Self::Error> {
+        let bytes = self.write(flavor)?;
+
+        if dst.len() < bytes.len() {
+            return Err(scroll::Error::TooBig { size: bytes.len(), len: dst.len() }.into());
+        }
+
+        dst[..bytes.len()].copy_from_slice(&bytes);
+
+        Ok(bytes.len())
+    }
+}
+
+impl ctx::MeasureWith<Flavor> for Nbt {
+    fn measure_with(&self, flavor: &Flavor) -> usize {
+        binary::len(self, *flavor)
+    }
+}
+
+// `#[serde(with = ..)]` can't tell a list of ints from an int array, so these make sure it's the array.
+macro_rules! array_type {
+    ($name:ident, $int:ty, $marker:expr) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Default)]
+        pub struct $name(pub Vec<$int>);
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($marker, &self.0)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                // A list of the same numbers works too.
+                Ok($name(serde::Deserialize::deserialize(deserializer)?))
+            }
+        }
+    };
+}
+
+pub(crate) const BYTE_ARRAY_MARKER: &str = "__rustic_nbt_byte_array";
+pub(crate) const INT_ARRAY_MARKER: &str = "__rustic_nbt_int_array";
+pub(crate) const LONG_ARRAY_MARKER: &str = "__rustic_nbt_long_array";
+// What `Tag` asks for when it's deserialized, so we can tell it which array it is instead of giving it a list.
+pub(crate) const TAG_MARKER: &str = "__rustic_nbt_tag";
+
+array_type!(ByteArray, i8, BYTE_ARRAY_MARKER);
+array_type!(IntArray, i32, INT_ARRAY_MARKER);
+array_type!(LongArray, i64, LONG_ARRAY_MARKER);
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use proptest::prelude::*;
+    use scroll::ctx::MeasureWith;
+    use scroll::{Pread, Pwrite};
+
+    use super::*;
+
+    // Any tag, nested a few levels deep. Floats are finite so tags can be compared and read back from SNBT, and lists
+    // only keep the elements that have the same type as their first one.
+    pub(super) fn tag() -> impl Strategy<Value = Tag> {
+        let leaf = prop_oneof![
+            any::<i8>().prop_map(Tag::Byte),
+            any::<i16>().prop_map(Tag::Short),
+            any::<i32>().prop_map(Tag::Int),
+            any::<i64>().prop_map(Tag::Long),
+            any::<f32>().prop_filter("finite", |value| value.is_finite()).prop_map(Tag::Float),
+            any::<f64>().prop_filter("finite", |value| value.is_finite()).prop_map(Tag::Double),
+            any::<Vec<i8>>().prop_map(Tag::ByteArray),
+            any::<String>().prop_map(Tag::String),
+            any::<Vec<i32>>().prop_map(Tag::IntArray),
+            any::<Vec<i64>>().prop_map(Tag::LongArray),
+        ];
+
+        leaf.prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(|tags| {
+                    let id = tags.first().map(Tag::id);
+                    Tag::List(tags.into_iter().filter(|tag| Some(tag.id()) == id).collect())
+                }),
+                compound(inner).prop_map(Tag::Compound),
+            ]
+        })
+    }
+
+    pub(super) fn compound(tag: impl Strategy<Value = Tag>) -> impl Strategy<Value = Compound> {
+        prop::collection::vec((any::<String>(), tag), 0..8).prop_map(Compound::from_iter)
+    }
+
+    fn level() -> Nbt {
+        let mut data = Compound::new();
+        data.insert("LevelName", Tag::String("world".to_owned()));
+        data.insert("RandomSeed", Tag::Long(-4530634556500121041));
+
+        let mut root = Compound::new();
+        root.insert("Data", Tag::Compound(data));
+
+        Nbt::new(root)
+    }
+
+    #[test]
+    fn compound_test() {
+        let mut compound = Compound::new();
+
+        assert_eq!(compound.insert("a", Tag::Int(1)), None);
+        assert_eq!(compound.insert("b", Tag::Int(2)), None);
+        assert_eq!(compound.insert("a", Tag::Int(3)), Some(Tag::Int(1)));
+
+        assert_eq!(compound.iter().map(|(name, _)| name).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(compound.get("a"), Some(&Tag::Int(3)));
+        assert_eq!(compound.remove("a"), Some(Tag::Int(3)));
+        assert_eq!(compound.len(), 1);
+
+        // Collecting works like inserting.
+        let tags = [("a", 1), ("b", 2), ("a", 3), ("c", 4)].map(|(name, value)| (name.to_owned(), Tag::Int(value)));
+        let collected: Compound = tags.into_iter().collect();
+
+        let mut inserted = Compound::new();
+        for (name, value) in [("a", 3), ("b", 2), ("c", 4)] {
+            inserted.insert(name, Tag::Int(value));
+        }
+
+        assert_eq!(collected, inserted);
+    }
+
+    #[test]
+    fn file_compression_test() -> Result<()> {
+        let level = level();
+
+        for compression in [FileCompression::None, FileCompression::Gzip, FileCompression::Zlib] {
+            let bytes = level.write_file(compression)?;
+
+            assert_eq!(Nbt::read_file(&bytes)?, level);
+        }
+
+        assert_eq!(&level.write_file(FileCompression::Gzip)?[..2], [0x1f, 0x8b]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn inflated_len_test() -> Result<()> {
+        let mut root = Compound::new();
+        root.insert("zeroes", Tag::ByteArray(vec![0; 1 << 20]));
+        let nbt = Nbt::new(root);
+
+        for compression in [FileCompression::Gzip, FileCompression::Zlib] {
+            let bytes = nbt.write_file(compression)?;
+            let len = nbt.write(Flavor::File)?.len();
+
+            // A few kilobytes that would take up a megabyte.
+            assert!(bytes.len() < 4096);
+            assert!(matches!(Nbt::read_file_limited(&bytes, len - 1), Err(NbtError::TooLarge(_))));
+            assert_eq!(Nbt::read_file_limited(&bytes, len)?, nbt);
+            assert_eq!(Nbt::read_compressed(&bytes, compression)?, nbt);
+        }
+
+        // Gzip isn't zlib, whatever it says it is.
+        assert!(Nbt::read_compressed(&nbt.write_file(FileCompression::Gzip)?, FileCompression::Zlib).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scroll_test() -> Result<()> {
+        let level = level();
+        let len = level.measure_with(&Flavor::Network);
+
+        // Something after the NBT, like the rest of a packet.
+        let mut bytes = vec![0; len + 1];
+        bytes.pwrite_with(level.clone(), 0, Flavor::Network)?;
+        bytes[len] = 0x2a;
+
+        let mut offset = 0;
+        assert_eq!(bytes.gread_with::<Nbt>(&mut offset, Flavor::Network)?, level);
+        assert_eq!(offset, len);
+
+        // The other flavor reads the root's type as the start of a name.
+        assert_ne!(bytes.pread_with::<Nbt>(0, Flavor::File).ok(), Some(level));
+
+        Ok(())
+    }
+}
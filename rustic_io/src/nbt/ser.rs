@@ -0,0 +1,473 @@
+use std::fmt::Display;
+
+use serde::ser::{self, Serialize};
+
+use super::{Compound, NbtError, Tag, BYTE_ARRAY_MARKER, INT_ARRAY_MARKER, LONG_ARRAY_MARKER};
+
+impl ser::Error for NbtError {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtError::Message(msg.to_string())
+    }
+}
+
+impl Serialize for Tag {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tag::Byte(v) => serializer.serialize_i8(*v),
+            Tag::Short(v) => serializer.serialize_i16(*v),
+            Tag::Int(v) => serializer.serialize_i32(*v),
+            Tag::Long(v) => serializer.serialize_i64(*v),
+            Tag::Float(v) => serializer.serialize_f32(*v),
+            Tag::Double(v) => serializer.serialize_f64(*v),
+            Tag::ByteArray(values) => serializer.serialize_newtype_struct(BYTE_ARRAY_MARKER, values),
+            Tag::String(v) => serializer.serialize_str(v),
+            Tag::List(tags) => tags.serialize(serializer),
+            Tag::Compound(compound) => compound.serialize(serializer),
+            Tag::IntArray(values) => serializer.serialize_newtype_struct(INT_ARRAY_MARKER, values),
+            Tag::LongArray(values) => serializer.serialize_newtype_struct(LONG_ARRAY_MARKER, values),
+        }
+    }
+}
+
+impl Serialize for Compound {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<Tag, NbtError> {
+    value.serialize(Serializer)?.ok_or_else(|| NbtError::Message("there's no tag for nothing".to_owned()))
+}
+
+// Structs and maps become compounds, which is what has to be at the root.
+pub fn to_compound<T: Serialize + ?Sized>(value: &T) -> Result<Compound, NbtError> {
+    match to_tag(value)? {
+        Tag::Compound(compound) => Ok(compound),
+        tag => Err(NbtError::RootNotCompound(tag.id())),
+    }
+}
+
+// There's no tag for `None` or `()`, so those give nothing, and compounds leave the field out.
+struct Serializer;
+
+pub(super) fn list(tags: Vec<Tag>) -> Result<Tag, NbtError> {
+    match tags.first() {
+        Some(first) if tags.iter().any(|tag| tag.id() != first.id()) => Err(NbtError::MixedList),
+        _ => Ok(Tag::List(tags)),
+    }
+}
+
+// Turns the list the array types serialize as into the array tag.
+fn array(tag: Option<Tag>, marker: &str) -> Result<Option<Tag>, NbtError> {
+    let tags = match tag {
+        Some(Tag::List(tags)) => tags,
+        _ => return Err(NbtError::Message(format!("{} has to be a sequence", marker))),
+    };
+
+    let wrong = || NbtError::Message("an array can only have numbers of its own type".to_owned());
+
+    let array = match marker {
+        BYTE_ARRAY_MARKER => {
+            Tag::ByteArray(tags.into_iter().map(|tag| if let Tag::Byte(b) = tag { Ok(b) } else { Err(wrong()) }).collect::<Result<_, _>>()?)
+        }
+        INT_ARRAY_MARKER => {
+            Tag::IntArray(tags.into_iter().map(|tag| if let Tag::Int(i) = tag { Ok(i) } else { Err(wrong()) }).collect::<Result<_, _>>()?)
+        }
+        _ => Tag::LongArray(
+            tags.into_iter().map(|tag| if let Tag::Long(l) = tag { Ok(l) } else { Err(wrong()) }).collect::<Result<_, _>>()?,
+        ),
+    };
+
+    Ok(Some(array))
+}
+
+// Enum variants with data are a compound with just the variant in it, like `{"Variant": ...}`.
+fn variant(name: &'static str, tag: Option<Tag>) -> Option<Tag> {
+    let mut compound = Compound::new();
+
+    if let Some(tag) = tag {
+        compound.insert(name, tag);
+    }
+
+    Some(Tag::Compound(compound))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    // NBT has no unsigned numbers, so these keep their bits in the signed tag of the same size, like Java does.
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|b| *b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::String(variant.to_owned())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<Self::Ok, Self::Error> {
+        match name {
+            BYTE_ARRAY_MARKER | INT_ARRAY_MARKER | LONG_ARRAY_MARKER => array(value.serialize(self)?, name),
+            _ => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(self::variant(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeList { tags: Vec::with_capacity(len.unwrap_or(0)), variant: None })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeList { tags: Vec::with_capacity(len), variant: Some(variant) })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeCompound { compound: Compound::new(), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeCompound { compound: Compound::new(), key: None, variant: Some(variant) })
+    }
+}
+
+struct SerializeList {
+    tags: Vec<Tag>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        let tag = value.serialize(Serializer)?.ok_or_else(|| NbtError::Message("lists can't have nothing in them".to_owned()))?;
+        self.tags.push(tag);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, NbtError> {
+        let tag = list(self.tags)?;
+
+        Ok(match self.variant {
+            Some(name) => variant(name, Some(tag)),
+            None => Some(tag),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+struct SerializeCompound {
+    compound: Compound,
+    // Maps give the key and the value separately.
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeCompound {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), NbtError> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.compound.insert(key, tag);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, NbtError> {
+        let tag = Tag::Compound(self.compound);
+
+        Ok(match self.variant {
+            Some(name) => variant(name, Some(tag)),
+            None => Some(tag),
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match key.serialize(Serializer)? {
+            Some(Tag::String(key)) => self.key = Some(key),
+            _ => return Err(NbtError::Message("compound keys have to be strings".to_owned())),
+        }
+
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.key.take().ok_or_else(|| NbtError::Message("a value without a key".to_owned()))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::nbt::{IntArray, LongArray};
+
+    #[derive(Serialize)]
+    enum Weather {
+        Clear,
+        Rain(i32),
+        Thunder { duration: i32 },
+    }
+
+    #[derive(Serialize)]
+    struct Level {
+        name: String,
+        seed: i64,
+        hardcore: bool,
+        spawn: (i32, i32, i32),
+        border: Option<f64>,
+        weather: Vec<Weather>,
+        heightmap: LongArray,
+        biomes: IntArray,
+    }
+
+    #[test]
+    fn struct_test() -> Result<()> {
+        let level = Level {
+            name: "world".to_owned(),
+            seed: -1,
+            hardcore: true,
+            spawn: (0, 64, 0),
+            border: None,
+            weather: vec![Weather::Rain(100), Weather::Thunder { duration: 20 }],
+            heightmap: LongArray(vec![1, 2]),
+            biomes: IntArray(vec![]),
+        };
+
+        let compound = to_compound(&level)?;
+
+        assert_eq!(compound.iter().map(|(name, _)| name).collect::<Vec<_>>(), [
+            "name", "seed", "hardcore", "spawn", "weather", "heightmap", "biomes"
+        ]);
+        assert_eq!(compound.get("hardcore"), Some(&Tag::Byte(1)));
+        assert_eq!(compound.get("spawn"), Some(&Tag::List(vec![Tag::Int(0), Tag::Int(64), Tag::Int(0)])));
+        assert_eq!(compound.get("heightmap"), Some(&Tag::LongArray(vec![1, 2])));
+        assert_eq!(compound.get("biomes"), Some(&Tag::IntArray(vec![])));
+
+        let mut thunder = Compound::new();
+        thunder.insert("duration", Tag::Int(20));
+        let weather = [("Rain", Tag::Int(100)), ("Thunder", Tag::Compound(thunder))]
+            .into_iter()
+            .map(|(name, tag)| Tag::Compound([(name.to_owned(), tag)].into_iter().collect()))
+            .collect();
+        assert_eq!(compound.get("weather"), Some(&Tag::List(weather)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn values_test() -> Result<()> {
+        assert_eq!(to_tag(&u8::MAX)?, Tag::Byte(-1));
+        assert_eq!(to_tag(&u64::MAX)?, Tag::Long(-1));
+        assert_eq!(to_tag(&'a')?, Tag::String("a".to_owned()));
+        assert_eq!(to_tag(&Weather::Clear)?, Tag::String("Clear".to_owned()));
+
+        let map: BTreeMap<_, _> = [("a", 1i16), ("b", 2)].into_iter().collect();
+        assert_eq!(to_compound(&map)?.get("b"), Some(&Tag::Short(2)));
+
+        assert!(to_compound(&5).is_err());
+        assert!(to_tag(&None::<i32>).is_err());
+        assert!(to_tag(&vec![Some(1), None]).is_err());
+        assert!(to_compound(&BTreeMap::from([(1, 1)])).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,676 @@
+use std::fmt;
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+
+use super::{Compound, Tag, MAX_DEPTH};
+
+// Where in the text parsing stopped, counted in characters so it lines up with what was typed.
+#[derive(Debug, Clone, PartialEq, Eq, Display, Error)]
+#[display(fmt = "{} at position {}", message, position)]
+pub struct SnbtError {
+    pub position: usize,
+    pub message: String,
+}
+
+// Characters vanilla allows in keys and strings without quotes.
+fn unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+fn skip_sign(text: &str) -> &str {
+    text.strip_prefix(['+', '-']).unwrap_or(text)
+}
+
+fn digits(text: &str) -> usize {
+    text.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+// `0` or a number without leading zeros, like vanilla's `[-+]?(?:0|[1-9][0-9]*)`.
+fn is_integer(text: &str) -> bool {
+    let text = skip_sign(text);
+
+    text == "0" || (!text.starts_with('0') && !text.is_empty() && digits(text) == text.len())
+}
+
+// `[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?`, where the dot isn't optional for doubles without a suffix.
+fn is_decimal(text: &str, needs_dot: bool) -> bool {
+    let text = skip_sign(text);
+
+    let (number, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(skip_sign(&text[i + 1..]))),
+        None => (text, None),
+    };
+
+    let valid_number = match number.split_once('.') {
+        Some((whole, fraction)) => {
+            digits(whole) == whole.len() && digits(fraction) == fraction.len() && !(whole.is_empty() && fraction.is_empty())
+        }
+        None => !needs_dot && !number.is_empty() && digits(number) == number.len(),
+    };
+
+    valid_number && exponent.is_none_or(|exponent| !exponent.is_empty() && digits(exponent) == exponent.len())
+}
+
+// Java's names for the values that aren't finite, which is how vanilla writes them. It reads them back as strings.
+fn non_finite_name(value: f64) -> &'static str {
+    if value.is_nan() {
+        "NaN"
+    } else if value > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
+// What an unquoted value is, by its suffix. Anything that doesn't look like a number (or is too big for its type)
+// is a string, like vanilla does it.
+fn literal(text: &str) -> Tag {
+    match text {
+        "true" => return Tag::Byte(1),
+        "false" => return Tag::Byte(0),
+        _ => {}
+    }
+
+    let (number, suffix) = match text.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&text[..i], Some(c.to_ascii_lowercase())),
+        _ => (text, None),
+    };
+
+    let tag = match suffix {
+        Some('b') if is_integer(number) => number.parse().ok().map(Tag::Byte),
+        Some('s') if is_integer(number) => number.parse().ok().map(Tag::Short),
+        Some('l') if is_integer(number) => number.parse().ok().map(Tag::Long),
+        Some('f') if is_decimal(number, false) => number.parse().ok().filter(|value: &f32| value.is_finite()).map(Tag::Float),
+        Some('d') if is_decimal(number, false) => number.parse().ok().filter(|value: &f64| value.is_finite()).map(Tag::Double),
+        None if is_integer(number) => number.parse().ok().map(Tag::Int),
+        None if is_decimal(number, true) => number.parse().ok().filter(|value: &f64| value.is_finite()).map(Tag::Double),
+        _ => None,
+    };
+
+    tag.unwrap_or_else(|| Tag::String(text.to_owned()))
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>) -> SnbtError {
+        self.error_at(self.offset, message)
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> SnbtError {
+        SnbtError { position: self.text[..offset].chars().count(), message: message.into() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.offset += c.len_utf8();
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected '{}'", expected))),
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), SnbtError> {
+        self.depth += 1;
+
+        match self.depth > MAX_DEPTH {
+            true => Err(self.error(format!("nested deeper than {} levels", MAX_DEPTH))),
+            false => Ok(()),
+        }
+    }
+
+    fn unquoted(&mut self) -> &'a str {
+        let rest = &self.text[self.offset..];
+        let len = rest.find(|c| !unquoted_char(c)).unwrap_or(rest.len());
+        self.offset += len;
+
+        &rest[..len]
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, SnbtError> {
+        let start = self.offset;
+        self.offset += 1;
+
+        let mut string = String::new();
+
+        loop {
+            let c = self.peek().ok_or_else(|| self.error_at(start, "unterminated string"))?;
+            self.offset += c.len_utf8();
+
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ ('\\' | '"' | '\'')) => {
+                        self.offset += 1;
+                        string.push(escaped);
+                    }
+                    _ => return Err(self.error_at(self.offset - 1, "invalid escape sequence")),
+                },
+                c if c == quote => return Ok(string),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.quoted(quote),
+            _ => match self.unquoted() {
+                "" => Err(self.error("expected a key")),
+                key => Ok(key.to_owned()),
+            },
+        }
+    }
+
+    fn value(&mut self) -> Result<Tag, SnbtError> {
+        self.skip_whitespace();
+
+        let rest = &self.text[self.offset..];
+
+        match self.peek() {
+            Some('{') => Ok(Tag::Compound(self.compound()?)),
+            Some('[') if rest.len() > 2 && rest[1..].starts_with(['B', 'I', 'L']) && rest[2..].starts_with(';') => self.array(),
+            Some('[') => self.list(),
+            Some(quote @ ('"' | '\'')) => Ok(Tag::String(self.quoted(quote)?)),
+            _ => match self.unquoted() {
+                "" => Err(self.error("expected a value")),
+                text => Ok(literal(text)),
+            },
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound, SnbtError> {
+        self.expect('{')?;
+        self.nest()?;
+
+        let mut entries = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.peek() != Some('}') {
+            loop {
+                let key = self.key()?;
+                self.expect(':')?;
+                entries.push((key, self.value()?));
+
+                if !self.next_element('}')? {
+                    break;
+                }
+            }
+        }
+
+        self.expect('}')?;
+        self.depth -= 1;
+
+        Ok(entries.into_iter().collect())
+    }
+
+    // After an element, there's either a comma and another element, or the end.
+    fn next_element(&mut self, end: char) -> Result<bool, SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(',') => {
+                self.offset += 1;
+                Ok(true)
+            }
+            Some(c) if c == end => Ok(false),
+            _ => Err(self.error(format!("expected ',' or '{}'", end))),
+        }
+    }
+
+    fn elements(&mut self, mut element: impl FnMut(&mut Self, Tag, usize) -> Result<(), SnbtError>) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+
+        if self.peek() != Some(']') {
+            loop {
+                self.skip_whitespace();
+
+                let start = self.offset;
+                let value = self.value()?;
+                element(self, value, start)?;
+
+                if !self.next_element(']')? {
+                    break;
+                }
+            }
+        }
+
+        self.expect(']')?;
+        self.depth -= 1;
+
+        Ok(())
+    }
+
+    fn list(&mut self) -> Result<Tag, SnbtError> {
+        self.expect('[')?;
+        self.nest()?;
+
+        let mut tags: Vec<Tag> = Vec::new();
+
+        self.elements(|parser, tag, start| {
+            if tags.first().is_some_and(|first| first.id() != tag.id()) {
+                return Err(parser.error_at(start, "lists can only hold one type of tag"));
+            }
+
+            tags.push(tag);
+            Ok(())
+        })?;
+
+        Ok(Tag::List(tags))
+    }
+
+    // `[B;1b,2b]`, `[I;1,2]` and `[L;1L,2L]`. Every number needs the array's own suffix, vanilla doesn't take any other.
+    fn array(&mut self) -> Result<Tag, SnbtError> {
+        let kind = self.text[self.offset + 1..].chars().next();
+        self.offset += 3;
+        self.nest()?;
+
+        let mut array = match kind {
+            Some('B') => Tag::ByteArray(Vec::new()),
+            Some('I') => Tag::IntArray(Vec::new()),
+            _ => Tag::LongArray(Vec::new()),
+        };
+
+        self.elements(|parser, tag, start| {
+            let wrong = || parser.error_at(start, "the array can't hold this type");
+
+            match (&mut array, tag) {
+                (Tag::ByteArray(values), Tag::Byte(value)) => values.push(value),
+                (Tag::IntArray(values), Tag::Int(value)) => values.push(value),
+                (Tag::LongArray(values), Tag::Long(value)) => values.push(value),
+                _ => return Err(wrong()),
+            }
+
+            Ok(())
+        })?;
+
+        Ok(array)
+    }
+}
+
+// Reads a value from the start of the text, and gives back how many bytes of it that took. Commands have more after it.
+pub fn parse_prefix(text: &str) -> Result<(Tag, usize), SnbtError> {
+    let mut parser = Parser { text, offset: 0, depth: 0 };
+    let tag = parser.value()?;
+
+    Ok((tag, parser.offset))
+}
+
+// The whole text has to be the value, with nothing but whitespace around it.
+pub fn parse(text: &str) -> Result<Tag, SnbtError> {
+    let mut parser = Parser { text, offset: 0, depth: 0 };
+    let tag = parser.value()?;
+
+    parser.skip_whitespace();
+
+    if parser.offset < text.len() {
+        return Err(parser.error("expected the end"));
+    }
+
+    Ok(tag)
+}
+
+impl FromStr for Tag {
+    type Err = SnbtError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text)
+    }
+}
+
+impl FromStr for Compound {
+    type Err = SnbtError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match parse(text)? {
+            Tag::Compound(compound) => Ok(compound),
+            _ => Err(SnbtError { position: 0, message: "expected a compound".to_owned() }),
+        }
+    }
+}
+
+// Double quotes, unless the string has some and no single quotes, like vanilla.
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    let quote = if string.contains('"') && !string.contains('\'') { '\'' } else { '"' };
+
+    write!(f, "{}", quote)?;
+
+    for c in string.chars() {
+        if c == quote || c == '\\' {
+            write!(f, "\\")?;
+        }
+
+        write!(f, "{}", c)?;
+    }
+
+    write!(f, "{}", quote)
+}
+
+fn write_key(f: &mut fmt::Formatter, key: &str) -> fmt::Result {
+    match !key.is_empty() && key.chars().all(unquoted_char) {
+        true => write!(f, "{}", key),
+        false => write_string(f, key),
+    }
+}
+
+fn write_indent(f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+    write!(f, "{:1$}", "", depth * 4)
+}
+
+// Compact like vanilla's `toString` by default, or indented with `{:#}`, where only compounds and lists of them or
+// of other lists get a line for every element.
+fn write_elements<T>(
+    f: &mut fmt::Formatter,
+    open: &str,
+    close: &str,
+    elements: impl ExactSizeIterator<Item = T>,
+    multiline: bool,
+    depth: usize,
+    mut write: impl FnMut(&mut fmt::Formatter, T) -> fmt::Result,
+) -> fmt::Result {
+    let pretty = f.alternate();
+    let multiline = pretty && multiline && elements.len() > 0;
+
+    write!(f, "{}", open)?;
+
+    for (i, element) in elements.enumerate() {
+        if i > 0 {
+            write!(f, "{}", if pretty && !multiline { ", " } else { "," })?;
+        }
+
+        if multiline {
+            writeln!(f)?;
+            write_indent(f, depth + 1)?;
+        }
+
+        write(f, element)?;
+    }
+
+    if multiline {
+        writeln!(f)?;
+        write_indent(f, depth)?;
+    }
+
+    write!(f, "{}", close)
+}
+
+fn write_tag(f: &mut fmt::Formatter, tag: &Tag, depth: usize) -> fmt::Result {
+    let pretty = f.alternate();
+    let array_open = |kind| if pretty { format!("[{}; ", kind) } else { format!("[{};", kind) };
+
+    match tag {
+        Tag::Byte(value) => write!(f, "{}b", value),
+        Tag::Short(value) => write!(f, "{}s", value),
+        Tag::Int(value) => write!(f, "{}", value),
+        Tag::Long(value) => write!(f, "{}L", value),
+        Tag::Float(value) if !value.is_finite() => write!(f, "{}f", non_finite_name(*value as f64)),
+        Tag::Double(value) if !value.is_finite() => write!(f, "{}d", non_finite_name(*value)),
+        // Debug always has a dot or an exponent, so they read back as the same number.
+        Tag::Float(value) => write!(f, "{:?}f", value),
+        Tag::Double(value) => write!(f, "{:?}d", value),
+        Tag::ByteArray(values) => {
+            write_elements(f, &array_open('B'), "]", values.iter(), false, depth, |f, value| write!(f, "{}b", value))
+        }
+        Tag::String(value) => write_string(f, value),
+        Tag::List(tags) => {
+            let multiline = tags.first().is_some_and(|tag| matches!(tag, Tag::Compound(_) | Tag::List(_)));
+
+            write_elements(f, "[", "]", tags.iter(), multiline, depth, |f, tag| write_tag(f, tag, depth + 1))
+        }
+        Tag::Compound(compound) => write_compound(f, compound, depth),
+        Tag::IntArray(values) => write_elements(f, &array_open('I'), "]", values.iter(), false, depth, |f, value| write!(f, "{}", value)),
+        Tag::LongArray(values) => {
+            write_elements(f, &array_open('L'), "]", values.iter(), false, depth, |f, value| write!(f, "{}L", value))
+        }
+    }
+}
+
+fn write_compound(f: &mut fmt::Formatter, compound: &Compound, depth: usize) -> fmt::Result {
+    write_elements(f, "{", "}", compound.0.iter(), true, depth, |f, (key, tag)| {
+        write_key(f, key)?;
+        write!(f, "{}", if f.alternate() { ": " } else { ":" })?;
+        write_tag(f, tag, depth + 1)
+    })
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tag(f, self, 0)
+    }
+}
+
+impl fmt::Display for Compound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_compound(f, self, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::nbt::tests::tag;
+    use crate::nbt::{Flavor, Nbt};
+
+    const SWORD: &str = r#"{Damage:3,Enchantments:[{id:"minecraft:sharpness",lvl:5s},{id:"minecraft:unbreaking",lvl:3s}],display:{Name:'{"text":"Excalibur"}'}}"#;
+
+    #[test]
+    fn literals_test() -> Result<()> {
+        let vals = [
+            ("1b", Tag::Byte(1)),
+            ("-128B", Tag::Byte(-128)),
+            ("true", Tag::Byte(1)),
+            ("false", Tag::Byte(0)),
+            ("300s", Tag::Short(300)),
+            ("25565", Tag::Int(25565)),
+            ("+7", Tag::Int(7)),
+            ("9000000000L", Tag::Long(9000000000)),
+            ("0.5f", Tag::Float(0.5)),
+            ("1f", Tag::Float(1.0)),
+            ("1e3F", Tag::Float(1000.0)),
+            ("1.5", Tag::Double(1.5)),
+            (".5", Tag::Double(0.5)),
+            ("2d", Tag::Double(2.0)),
+            ("-1.5e-3d", Tag::Double(-0.0015)),
+            // None of these are numbers vanilla would read.
+            ("128b", Tag::String("128b".to_owned())),
+            ("01", Tag::String("01".to_owned())),
+            ("1e3", Tag::String("1e3".to_owned())),
+            ("Infinityf", Tag::String("Infinityf".to_owned())),
+            ("-Infinityd", Tag::String("-Infinityd".to_owned())),
+            ("NaNf", Tag::String("NaNf".to_owned())),
+            ("nan", Tag::String("nan".to_owned())),
+            ("NaN", Tag::String("NaN".to_owned())),
+            ("inff", Tag::String("inff".to_owned())),
+            ("1e39f", Tag::String("1e39f".to_owned())),
+            ("2e400d", Tag::String("2e400d".to_owned())),
+            ("minecraft.stone", Tag::String("minecraft.stone".to_owned())),
+            (r#""a \"b\" \\ c""#, Tag::String(r#"a "b" \ c"#.to_owned())),
+        ];
+
+        for (text, tag) in vals {
+            assert_eq!(parse(text)?, tag, "{}", text);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compound_test() -> Result<()> {
+        let compound: Compound = SWORD.parse()?;
+
+        assert_eq!(compound.get("Damage"), Some(&Tag::Int(3)));
+        assert!(matches!(compound.get("Enchantments"), Some(Tag::List(enchantments)) if enchantments.len() == 2));
+
+        // Vanilla's order and quoting, so it comes out the same.
+        assert_eq!(compound.to_string(), SWORD);
+
+        let arrays: Compound = "{ 'a key' : [B; 1b, 2B], ints: [I;], longs: [L;-1L, 2l] }".parse()?;
+        assert_eq!(arrays.get("a key"), Some(&Tag::ByteArray(vec![1, 2])));
+        assert_eq!(arrays.get("ints"), Some(&Tag::IntArray(vec![])));
+        assert_eq!(arrays.get("longs"), Some(&Tag::LongArray(vec![-1, 2])));
+        assert_eq!(arrays.to_string(), r#"{"a key":[B;1b,2b],ints:[I;],longs:[L;-1L,2L]}"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_test() -> Result<()> {
+        let command = "diamond_sword{Damage:3} 1";
+        let (tag, len) = parse_prefix(&command[13..])?;
+
+        assert_eq!(tag.to_string(), "{Damage:3}");
+        assert_eq!(&command[13 + len..], " 1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_test() {
+        let vals = [
+            ("", 0, "expected a value"),
+            ("{a:1", 4, "expected ',' or '}'"),
+            ("{a 1}", 3, "expected ':'"),
+            ("{:1}", 1, "expected a key"),
+            ("[1, 2b]", 4, "lists can only hold one type of tag"),
+            ("[B; 1b, 300]", 8, "the array can't hold this type"),
+            ("[I; 1L]", 4, "the array can't hold this type"),
+            ("[B;1]", 3, "the array can't hold this type"),
+            ("[L;1L,2]", 6, "the array can't hold this type"),
+            ("[L;1,2]", 3, "the array can't hold this type"),
+            ("[1f, NaNf]", 5, "lists can only hold one type of tag"),
+            ("[1d, Infinityd]", 5, "lists can only hold one type of tag"),
+            ("{a: \"oops}", 4, "unterminated string"),
+            (r#""\n""#, 1, "invalid escape sequence"),
+            ("{} {}", 3, "expected the end"),
+            ("'it''s'", 4, "expected the end"),
+            // Positions are in characters, not bytes.
+            (r#"{"ü": [1, "ü"]}"#, 10, "lists can only hold one type of tag"),
+        ];
+
+        for (text, position, message) in vals {
+            let error = parse(text).unwrap_err();
+
+            assert_eq!((error.position, error.message.as_str()), (position, message), "{}", text);
+        }
+
+        let deep = "[".repeat(MAX_DEPTH + 1);
+        assert!(parse(&deep).unwrap_err().message.starts_with("nested deeper"));
+    }
+
+    #[test]
+    fn pretty_test() -> Result<()> {
+        let compound: Compound = SWORD.parse()?;
+
+        let pretty = [
+            "{",
+            "    Damage: 3,",
+            "    Enchantments: [",
+            "        {",
+            "            id: \"minecraft:sharpness\",",
+            "            lvl: 5s",
+            "        },",
+            "        {",
+            "            id: \"minecraft:unbreaking\",",
+            "            lvl: 3s",
+            "        }",
+            "    ],",
+            "    display: {",
+            "        Name: '{\"text\":\"Excalibur\"}'",
+            "    }",
+            "}",
+        ]
+        .join("\n");
+
+        assert_eq!(format!("{:#}", compound), pretty);
+        assert_eq!(pretty.parse::<Compound>()?, compound);
+
+        assert_eq!(format!("{:#}", parse("{list: [1, 2], empty: {}, array: [I;1,2]}")?), [
+            "{",
+            "    list: [1, 2],",
+            "    empty: {},",
+            "    array: [I; 1, 2]",
+            "}"
+        ]
+        .join("\n"));
+
+        Ok(())
+    }
+
+    // Every tag type through SNBT and back gives the same binary NBT.
+    #[test]
+    fn round_trip_test() -> Result<()> {
+        let text = concat!(
+            r#"{byte:-1b,short:-300s,int:25565,long:-9223372036854775808L,float:0.1f,double:-1.25d,big:1e300d,"#,
+            r#"bytes:[B;0b,-128b,127b],string:"Ünïcödé 🎉",quotes:'say "hi"',list:[[1],[]],empty:[],"#,
+            r#"compound:{"":{}},ints:[I;-2147483648,0],longs:[L;81985529216486895L,-1L]}"#,
+        );
+
+        let tag = parse(text)?;
+        assert_eq!(tag.to_string(), text);
+
+        let compound = match tag {
+            Tag::Compound(compound) => compound,
+            _ => unreachable!(),
+        };
+
+        let bytes = Nbt::new(compound).write(Flavor::File)?;
+        let read = Nbt::read(&bytes, Flavor::File)?;
+
+        assert_eq!(read.root.to_string(), text);
+        assert_eq!(format!("{:#}", read.root).parse::<Compound>()?, read.root);
+
+        Ok(())
+    }
+
+    // Written the way vanilla writes them, which it (and so we) then reads as strings.
+    #[test]
+    fn non_finite_test() -> Result<()> {
+        let tag = Tag::List(vec![Tag::Float(f32::NAN), Tag::Float(f32::INFINITY), Tag::Float(f32::NEG_INFINITY)]);
+        assert_eq!(tag.to_string(), "[NaNf,Infinityf,-Infinityf]");
+        let strings = ["NaNf", "Infinityf", "-Infinityf"].map(|value| Tag::String(value.to_owned()));
+        assert_eq!(parse(&tag.to_string())?, Tag::List(strings.into()));
+
+        let tag = Tag::Double(f64::NAN);
+        assert_eq!(tag.to_string(), "NaNd");
+        assert_eq!(parse(&tag.to_string())?, Tag::String("NaNd".to_owned()));
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn snbt_property_test(tag in tag()) {
+            prop_assert_eq!(parse(&tag.to_string())?, tag.clone());
+            prop_assert_eq!(parse(&format!("{:#}", tag))?, tag);
+        }
+
+        #[test]
+        fn snbt_arbitrary_text_test(text in any::<String>()) {
+            if let Ok(tag) = parse(&text) {
+                prop_assert_eq!(parse(&tag.to_string())?, tag);
+            }
+        }
+    }
+}
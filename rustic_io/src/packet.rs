@@ -0,0 +1,323 @@
+use bytes::Bytes;
+use scroll::{ctx, Pread, Pwrite};
+
+use crate::datatypes::var::VarInt;
+
+pub use rustic_utils::Packet;
+
+// The longest string the protocol allows, in UTF-16 code units like the vanilla server counts them.
+pub const MAX_STRING_LEN: usize = 32767;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+}
+
+pub trait Packet: Sized
+    + for<'a> ctx::TryFromCtx<'a, Error = scroll::Error>
+    + ctx::TryIntoCtx<Error = scroll::Error>
+    + ctx::MeasureWith<()>
+{
+    const ID: i32;
+    const STATE: ConnectionState;
+
+    // Reads the packet from a frame's payload (without the packet id), which it has to take up entirely.
+    fn decode(src: &[u8]) -> Result<Self, scroll::Error> {
+        // Not `gread` either, which also refuses empty buffers.
+        let (packet, offset) = Self::try_from_ctx(src, ())?;
+
+        if offset != src.len() {
+            return Err(scroll::Error::BadInput { size: offset, msg: "packet has trailing bytes" });
+        }
+
+        Ok(packet)
+    }
+
+    // Writes the packet into a payload that can be handed to the frame codec together with `Self::ID`.
+    fn encode(self) -> Result<Bytes, scroll::Error> {
+        let mut bytes = vec![0; self.measure_with(&())];
+        // Not `pwrite`, which refuses to write anything at all into an empty buffer, even a packet without fields.
+        self.try_into_ctx(&mut bytes, ())?;
+
+        Ok(bytes.into())
+    }
+}
+
+pub fn read_length(src: &[u8], offset: &mut usize) -> Result<usize, scroll::Error> {
+    let VarInt(len) = src.gread(offset)?;
+
+    if len < 0 {
+        return Err(scroll::Error::BadInput { size: *offset, msg: "negative length" });
+    }
+
+    Ok(len as usize)
+}
+
+pub fn write_length(dst: &mut [u8], offset: &mut usize, len: usize) -> Result<(), scroll::Error> {
+    dst.gwrite(VarInt(len as i32), offset)?;
+
+    Ok(())
+}
+
+fn read_bytes<'a>(src: &'a [u8], offset: &mut usize, len: usize) -> Result<&'a [u8], scroll::Error> {
+    // scroll won't read even nothing at the very end of the buffer.
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    src.gread_with(offset, len)
+}
+
+pub fn read_string(src: &[u8], offset: &mut usize, max_len: usize) -> Result<String, scroll::Error> {
+    let len = read_length(src, offset)?;
+
+    // A code unit never takes more than 3 bytes (characters that take 4 are 2 units), so anything longer can be thrown out
+    // before reading it, like vanilla does.
+    if len > max_len.saturating_mul(3) {
+        return Err(scroll::Error::BadInput { size: len, msg: "string is too long" });
+    }
+
+    let bytes = read_bytes(src, offset, len)?;
+    let string = std::str::from_utf8(bytes)
+        .map_err(|_| scroll::Error::BadInput { size: len, msg: "string is not valid UTF-8" })?;
+
+    if string.encode_utf16().count() > max_len {
+        return Err(scroll::Error::BadInput { size: len, msg: "string is too long" });
+    }
+
+    Ok(string.to_owned())
+}
+
+pub fn write_string(dst: &mut [u8], offset: &mut usize, string: &str) -> Result<(), scroll::Error> {
+    write_byte_array(dst, offset, string.as_bytes())
+}
+
+pub fn string_len(string: &str) -> usize {
+    byte_array_len(string.as_bytes())
+}
+
+pub fn read_byte_array(src: &[u8], offset: &mut usize) -> Result<Vec<u8>, scroll::Error> {
+    let len = read_length(src, offset)?;
+    let bytes = read_bytes(src, offset, len)?;
+
+    Ok(bytes.to_vec())
+}
+
+pub fn write_byte_array(dst: &mut [u8], offset: &mut usize, bytes: &[u8]) -> Result<(), scroll::Error> {
+    write_length(dst, offset, bytes.len())?;
+    write_bytes(dst, offset, bytes)
+}
+
+pub fn byte_array_len(bytes: &[u8]) -> usize {
+    VarInt(bytes.len() as i32).encoded_len() + bytes.len()
+}
+
+pub fn write_bytes(dst: &mut [u8], offset: &mut usize, bytes: &[u8]) -> Result<(), scroll::Error> {
+    // scroll won't write even nothing at the very end of the buffer.
+    if bytes.is_empty() {
+        return Ok(());
+    }
+
+    dst.gwrite(bytes, offset)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::datatypes::{position::Position, var::{VarInt, VarLong}};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Handshaking)]
+    struct Handshake {
+        protocol_version: VarInt,
+        #[packet(max_len = 255)]
+        server_address: String,
+        server_port: u16,
+        next_state: VarInt,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    struct Property {
+        name: String,
+        value: String,
+        signature: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x2a, state = Play)]
+    struct Everything {
+        flag: bool,
+        position: Position,
+        time: VarLong,
+        properties: Vec<Property>,
+        ids: Vec<Option<VarInt>>,
+        data: Vec<u8>,
+        angle: f32,
+        #[packet(rest)]
+        rest: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Status)]
+    struct Request;
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x02, state = Login)]
+    struct PluginResponse {
+        message_id: VarInt,
+        #[packet(rest)]
+        data: Option<Vec<u8>>,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x01, state = Play)]
+    struct Trailing {
+        id: VarInt,
+        data: Vec<u8>,
+        name: String,
+    }
+
+    #[test]
+    fn packet_encode_test() -> Result<()> {
+        assert_eq!(Handshake::ID, 0x00);
+        assert_eq!(Handshake::STATE, ConnectionState::Handshaking);
+
+        let handshake = Handshake {
+            protocol_version: VarInt(758),
+            server_address: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+
+        let mut expected = vec![0xf6, 0x05, 0x09];
+        expected.extend(b"localhost");
+        expected.extend([0x63, 0xdd, 0x01]);
+
+        assert_eq!(&handshake.encode()?[..], &expected[..]);
+        assert_eq!(Request.encode()?.len(), 0);
+        assert_eq!(Request::decode(&[])?, Request);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packet_round_trip_test() -> Result<()> {
+        let packet = Everything {
+            flag: true,
+            position: Position { x: 111560, y: 333, z: 47 },
+            time: VarLong(-1),
+            properties: vec![
+                Property { name: "textures".to_owned(), value: "e30=".to_owned(), signature: None },
+                Property { name: "ünicode".to_owned(), value: String::new(), signature: Some("sig".to_owned()) },
+            ],
+            ids: vec![Some(VarInt(300)), None, Some(VarInt(-5))],
+            data: vec![1, 2, 3],
+            angle: 0.5,
+            rest: vec![0xca, 0xfe],
+        };
+
+        let bytes = Everything::decode(&packet_bytes())?.encode()?;
+        assert_eq!(&bytes[..], &packet_bytes()[..]);
+
+        assert_eq!(Everything::decode(&bytes)?, packet);
+
+        // Empty byte arrays at the very end still have to encode.
+        let empty = Everything { rest: Vec::new(), ..packet };
+        assert_eq!(Everything::decode(&empty.encode()?)?.rest, Vec::<u8>::new());
+
+        // Same for reading an empty string there.
+        assert_eq!(read_string(&[0x00], &mut 0, MAX_STRING_LEN)?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_test() -> Result<()> {
+        // Nothing at all, like Status Request.
+        assert_eq!(Request::decode(&[])?, Request);
+
+        // Empty arrays and strings, the last one with nothing left after its length.
+        let empty = || Trailing { id: VarInt(1), data: Vec::new(), name: String::new() };
+        let bytes = empty().encode()?;
+
+        assert_eq!(&bytes[..], [0x01, 0x00, 0x00]);
+        assert_eq!(Trailing::decode(&bytes)?, empty());
+
+        let mut offset = 1;
+        assert_eq!(read_byte_array(&[0x01, 0x00], &mut offset)?, Vec::<u8>::new());
+        assert_eq!(offset, 2);
+
+        let mut dst = [0; 1];
+        let mut offset = 1;
+        write_bytes(&mut dst, &mut offset, &[])?;
+        assert_eq!(offset, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn optional_rest_test() -> Result<()> {
+        let answered = PluginResponse { message_id: VarInt(7), data: Some(vec![1, 2, 3]) };
+        assert_eq!(&answered.encode()?[..], [0x07, 0x01, 1, 2, 3]);
+        assert_eq!(PluginResponse::decode(&[0x07, 0x01, 1, 2, 3])?.data, Some(vec![1, 2, 3]));
+
+        let unknown = PluginResponse { message_id: VarInt(7), data: None };
+        assert_eq!(&unknown.encode()?[..], [0x07, 0x00]);
+        assert_eq!(PluginResponse::decode(&[0x07, 0x00])?.data, None);
+        // Nothing may follow if there's no data.
+        assert!(PluginResponse::decode(&[0x07, 0x00, 0x01]).is_err());
+
+        Ok(())
+    }
+
+    fn packet_bytes() -> Vec<u8> {
+        let mut bytes = vec![0x01];
+        bytes.extend([0x00, 0x6c, 0xf2, 0x00, 0x00, 0x02, 0xf1, 0x4d]);
+        bytes.extend([0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        bytes.extend([0x02, 0x08]);
+        bytes.extend(b"textures");
+        bytes.extend([0x04]);
+        bytes.extend(b"e30=");
+        bytes.extend([0x00, 0x08]);
+        bytes.extend("ünicode".as_bytes());
+        bytes.extend([0x00, 0x01, 0x03]);
+        bytes.extend(b"sig");
+        bytes.extend([0x03, 0x01, 0xac, 0x02, 0x00, 0x01, 0xfb, 0xff, 0xff, 0xff, 0x0f]);
+        bytes.extend([0x03, 1, 2, 3]);
+        bytes.extend([0x3f, 0x00, 0x00, 0x00]);
+        bytes.extend([0xca, 0xfe]);
+        bytes
+    }
+
+    #[test]
+    fn packet_decode_errors_test() {
+        // The address is longer than the 255 characters it's allowed to be.
+        let mut bytes = vec![0xf6, 0x05, 0x80, 0x02];
+        bytes.extend([b'a'; 256]);
+        bytes.extend([0x63, 0xdd, 0x01]);
+        assert!(Handshake::decode(&bytes).is_err());
+
+        // Cut off in the middle of the address.
+        assert!(Handshake::decode(&[0xf6, 0x05, 0x09, b'l', b'o']).is_err());
+
+        // Invalid UTF-8 in the address.
+        assert!(Handshake::decode(&[0xf6, 0x05, 0x01, 0xff, 0x63, 0xdd, 0x01]).is_err());
+
+        // A valid packet followed by garbage.
+        assert!(Request::decode(&[0x00]).is_err());
+
+        // An array that claims to be a lot longer than the packet is.
+        let mut bytes = packet_bytes();
+        bytes[19] = 0xff;
+        bytes.insert(20, 0x7f);
+        assert!(Everything::decode(&bytes).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use bevy_ecs::prelude::*;
+
+// Where an entity is, in blocks. For players that's their feet.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Position {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    // The chunk the position is in, as chunk coordinates.
+    pub fn chunk(&self) -> (i32, i32) {
+        ((self.x.floor() as i32) >> 4, (self.z.floor() as i32) >> 4)
+    }
+}
+
+// In degrees, like the protocol has them. A yaw of 0 faces south (+z), a pitch of -90 straight up.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rotation {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+// In blocks per tick.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Velocity {
+    // What Set Entity Velocity sends, in 1/8000 of a block per tick and capped at 3.9 blocks per tick like vanilla does.
+    pub fn to_protocol(&self) -> [i16; 3] {
+        [self.x, self.y, self.z].map(|value| (value.clamp(-3.9, 3.9) * 8000.0) as i16)
+    }
+}
+
+// What packets call the entity by. Unique among the entities in the world while it's there.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(pub i32);
+
+// Hands out entity ids. They start from 1, like vanilla, which some packets rely on to mean "none" with 0.
+#[derive(Resource, Debug, Default)]
+pub struct EntityIds {
+    last: i32,
+}
+
+impl EntityIds {
+    pub fn allocate(&mut self) -> EntityId {
+        self.last = self.last.wrapping_add(1);
+
+        EntityId(self.last)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uuid(pub uuid::Uuid);
+
+// Only players have one.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Username(pub String);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    // The id in Join Game and Change Game State.
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(GameMode::Survival),
+            1 => Some(GameMode::Creative),
+            2 => Some(GameMode::Adventure),
+            3 => Some(GameMode::Spectator),
+            _ => None,
+        }
+    }
+}
+
+// In half hearts, players have 20 of them.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Health(pub f32);
+
+impl Health {
+    pub const MAX: f32 = 20.0;
+
+    pub fn is_dead(&self) -> bool {
+        self.0 <= 0.0
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self(Self::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_chunk_test() {
+        assert_eq!(Position::new(0.0, 64.0, 15.9).chunk(), (0, 0));
+        assert_eq!(Position::new(16.0, 64.0, -0.1).chunk(), (1, -1));
+        assert_eq!(Position::new(-16.5, 64.0, -32.0).chunk(), (-2, -2));
+    }
+
+    #[test]
+    fn components_test() {
+        assert_eq!(Velocity { x: 0.5, y: -10.0, z: 0.0 }.to_protocol(), [4000, -31200, 0]);
+
+        for mode in [GameMode::Survival, GameMode::Creative, GameMode::Adventure, GameMode::Spectator] {
+            assert_eq!(GameMode::from_id(mode.id()), Some(mode));
+        }
+        assert_eq!(GameMode::from_id(4), None);
+
+        assert_eq!(Health::default(), Health(20.0));
+        assert!(Health(0.0).is_dead());
+
+        let mut ids = EntityIds::default();
+        assert_eq!(ids.allocate(), EntityId(1));
+        assert_eq!(ids.allocate(), EntityId(2));
+    }
+}
@@ -1,8 +1,8 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }
-}
+// What entities are made of: where they are, who they are and how they're doing
+pub mod components;
+
+// Turning what players send into events for the systems, and what systems send back into packets
+pub mod network;
+
+// The stages every tick goes through, and the loop that runs them 20 times a second
+pub mod tick;
@@ -0,0 +1,697 @@
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+use rustic_io::connection::ConnectionId;
+use rustic_io::datatypes::position::Position as BlockPosition;
+use rustic_types::protocol::{ClientboundEvent, ServerboundEvent};
+use rustic_types::text::TextComponent;
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::components::{EntityIds, GameMode, Health, Position, Rotation, Username, Uuid};
+use crate::tick::{Game, Stage};
+
+// How much can be queued up between two ticks for each player, on its way in and out. A connection that fills either
+// of them is dropped instead of making the server hold on to whatever it sends.
+pub const INCOMING_CAPACITY: usize = 1024;
+pub const OUTGOING_CAPACITY: usize = 4096;
+
+// Players joining and leaving, for all connections together. Those wait for room instead.
+const MEMBERSHIP_CAPACITY: usize = 256;
+
+// How far from the origin players can go, vanilla keeps them inside the same bounds.
+const MAX_HORIZONTAL: f64 = 3.0e7;
+const MAX_VERTICAL: f64 = 2.0e7;
+
+// What vanilla kicks players with for moving somewhere that isn't a number.
+const INVALID_MOVE: &str = "Invalid move player packet received";
+
+// What the connections tell the game. They never wait for a tick, they just queue it up and every tick takes
+// whatever came in since the last one. Each player's packets have a channel of their own.
+#[derive(Debug)]
+pub enum Incoming {
+    Joined {
+        connection: ConnectionId,
+        username: String,
+        uuid: uuid::Uuid,
+        packets: Receiver<ServerboundEvent>,
+        outgoing: Sender<ClientboundEvent>,
+    },
+    Left {
+        connection: ConnectionId,
+    },
+}
+
+// The connections' end of the channel, one clone for each of them.
+#[derive(Debug, Clone)]
+pub struct NetworkHandle {
+    incoming: Sender<Incoming>,
+}
+
+// The game couldn't keep up with what the connection sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full;
+
+// Sending only fails once the game is gone, and then there's nobody left to tell anyway. Joining and leaving wait for
+// room, they only happen once for every connection and the game can't lose track of them.
+impl NetworkHandle {
+    pub async fn join(&self, connection: ConnectionId, username: String, uuid: uuid::Uuid) -> Link {
+        let (packets, received_packets) = mpsc::channel(INCOMING_CAPACITY);
+        let (outgoing, received) = mpsc::channel(OUTGOING_CAPACITY);
+
+        let joined = Incoming::Joined { connection, username, uuid, packets: received_packets, outgoing };
+        let _ = self.incoming.send(joined).await;
+
+        Link { packets, outgoing: received }
+    }
+
+    pub async fn leave(&self, connection: ConnectionId) {
+        let _ = self.incoming.send(Incoming::Left { connection }).await;
+    }
+}
+
+// One player's connection to the game, from joining until it leaves.
+#[derive(Debug)]
+pub struct Link {
+    packets: Sender<ServerboundEvent>,
+    outgoing: Receiver<ClientboundEvent>,
+}
+
+impl Link {
+    // `Full` means the connection sent more than a tick's worth and should be closed. Nobody else's packets count
+    // towards that.
+    pub fn packet(&self, event: ServerboundEvent) -> Result<(), Full> {
+        match self.packets.try_send(event) {
+            Err(TrySendError::Full(_)) => Err(Full),
+            _ => Ok(()),
+        }
+    }
+
+    // Whatever the systems send to the player. `None` once the game dropped them, because they were kicked or fell too
+    // far behind on reading it.
+    pub async fn recv(&mut self) -> Option<ClientboundEvent> {
+        self.outgoing.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Result<ClientboundEvent, TryRecvError> {
+        self.outgoing.try_recv()
+    }
+}
+
+// A player in the game and what it sent since the last tick.
+#[derive(Debug)]
+struct Connected {
+    player: Entity,
+    packets: Receiver<ServerboundEvent>,
+    // Kicked, but the connection hasn't hung up yet. Nothing it still sends counts.
+    kicked: bool,
+}
+
+// The game's end of the channel, and which player is on which connection.
+#[derive(Resource, Debug)]
+pub struct Network {
+    incoming: Receiver<Incoming>,
+    players: HashMap<ConnectionId, Connected>,
+}
+
+impl Network {
+    pub fn player(&self, connection: ConnectionId) -> Option<Entity> {
+        self.players.get(&connection).map(|connected| connected.player)
+    }
+}
+
+// A player's connection, and where to put what should be sent over it.
+#[derive(Component, Debug)]
+pub struct Client {
+    pub connection: ConnectionId,
+    outgoing: Sender<ClientboundEvent>,
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PlayerJoined {
+    pub player: Entity,
+}
+
+// The player's entity is already gone by the time anyone reads this.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PlayerLeft {
+    pub player: Entity,
+    pub username: String,
+}
+
+// Whatever the client said changed, the rest stays how it was.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PlayerMoved {
+    pub player: Entity,
+    pub position: Option<Position>,
+    pub rotation: Option<Rotation>,
+    pub on_ground: bool,
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub player: Entity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Main,
+    Off,
+}
+
+impl Hand {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Hand::Main),
+            1 => Some(Hand::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockFace {
+    Bottom,
+    Top,
+    North,
+    South,
+    West,
+    East,
+}
+
+impl BlockFace {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(BlockFace::Bottom),
+            1 => Some(BlockFace::Top),
+            2 => Some(BlockFace::North),
+            3 => Some(BlockFace::South),
+            4 => Some(BlockFace::West),
+            5 => Some(BlockFace::East),
+            _ => None,
+        }
+    }
+}
+
+// Player Digging is also how the client drops items and swaps hands, not only how it breaks blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DigStatus {
+    Started,
+    Cancelled,
+    Finished,
+    DropItemStack,
+    DropItem,
+    // Done eating, drawing a bow and the like.
+    ReleaseUseItem,
+    SwapItemInHand,
+}
+
+impl DigStatus {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(DigStatus::Started),
+            1 => Some(DigStatus::Cancelled),
+            2 => Some(DigStatus::Finished),
+            3 => Some(DigStatus::DropItemStack),
+            4 => Some(DigStatus::DropItem),
+            5 => Some(DigStatus::ReleaseUseItem),
+            6 => Some(DigStatus::SwapItemInHand),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct BlockDig {
+    pub player: Entity,
+    pub status: DigStatus,
+    pub location: BlockPosition,
+    pub face: BlockFace,
+}
+
+// The block is placed against `location`, on its `face`.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct BlockPlace {
+    pub player: Entity,
+    pub hand: Hand,
+    pub location: BlockPosition,
+    pub face: BlockFace,
+    // Where on that face the player clicked, from 0 to 1.
+    pub cursor: [f32; 3],
+    pub inside_block: bool,
+}
+
+// Right clicking with an item, but not at a block.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct UseItem {
+    pub player: Entity,
+    pub hand: Hand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Player(Entity),
+    All,
+    AllExcept(Entity),
+}
+
+// How systems send packets. Each player's connection encodes it for the version it speaks.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct SendPacket {
+    pub target: Target,
+    pub event: ClientboundEvent,
+}
+
+impl SendPacket {
+    pub fn to(player: Entity, event: ClientboundEvent) -> Self {
+        Self { target: Target::Player(player), event }
+    }
+
+    pub fn all(event: ClientboundEvent) -> Self {
+        Self { target: Target::All, event }
+    }
+}
+
+#[derive(SystemParam)]
+struct PlayerEvents<'w> {
+    joined: EventWriter<'w, PlayerJoined>,
+    left: EventWriter<'w, PlayerLeft>,
+    moved: EventWriter<'w, PlayerMoved>,
+    chat: EventWriter<'w, ChatMessage>,
+    dig: EventWriter<'w, BlockDig>,
+    place: EventWriter<'w, BlockPlace>,
+    use_item: EventWriter<'w, UseItem>,
+}
+
+// Hooks the network up to the game, the returned handle goes to the connections.
+pub fn install(game: &mut Game) -> NetworkHandle {
+    let (incoming, received) = mpsc::channel(MEMBERSHIP_CAPACITY);
+
+    game.insert_resource(Network { incoming: received, players: HashMap::new() })
+        .add_event::<PlayerJoined>()
+        .add_event::<PlayerLeft>()
+        .add_event::<PlayerMoved>()
+        .add_event::<ChatMessage>()
+        .add_event::<BlockDig>()
+        .add_event::<BlockPlace>()
+        .add_event::<UseItem>()
+        .add_event::<SendPacket>()
+        .add_systems(Stage::NetworkInput, receive)
+        .add_systems(Stage::GameLogic, apply_movement)
+        .add_systems(Stage::NetworkOutput, send);
+
+    NetworkHandle { incoming }
+}
+
+fn receive(
+    mut commands: Commands,
+    mut network: ResMut<Network>,
+    mut ids: ResMut<EntityIds>,
+    usernames: Query<&Username>,
+    clients: Query<&Client>,
+    mut events: PlayerEvents,
+) {
+    let network = &mut *network;
+
+    // What the players that are already in the game sent, before anyone leaves. Only what's there already, otherwise a
+    // busy connection could keep the tick from ever ending.
+    for connected in network.players.values_mut().filter(|connected| !connected.kicked) {
+        for _ in 0..connected.packets.len() {
+            let Ok(event) = connected.packets.try_recv() else {
+                break;
+            };
+
+            if let Err(reason) = synthesize(connected.player, event, &mut events) {
+                if let Ok(client) = clients.get(connected.player) {
+                    let _ = client.outgoing.try_send(ClientboundEvent::Disconnect { reason: TextComponent::text(reason).to_json() });
+                }
+
+                commands.entity(connected.player).remove::<Client>();
+                connected.kicked = true;
+                break;
+            }
+        }
+    }
+
+    for _ in 0..network.incoming.len() {
+        let Ok(incoming) = network.incoming.try_recv() else {
+            break;
+        };
+
+        match incoming {
+            Incoming::Joined { connection, username, uuid, packets, outgoing } => {
+                let player = commands
+                    .spawn((
+                        Client { connection, outgoing },
+                        Username(username),
+                        Uuid(uuid),
+                        ids.allocate(),
+                        Position::default(),
+                        Rotation::default(),
+                        GameMode::default(),
+                        Health::default(),
+                    ))
+                    .id();
+
+                network.players.insert(connection, Connected { player, packets, kicked: false });
+                events.joined.write(PlayerJoined { player });
+            }
+            Incoming::Left { connection } => {
+                // Whatever it sent since is dropped with it.
+                let Some(Connected { player, .. }) = network.players.remove(&connection) else {
+                    continue;
+                };
+
+                // Joined in the same tick, so the entity isn't there yet for the query.
+                let username = usernames.get(player).map(|username| username.0.clone()).unwrap_or_default();
+
+                commands.entity(player).despawn();
+                events.left.write(PlayerLeft { player, username });
+            }
+        }
+    }
+}
+
+// Like vanilla, coordinates that aren't numbers get the player kicked and the rest are kept inside the world.
+fn position(x: f64, y: f64, z: f64) -> Result<Position, &'static str> {
+    if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+        return Err(INVALID_MOVE);
+    }
+
+    let horizontal = |value: f64| value.clamp(-MAX_HORIZONTAL, MAX_HORIZONTAL);
+    Ok(Position::new(horizontal(x), y.clamp(-MAX_VERTICAL, MAX_VERTICAL), horizontal(z)))
+}
+
+fn rotation(yaw: f32, pitch: f32) -> Result<Rotation, &'static str> {
+    if !(yaw.is_finite() && pitch.is_finite()) {
+        return Err(INVALID_MOVE);
+    }
+
+    Ok(Rotation { yaw, pitch })
+}
+
+// Packets without an event of their own here, or with values that don't mean anything, are left out. The error is why
+// the player should be kicked instead.
+fn synthesize(player: Entity, event: ServerboundEvent, events: &mut PlayerEvents) -> Result<(), &'static str> {
+    match event {
+        ServerboundEvent::PlayerPosition { x, y, z, on_ground } => {
+            events.moved.write(PlayerMoved { player, position: Some(position(x, y, z)?), rotation: None, on_ground });
+        }
+        ServerboundEvent::PlayerPositionAndRotation { x, y, z, yaw, pitch, on_ground } => {
+            let (position, rotation) = (Some(position(x, y, z)?), Some(rotation(yaw, pitch)?));
+            events.moved.write(PlayerMoved { player, position, rotation, on_ground });
+        }
+        ServerboundEvent::PlayerRotation { yaw, pitch, on_ground } => {
+            events.moved.write(PlayerMoved { player, position: None, rotation: Some(rotation(yaw, pitch)?), on_ground });
+        }
+        ServerboundEvent::PlayerOnGround { on_ground } => {
+            events.moved.write(PlayerMoved { player, position: None, rotation: None, on_ground });
+        }
+        ServerboundEvent::ChatMessage { message } => {
+            events.chat.write(ChatMessage { player, message });
+        }
+        ServerboundEvent::BlockDig { status, location, face } => {
+            if let (Some(status), Some(face)) = (DigStatus::from_id(status), BlockFace::from_id(face.into())) {
+                events.dig.write(BlockDig { player, status, location, face });
+            }
+        }
+        ServerboundEvent::BlockPlace { hand, location, face, cursor_x, cursor_y, cursor_z, inside_block } => {
+            if let (Some(hand), Some(face)) = (Hand::from_id(hand), BlockFace::from_id(face)) {
+                let cursor = [cursor_x, cursor_y, cursor_z];
+                events.place.write(BlockPlace { player, hand, location, face, cursor, inside_block });
+            }
+        }
+        ServerboundEvent::UseItem { hand } => {
+            if let Some(hand) = Hand::from_id(hand) {
+                events.use_item.write(UseItem { player, hand });
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn apply_movement(mut moved: EventReader<PlayerMoved>, mut players: Query<(&mut Position, &mut Rotation)>) {
+    for event in moved.read() {
+        let Ok((mut position, mut rotation)) = players.get_mut(event.player) else {
+            continue;
+        };
+
+        if let Some(moved) = event.position {
+            *position = moved;
+        }
+
+        if let Some(rotated) = event.rotation {
+            *rotation = rotated;
+        }
+    }
+}
+
+fn send(mut commands: Commands, mut packets: EventReader<SendPacket>, clients: Query<(Entity, &Client)>) {
+    let mut behind = Vec::new();
+
+    let mut send = |player: Entity, client: &Client, event: &ClientboundEvent| {
+        // A closed channel means the player is leaving, which the next tick hears about.
+        if let Err(TrySendError::Full(_)) = client.outgoing.try_send(event.clone()) {
+            behind.push(player);
+        }
+    };
+
+    for packet in packets.read() {
+        match packet.target {
+            Target::Player(player) => {
+                if let Ok((player, client)) = clients.get(player) {
+                    send(player, client, &packet.event);
+                }
+            }
+            Target::All => {
+                for (player, client) in &clients {
+                    send(player, client, &packet.event);
+                }
+            }
+            Target::AllExcept(except) => {
+                for (player, client) in clients.iter().filter(|(player, _)| *player != except) {
+                    send(player, client, &packet.event);
+                }
+            }
+        }
+    }
+
+    // Dropping the sender closes the channel, which is the connection's cue to hang up. Until it has, nothing more is
+    // sent to the player.
+    for player in behind {
+        commands.entity(player).remove::<Client>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::EntityId;
+
+    use super::*;
+
+    fn player(game: &Game, connection: ConnectionId) -> Entity {
+        game.world().resource::<Network>().player(connection).unwrap()
+    }
+
+    #[tokio::test]
+    async fn join_leave_test() {
+        let mut game = Game::new();
+        let network = install(&mut game);
+
+        let notch_link = network.join(1, "Notch".to_owned(), uuid::Uuid::from_u128(42)).await;
+        // Gone before it was ever in the world.
+        let _jeb = network.join(2, "jeb_".to_owned(), uuid::Uuid::from_u128(43)).await;
+        network.leave(2).await;
+        game.tick();
+
+        let notch = player(&game, 1);
+        assert_eq!(game.world().get::<Username>(notch), Some(&Username("Notch".to_owned())));
+        assert_eq!(game.world().get::<Uuid>(notch), Some(&Uuid(uuid::Uuid::from_u128(42))));
+        assert_eq!(game.world().get::<EntityId>(notch), Some(&EntityId(1)));
+        assert!(game.world().resource::<Network>().player(2).is_none());
+
+        let joined: Vec<_> = game.world().resource::<Events<PlayerJoined>>().iter_current_update_events().cloned().collect();
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0], PlayerJoined { player: notch });
+        assert!(game.world().get_entity(joined[1].player).is_err());
+
+        network.leave(1).await;
+        game.tick();
+
+        assert!(game.world().get_entity(notch).is_err());
+
+        let left: Vec<_> = game.world().resource::<Events<PlayerLeft>>().iter_current_update_events().cloned().collect();
+        assert_eq!(left, [PlayerLeft { player: notch, username: "Notch".to_owned() }]);
+
+        // Nobody is listening anymore.
+        assert_eq!(notch_link.packet(ServerboundEvent::ChatMessage { message: "too late".to_owned() }), Ok(()));
+        game.tick();
+
+        assert!(game.world().resource::<Events<ChatMessage>>().is_empty());
+    }
+
+    #[tokio::test]
+    async fn synthesize_test() {
+        let mut game = Game::new();
+        let network = install(&mut game);
+
+        let link = network.join(1, "Notch".to_owned(), uuid::Uuid::from_u128(42)).await;
+        game.tick();
+        let notch = player(&game, 1);
+
+        let location = BlockPosition::new(1, -2, 3).unwrap();
+
+        link.packet(ServerboundEvent::PlayerPosition { x: 0.5, y: 64.0, z: -0.5, on_ground: true }).unwrap();
+        link.packet(ServerboundEvent::PlayerRotation { yaw: 90.0, pitch: -45.0, on_ground: true }).unwrap();
+        link.packet(ServerboundEvent::BlockDig { status: 2, location, face: 1 }).unwrap();
+        // Not a real face.
+        link.packet(ServerboundEvent::BlockDig { status: 0, location, face: 9 }).unwrap();
+        link.packet(ServerboundEvent::BlockPlace {
+            hand: 1,
+            location,
+            face: 5,
+            cursor_x: 1.0,
+            cursor_y: 0.5,
+            cursor_z: 0.0,
+            inside_block: false,
+        }).unwrap();
+        link.packet(ServerboundEvent::UseItem { hand: 0 }).unwrap();
+        link.packet(ServerboundEvent::ChatMessage { message: "hi".to_owned() }).unwrap();
+        game.tick();
+
+        let world = game.world();
+        assert_eq!(world.get::<Position>(notch), Some(&Position::new(0.5, 64.0, -0.5)));
+        assert_eq!(world.get::<Rotation>(notch), Some(&Rotation { yaw: 90.0, pitch: -45.0 }));
+
+        let digs: Vec<_> = world.resource::<Events<BlockDig>>().iter_current_update_events().cloned().collect();
+        assert_eq!(digs, [BlockDig { player: notch, status: DigStatus::Finished, location, face: BlockFace::Top }]);
+
+        let places: Vec<_> = world.resource::<Events<BlockPlace>>().iter_current_update_events().cloned().collect();
+        assert_eq!(places.len(), 1);
+        assert_eq!((places[0].hand, places[0].face, places[0].cursor), (Hand::Off, BlockFace::East, [1.0, 0.5, 0.0]));
+
+        let uses: Vec<_> = world.resource::<Events<UseItem>>().iter_current_update_events().cloned().collect();
+        assert_eq!(uses, [UseItem { player: notch, hand: Hand::Main }]);
+
+        let chat: Vec<_> = world.resource::<Events<ChatMessage>>().iter_current_update_events().cloned().collect();
+        assert_eq!(chat, [ChatMessage { player: notch, message: "hi".to_owned() }]);
+    }
+
+    #[tokio::test]
+    async fn send_test() {
+        // Everyone hears what everyone else says.
+        fn echo(mut chat: EventReader<ChatMessage>, mut packets: EventWriter<SendPacket>) {
+            for message in chat.read() {
+                let event = ClientboundEvent::ChatMessage { message: message.message.clone() };
+                packets.write(SendPacket { target: Target::AllExcept(message.player), event });
+            }
+        }
+
+        let mut game = Game::new();
+        let network = install(&mut game);
+        game.add_systems(Stage::GameLogic, echo);
+
+        let mut notch = network.join(1, "Notch".to_owned(), uuid::Uuid::from_u128(42)).await;
+        let mut jeb = network.join(2, "jeb_".to_owned(), uuid::Uuid::from_u128(43)).await;
+        game.tick();
+
+        notch.packet(ServerboundEvent::ChatMessage { message: "hi".to_owned() }).unwrap();
+        game.tick();
+
+        assert_eq!(jeb.try_recv(), Ok(ClientboundEvent::ChatMessage { message: "hi".to_owned() }));
+        assert_eq!(jeb.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(notch.try_recv(), Err(TryRecvError::Empty));
+
+        let jeb_entity = player(&game, 2);
+        let disconnect = ClientboundEvent::Disconnect { reason: "bye".to_owned() };
+        game.world_mut().send_event(SendPacket::to(jeb_entity, disconnect.clone()));
+        game.world_mut().send_event(SendPacket::all(ClientboundEvent::KeepAlive { id: 7 }));
+        game.tick();
+
+        assert_eq!(jeb.try_recv(), Ok(disconnect));
+        assert_eq!(jeb.try_recv(), Ok(ClientboundEvent::KeepAlive { id: 7 }));
+        assert_eq!(notch.try_recv(), Ok(ClientboundEvent::KeepAlive { id: 7 }));
+    }
+
+    #[tokio::test]
+    async fn invalid_movement_test() {
+        let mut game = Game::new();
+        let network = install(&mut game);
+
+        let mut notch = network.join(1, "Notch".to_owned(), uuid::Uuid::from_u128(42)).await;
+        let jeb = network.join(2, "jeb_".to_owned(), uuid::Uuid::from_u128(43)).await;
+        game.tick();
+        let (notch_entity, jeb_entity) = (player(&game, 1), player(&game, 2));
+
+        // Far out, but still a place.
+        jeb.packet(ServerboundEvent::PlayerPosition { x: 1e300, y: -1e300, z: 0.5, on_ground: false }).unwrap();
+        notch.packet(ServerboundEvent::PlayerPosition { x: f64::NAN, y: 64.0, z: 0.0, on_ground: true }).unwrap();
+        notch.packet(ServerboundEvent::PlayerPosition { x: 1.0, y: 64.0, z: 1.0, on_ground: true }).unwrap();
+        game.tick();
+
+        let world = game.world();
+        assert_eq!(world.get::<Position>(jeb_entity), Some(&Position::new(MAX_HORIZONTAL, -MAX_VERTICAL, 0.5)));
+        assert_eq!(world.get::<Position>(notch_entity), Some(&Position::default()));
+        assert!(world.get::<Client>(notch_entity).is_none());
+
+        let reason = TextComponent::text(INVALID_MOVE).to_json();
+        assert_eq!(notch.try_recv(), Ok(ClientboundEvent::Disconnect { reason }));
+        assert_eq!(notch.try_recv(), Err(TryRecvError::Disconnected));
+
+        notch.packet(ServerboundEvent::PlayerRotation { yaw: 90.0, pitch: 0.0, on_ground: true }).unwrap();
+        jeb.packet(ServerboundEvent::PlayerRotation { yaw: f32::INFINITY, pitch: 0.0, on_ground: true }).unwrap();
+        game.tick();
+
+        assert_eq!(game.world().get::<Rotation>(notch_entity), Some(&Rotation::default()));
+        assert_eq!(game.world().get::<Rotation>(jeb_entity), Some(&Rotation::default()));
+        assert!(game.world().get::<Client>(jeb_entity).is_none());
+    }
+
+    #[tokio::test]
+    async fn full_test() {
+        fn on_ground(link: &Link) -> Result<(), Full> {
+            link.packet(ServerboundEvent::PlayerOnGround { on_ground: true })
+        }
+
+        let mut game = Game::new();
+        let network = install(&mut game);
+
+        let mut notch = network.join(1, "Notch".to_owned(), uuid::Uuid::from_u128(42)).await;
+        let jeb = network.join(2, "jeb_".to_owned(), uuid::Uuid::from_u128(43)).await;
+        game.tick();
+        let (notch_entity, jeb_entity) = (player(&game, 1), player(&game, 2));
+
+        // Only the one sending too much is told so.
+        for _ in 0..INCOMING_CAPACITY {
+            on_ground(&notch).unwrap();
+        }
+
+        assert_eq!(on_ground(&notch), Err(Full));
+        jeb.packet(ServerboundEvent::ChatMessage { message: "still here".to_owned() }).unwrap();
+        game.tick();
+
+        let chat: Vec<_> = game.world().resource::<Events<ChatMessage>>().iter_current_update_events().cloned().collect();
+        assert_eq!(chat, [ChatMessage { player: jeb_entity, message: "still here".to_owned() }]);
+        assert_eq!(on_ground(&notch), Ok(()));
+        assert!(game.world().get::<Client>(jeb_entity).is_some());
+
+        // A player that doesn't read what's sent to them.
+        for id in 0..=OUTGOING_CAPACITY as i64 {
+            game.world_mut().send_event(SendPacket::to(notch_entity, ClientboundEvent::KeepAlive { id }));
+        }
+
+        game.tick();
+        assert!(game.world().get::<Client>(notch_entity).is_none());
+        assert!(game.world().get::<Client>(jeb_entity).is_some());
+
+        for id in 0..OUTGOING_CAPACITY as i64 {
+            assert_eq!(notch.try_recv(), Ok(ClientboundEvent::KeepAlive { id }));
+        }
+
+        assert_eq!(notch.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use bevy_ecs::event::{event_update_system, EventRegistry};
+use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::ScheduleLabel;
+use bevy_ecs::system::ScheduleSystem;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::components::EntityIds;
+
+pub const TICKS_PER_SECOND: u64 = 20;
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+
+// Every tick goes through these in order: first whatever the players sent, then the game itself, then what has to be
+// sent back to them.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    NetworkInput,
+    GameLogic,
+    NetworkOutput,
+}
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameTick;
+
+// How many ticks have passed since the server started.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tick(pub u64);
+
+// The world and the systems that run on it every tick.
+pub struct Game {
+    world: World,
+    schedule: Schedule,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        let mut world = World::new();
+        world.init_resource::<Tick>();
+        world.init_resource::<EntityIds>();
+
+        let mut schedule = Schedule::new(GameTick);
+        schedule.configure_sets((Stage::NetworkInput, Stage::GameLogic, Stage::NetworkOutput).chain());
+        // Events stay around for the tick after the one they were sent in, so every system gets to see them once.
+        schedule.add_systems(event_update_system.before(Stage::NetworkInput));
+
+        Self { world, schedule }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn add_systems<M>(&mut self, stage: Stage, systems: impl IntoScheduleConfigs<ScheduleSystem, M>) -> &mut Self {
+        self.schedule.add_systems(systems.in_set(stage));
+        self
+    }
+
+    pub fn add_event<E: Event>(&mut self) -> &mut Self {
+        EventRegistry::register_event::<E>(&mut self.world);
+        self
+    }
+
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> &mut Self {
+        self.world.insert_resource(resource);
+        self
+    }
+
+    // Runs every stage once.
+    pub fn tick(&mut self) {
+        self.schedule.run(&mut self.world);
+
+        self.world.resource_mut::<Tick>().0 += 1;
+        // Otherwise components removed during this tick would still show up as removed in the next one.
+        self.world.clear_trackers();
+    }
+
+    // Ticks 20 times a second, for as long as the runtime is running. Ticks run on one of its worker threads, so
+    // systems can't block.
+    pub async fn run(mut self) {
+        let mut interval = time::interval(TICK_DURATION);
+        // Ticks that took too long push the next ones back instead of running them all at once to catch up, which
+        // would only make the game jump ahead.
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.tick();
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use crate::components::{EntityId, Position, Velocity};
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Ran(Vec<Stage>);
+
+    #[test]
+    fn stages_test() {
+        let mut game = Game::new();
+        game.world_mut().init_resource::<Ran>();
+
+        // Added backwards, but they still run in the order of their stages.
+        game.add_systems(Stage::NetworkOutput, |mut ran: ResMut<Ran>| ran.0.push(Stage::NetworkOutput));
+        game.add_systems(Stage::GameLogic, |mut ran: ResMut<Ran>| ran.0.push(Stage::GameLogic));
+        game.add_systems(Stage::NetworkInput, |mut ran: ResMut<Ran>| ran.0.push(Stage::NetworkInput));
+
+        game.tick();
+        game.tick();
+
+        let stages = [Stage::NetworkInput, Stage::GameLogic, Stage::NetworkOutput];
+        assert_eq!(game.world().resource::<Ran>().0, [stages, stages].concat());
+        assert_eq!(*game.world().resource::<Tick>(), Tick(2));
+    }
+
+    #[test]
+    fn movement_test() {
+        fn apply_velocity(mut entities: Query<(&mut Position, &Velocity)>) {
+            for (mut position, velocity) in &mut entities {
+                position.x += velocity.x;
+                position.y += velocity.y;
+                position.z += velocity.z;
+            }
+        }
+
+        let mut game = Game::new();
+        game.add_systems(Stage::GameLogic, apply_velocity);
+
+        let id = game.world_mut().resource_mut::<EntityIds>().allocate();
+        let entity = game.world_mut().spawn((id, Position::new(0.0, 64.0, 0.0), Velocity { x: 0.5, y: 0.0, z: -1.0 })).id();
+
+        game.tick();
+        game.tick();
+
+        assert_eq!(game.world().get::<Position>(entity), Some(&Position::new(1.0, 64.0, -2.0)));
+        assert_eq!(game.world().get::<EntityId>(entity), Some(&EntityId(1)));
+    }
+
+    #[test]
+    fn events_test() {
+        #[derive(Event)]
+        struct Ping(u64);
+
+        #[derive(Resource, Default)]
+        struct Seen(Vec<u64>);
+
+        let mut game = Game::new();
+        game.add_event::<Ping>().insert_resource(Seen::default());
+
+        // Sent after they're read in the same tick, so they only get read in the next one.
+        game.add_systems(Stage::GameLogic, |mut pings: EventReader<Ping>, mut seen: ResMut<Seen>| {
+            seen.0.extend(pings.read().map(|ping| ping.0))
+        });
+        game.add_systems(Stage::NetworkOutput, |mut pings: EventWriter<Ping>, tick: Res<Tick>| {
+            pings.write(Ping(tick.0));
+        });
+
+        for _ in 0..4 {
+            game.tick();
+        }
+
+        assert_eq!(game.world().resource::<Seen>().0, [0, 1, 2]);
+        // Nothing piles up.
+        assert!(game.world().resource::<Events<Ping>>().len() <= 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_test() {
+        let ticks = Arc::new(AtomicU64::new(0));
+        let counted = ticks.clone();
+
+        let mut game = Game::new();
+        game.add_systems(Stage::GameLogic, move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+
+        tokio::spawn(game.run());
+
+        // The first tick is right away, then one every 50ms.
+        time::sleep(Duration::from_millis(1025)).await;
+        assert_eq!(ticks.load(Ordering::Relaxed), 21);
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Write;
+
+use crate::protocol::{PacketInfo, Packets};
+
+// A packet that means the same thing in every version, even if its id or layout changes between them.
+struct Event {
+    name: &'static str,
+    state: &'static str,
+    // The packet's name in protocol.json.
+    packet: &'static str,
+    // `(field, type, name in protocol.json)`
+    fields: &'static [(&'static str, &'static str, &'static str)],
+    // What the packet's other fields are set to when sending the event, as `(name in protocol.json, type, value)`.
+    defaults: &'static [(&'static str, &'static str, &'static str)],
+}
+
+// Block positions and UUIDs are the same types in packets and events.
+const POSITION: &str = "rustic_io::datatypes::position::Position";
+const UUID: &str = "rustic_io::datatypes::numbers::Uuid";
+
+const SERVERBOUND: &[Event] = &[
+    Event {
+        name: "Handshake",
+        state: "handshaking",
+        packet: "set_protocol",
+        fields: &[
+            ("protocol_version", "i32", "protocolVersion"),
+            ("server_address", "String", "serverHost"),
+            ("server_port", "u16", "serverPort"),
+            ("next_state", "i32", "nextState"),
+        ],
+        defaults: &[],
+    },
+    Event { name: "StatusRequest", state: "status", packet: "ping_start", fields: &[], defaults: &[] },
+    Event { name: "StatusPing", state: "status", packet: "ping", fields: &[("payload", "i64", "time")], defaults: &[] },
+    Event { name: "LoginStart", state: "login", packet: "login_start", fields: &[("username", "String", "username")], defaults: &[] },
+    Event {
+        name: "EncryptionResponse",
+        state: "login",
+        packet: "encryption_begin",
+        fields: &[("shared_secret", "Vec<u8>", "sharedSecret"), ("verify_token", "Vec<u8>", "verifyToken")],
+        defaults: &[],
+    },
+    Event { name: "KeepAlive", state: "play", packet: "keep_alive", fields: &[("id", "i64", "keepAliveId")], defaults: &[] },
+    Event { name: "ChatMessage", state: "play", packet: "chat", fields: &[("message", "String", "message")], defaults: &[] },
+    Event {
+        name: "PlayerPosition",
+        state: "play",
+        packet: "position",
+        fields: &[("x", "f64", "x"), ("y", "f64", "y"), ("z", "f64", "z"), ("on_ground", "bool", "onGround")],
+        defaults: &[],
+    },
+    Event {
+        name: "PlayerPositionAndRotation",
+        state: "play",
+        packet: "position_look",
+        fields: &[
+            ("x", "f64", "x"),
+            ("y", "f64", "y"),
+            ("z", "f64", "z"),
+            ("yaw", "f32", "yaw"),
+            ("pitch", "f32", "pitch"),
+            ("on_ground", "bool", "onGround"),
+        ],
+        defaults: &[],
+    },
+    Event {
+        name: "PlayerRotation",
+        state: "play",
+        packet: "look",
+        fields: &[("yaw", "f32", "yaw"), ("pitch", "f32", "pitch"), ("on_ground", "bool", "onGround")],
+        defaults: &[],
+    },
+    Event { name: "PlayerOnGround", state: "play", packet: "flying", fields: &[("on_ground", "bool", "onGround")], defaults: &[] },
+    Event {
+        name: "BlockDig",
+        state: "play",
+        packet: "block_dig",
+        fields: &[("status", "i32", "status"), ("location", POSITION, "location"), ("face", "i8", "face")],
+        defaults: &[],
+    },
+    Event {
+        name: "BlockPlace",
+        state: "play",
+        packet: "block_place",
+        fields: &[
+            ("hand", "i32", "hand"),
+            ("location", POSITION, "location"),
+            ("face", "i32", "direction"),
+            ("cursor_x", "f32", "cursorX"),
+            ("cursor_y", "f32", "cursorY"),
+            ("cursor_z", "f32", "cursorZ"),
+            ("inside_block", "bool", "insideBlock"),
+        ],
+        defaults: &[],
+    },
+    Event { name: "UseItem", state: "play", packet: "use_item", fields: &[("hand", "i32", "hand")], defaults: &[] },
+];
+
+const CLIENTBOUND: &[Event] = &[
+    Event { name: "StatusResponse", state: "status", packet: "server_info", fields: &[("json", "String", "response")], defaults: &[] },
+    Event { name: "StatusPong", state: "status", packet: "ping", fields: &[("payload", "i64", "time")], defaults: &[] },
+    Event { name: "LoginDisconnect", state: "login", packet: "disconnect", fields: &[("reason", "String", "reason")], defaults: &[] },
+    Event {
+        name: "EncryptionRequest",
+        state: "login",
+        packet: "encryption_begin",
+        fields: &[
+            ("server_id", "String", "serverId"),
+            ("public_key", "Vec<u8>", "publicKey"),
+            ("verify_token", "Vec<u8>", "verifyToken"),
+        ],
+        defaults: &[],
+    },
+    Event {
+        name: "LoginSuccess",
+        state: "login",
+        packet: "success",
+        fields: &[("uuid", UUID, "uuid"), ("username", "String", "username")],
+        defaults: &[],
+    },
+    Event { name: "SetCompression", state: "login", packet: "compress", fields: &[("threshold", "i32", "threshold")], defaults: &[] },
+    Event { name: "KeepAlive", state: "play", packet: "keep_alive", fields: &[("id", "i64", "keepAliveId")], defaults: &[] },
+    Event {
+        name: "ChatMessage",
+        state: "play",
+        packet: "chat",
+        fields: &[("message", "String", "message")],
+        // A system message, from nobody.
+        defaults: &[("position", "i8", "1"), ("sender", UUID, "rustic_io::datatypes::numbers::Uuid(0)")],
+    },
+    Event { name: "Disconnect", state: "play", packet: "kick_disconnect", fields: &[("reason", "String", "reason")], defaults: &[] },
+    Event {
+        name: "BlockChange",
+        state: "play",
+        packet: "block_change",
+        fields: &[("location", POSITION, "location"), ("block_state", "i32", "type")],
+        defaults: &[],
+    },
+    Event {
+        name: "EntityTeleport",
+        state: "play",
+        packet: "entity_teleport",
+        fields: &[
+            ("entity_id", "i32", "entityId"),
+            ("x", "f64", "x"),
+            ("y", "f64", "y"),
+            ("z", "f64", "z"),
+            ("yaw", "i8", "yaw"),
+            ("pitch", "i8", "pitch"),
+            ("on_ground", "bool", "onGround"),
+        ],
+        defaults: &[],
+    },
+];
+
+// Type changes between versions that `Translate` in src/protocol.rs knows how to undo.
+const CONVERSIONS: &[(&str, &str)] = &[
+    ("VarInt", "i32"),
+    ("VarInt", "i64"),
+    ("VarLong", "i64"),
+    ("String", UUID),
+];
+
+fn convertible(a: &str, b: &str) -> bool {
+    a == b || CONVERSIONS.contains(&(a, b)) || CONVERSIONS.contains(&(b, a))
+}
+
+fn state_variant(state: &str) -> &'static str {
+    match state {
+        "handshaking" => "Handshaking",
+        "status" => "Status",
+        "login" => "Login",
+        _ => "Play",
+    }
+}
+
+fn field<'a>(packet: &'a PacketInfo, name: &str) -> Option<&'a (String, String, String)> {
+    packet.fields.iter().find(|(field, _, _)| field == name)
+}
+
+// Whether every field of the event is in the packet, with a type that can be translated.
+fn decodable(event: &Event, packet: &PacketInfo) -> bool {
+    event.fields.iter().all(|(_, ty, name)| field(packet, name).is_some_and(|(_, _, packet_ty)| convertible(packet_ty, ty)))
+}
+
+// Sending also needs a value for every field of the packet.
+fn encodable(event: &Event, packet: &PacketInfo) -> bool {
+    decodable(event, packet)
+        && !packet.partial
+        && packet.fields.iter().all(|(name, _, ty)| {
+            event.fields.iter().any(|(_, _, field)| field == name)
+                || event.defaults.iter().any(|(field, default_ty, _)| field == name && convertible(default_ty, ty))
+        })
+}
+
+fn packet_path(event: &Event, direction: &str, packet: &PacketInfo) -> String {
+    let module = if direction == "toServer" { "serverbound" } else { "clientbound" };
+
+    format!("super::{}::{}::{}", event.state, module, packet.struct_name)
+}
+
+fn write_decode(out: &mut String, events: &[Event], enum_name: &str, direction: &'static str, packets: &Packets) {
+    let function = enum_name.trim_end_matches("Event").to_lowercase();
+    let mut arms = String::new();
+
+    for event in events {
+        let Some(packet) = packets.get(&(event.state, direction, event.packet.to_owned())) else { continue };
+
+        if !decodable(event, packet) {
+            continue;
+        }
+
+        let path = packet_path(event, direction, packet);
+
+        writeln!(arms, "        (ConnectionState::{}, {}::ID) => {{", state_variant(event.state), path).unwrap();
+
+        if event.fields.is_empty() {
+            writeln!(arms, "            {}::decode(data)?;\n", path).unwrap();
+        } else {
+            writeln!(arms, "            let packet = {}::decode(data)?;\n", path).unwrap();
+        }
+
+        if event.fields.is_empty() {
+            writeln!(arms, "            {}::{}\n        }}", enum_name, event.name).unwrap();
+            continue;
+        }
+
+        writeln!(arms, "            {}::{} {{", enum_name, event.name).unwrap();
+
+        for (name, _, field_name) in event.fields {
+            let (_, packet_field, _) = field(packet, field_name).unwrap();
+
+            writeln!(arms, "                {}: translate_field(packet.{}, {:?}, {:?})?,", name, packet_field, event.name, name).unwrap();
+        }
+
+        arms.push_str("            }\n        }\n");
+    }
+
+    writeln!(
+        out,
+        "pub fn decode_{}(state: ConnectionState, id: i32, data: &[u8]) -> Result<Option<{}>, TranslateError> {{",
+        function, enum_name
+    )
+    .unwrap();
+
+    if arms.is_empty() {
+        out.push_str("    let _ = (state, id, data);\n\n    Ok(None)\n}\n\n");
+    } else {
+        writeln!(out, "    let event = match (state, id) {{\n{}        _ => return Ok(None),\n    }};\n\n    Ok(Some(event))\n}}\n", arms).unwrap();
+    }
+}
+
+fn write_encode(out: &mut String, events: &[Event], enum_name: &str, direction: &'static str, packets: &Packets) {
+    let function = enum_name.trim_end_matches("Event").to_lowercase();
+
+    // Every event might be supported, which makes the fallback arm unreachable, or none, which leaves only that arm.
+    out.push_str("#[allow(unreachable_patterns, clippy::match_single_binding)]\n");
+    writeln!(out, "pub fn encode_{}(event: {}) -> Result<(i32, Bytes), TranslateError> {{\n    match event {{", function, enum_name).unwrap();
+
+    for event in events {
+        let Some(packet) = packets.get(&(event.state, direction, event.packet.to_owned())) else { continue };
+
+        if !encodable(event, packet) {
+            continue;
+        }
+
+        let path = packet_path(event, direction, packet);
+        let names: Vec<_> = event.fields.iter().map(|(name, _, _)| *name).collect();
+
+        if names.is_empty() {
+            writeln!(out, "        {}::{} => {{", enum_name, event.name).unwrap();
+        } else {
+            writeln!(out, "        {}::{} {{ {} }} => {{", enum_name, event.name, names.join(", ")).unwrap();
+        }
+
+        writeln!(out, "            let packet = {} {{", path).unwrap();
+
+        for (field_name, packet_field, _) in &packet.fields {
+            match event.fields.iter().find(|(_, _, field)| field == field_name) {
+                Some((name, _, _)) => {
+                    writeln!(out, "                {}: translate_field({}, {:?}, {:?})?,", packet_field, name, event.name, name).unwrap()
+                }
+                None => {
+                    let (_, ty, value) = event.defaults.iter().find(|(field, _, _)| field == field_name).unwrap();
+
+                    writeln!(out, "                {}: translate_field::<{}, _>({}, {:?}, {:?})?,", packet_field, ty, value, event.name, field_name)
+                        .unwrap()
+                }
+            }
+        }
+
+        writeln!(out, "            }};\n\n            Ok(({}::ID, packet.encode()?))\n        }}", path).unwrap();
+    }
+
+    out.push_str("        event => Err(TranslateError::Unsupported { event: event.name(), version: MINECRAFT_VERSION }),\n    }\n}\n\n");
+}
+
+// Translates between one version's packets and the events shared by all of them.
+pub fn generate_translation(packets: &Packets) -> String {
+    let mut out = String::new();
+
+    out.push_str("pub mod translate {\n");
+    out.push_str("#[allow(unused_imports)]\nuse bytes::Bytes;\n#[allow(unused_imports)]\nuse rustic_io::packet::{ConnectionState, Packet};\n\n");
+    out.push_str("#[allow(unused_imports)]\nuse super::super::{translate_field, ClientboundEvent, ServerboundEvent, TranslateError};\nuse super::MINECRAFT_VERSION;\n\n");
+
+    write_decode(&mut out, SERVERBOUND, "ServerboundEvent", "toServer", packets);
+    write_encode(&mut out, SERVERBOUND, "ServerboundEvent", "toServer", packets);
+    write_decode(&mut out, CLIENTBOUND, "ClientboundEvent", "toClient", packets);
+    write_encode(&mut out, CLIENTBOUND, "ClientboundEvent", "toClient", packets);
+    write_ids(&mut out, "serverbound_id", "toServer", packets);
+    write_ids(&mut out, "clientbound_id", "toClient", packets);
+
+    out.push_str("}\n");
+    out
+}
+
+// Looks up a packet's id by its name in protocol.json, for packets without an event that are written by hand.
+fn write_ids(out: &mut String, function: &str, direction: &str, packets: &Packets) {
+    let packets: Vec<_> = packets.iter().filter(|((_, key, _), _)| *key == direction).collect();
+
+    // A match with nothing but `_` would be linted.
+    if packets.is_empty() {
+        writeln!(out, "pub fn {}(_state: ConnectionState, _name: &str) -> Option<i32> {{\n    None\n}}\n", function).unwrap();
+        return;
+    }
+
+    writeln!(out, "pub fn {}(state: ConnectionState, name: &str) -> Option<i32> {{\n    match (state, name) {{", function).unwrap();
+
+    for ((state, _, name), packet) in packets {
+        writeln!(out, "        (ConnectionState::{}, {:?}) => Some({:#04x}),", state_variant(state), name, packet.id).unwrap();
+    }
+
+    out.push_str("        _ => None,\n    }\n}\n\n");
+}
+
+fn write_event_enum(out: &mut String, name: &str, events: &[Event]) {
+    out.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    writeln!(out, "pub enum {} {{", name).unwrap();
+
+    for event in events {
+        let fields: Vec<_> = event.fields.iter().map(|(name, ty, _)| format!("{}: {}", name, ty)).collect();
+
+        if fields.is_empty() {
+            writeln!(out, "    {},", event.name).unwrap();
+        } else {
+            writeln!(out, "    {} {{ {} }},", event.name, fields.join(", ")).unwrap();
+        }
+    }
+
+    writeln!(out, "}}\n\nimpl {} {{\n    pub fn name(&self) -> &'static str {{\n        match self {{", name).unwrap();
+
+    for event in events {
+        writeln!(out, "            Self::{0} {{ .. }} => {0:?},", event.name).unwrap();
+    }
+
+    out.push_str("        }\n    }\n\n    pub fn state(&self) -> ConnectionState {\n        match self {\n");
+
+    for event in events {
+        writeln!(out, "            Self::{} {{ .. }} => ConnectionState::{},", event.name, state_variant(event.state)).unwrap();
+    }
+
+    out.push_str("        }\n    }\n}\n\n");
+}
+
+pub fn generate_events() -> String {
+    let mut out = String::new();
+
+    write_event_enum(&mut out, "ServerboundEvent", SERVERBOUND);
+    write_event_enum(&mut out, "ClientboundEvent", CLIENTBOUND);
+
+    out
+}
@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+mod events;
+mod protocol;
+mod registries;
+
+// The version code is generated for, unless `RUSTIC_MC_VERSION` says otherwise.
+const DEFAULT_VERSION: &str = "1.18.2";
+
+// Older versions clients can also connect with, unless `RUSTIC_MC_VERSIONS` (comma separated) says otherwise.
+const DEFAULT_VERSIONS: &[&str] = &["1.15.2", "1.16.5", "1.17.1"];
+
+// Where one version's files live, as listed in minecraft-data's `dataPaths.json`.
+pub struct VersionData {
+    root: PathBuf,
+    paths: serde_json::Map<String, Value>,
+}
+
+impl VersionData {
+    fn load(root: &Path, version: &str) -> Option<Self> {
+        let data_paths = read_json(&root.join("dataPaths.json"))?;
+        let paths = data_paths["pc"][version].as_object()?.clone();
+
+        Some(Self { root: root.to_owned(), paths })
+    }
+
+    // Reads e.g. `("protocol", "protocol.json")` for this version.
+    pub fn json(&self, kind: &str, file: &str) -> Option<Value> {
+        let dir = self.paths.get(kind)?.as_str()?;
+
+        read_json(&self.root.join(dir).join(file))
+    }
+}
+
+pub fn read_json(path: &Path) -> Option<Value> {
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let text = fs::read_to_string(path).ok()?;
+
+    Some(serde_json::from_str(&text).unwrap_or_else(|err| panic!("{} is not valid JSON: {}", path.display(), err)))
+}
+
+fn generate_protocol(primary: &str, data: Option<&VersionData>, others: &[(String, VersionData)]) -> String {
+    let mut out = String::new();
+    let mut versions = Vec::new();
+
+    out.push_str("// Generated by build/protocol.rs and build/events.rs from minecraft-data's protocol.json, do not edit.\n\n");
+
+    let all = std::iter::once((primary, data)).chain(others.iter().map(|(version, data)| (version.as_str(), Some(data))));
+
+    for (version, data) in all {
+        let module = protocol::module_name(version);
+        let (definitions, packets) = protocol::generate(version, data);
+        let translation = events::generate_translation(&packets);
+
+        out.push_str(&format!("pub mod {} {{\n{}\n{}}}\n\n", module, definitions, translation));
+
+        if data.is_some() {
+            versions.push((protocol::protocol_version(data), version, module));
+        }
+    }
+
+    // The primary version can also be used without naming it.
+    out.push_str(&format!("pub use {}::*;\n\n", protocol::module_name(primary)));
+    out.push_str(&events::generate_events());
+
+    versions.sort();
+    versions.dedup_by(|b, a| {
+        let duplicate = a.0 == b.0;
+
+        if duplicate {
+            println!("cargo:warning={} uses the same protocol as {}, leaving it out", b.1, a.1);
+        }
+
+        duplicate
+    });
+
+    out.push_str("pub static VERSIONS: &[Version] = &[\n");
+
+    for (_, _, module) in versions {
+        out.push_str(&format!(
+            "    Version {{\n        name: {0}::MINECRAFT_VERSION,\n        protocol: {0}::PROTOCOL_VERSION,\n        \
+             decode_serverbound: {0}::translate::decode_serverbound,\n        encode_serverbound: {0}::translate::encode_serverbound,\n        \
+             decode_clientbound: {0}::translate::decode_clientbound,\n        encode_clientbound: {0}::translate::encode_clientbound,\n        \
+             serverbound_id: {0}::translate::serverbound_id,\n        clientbound_id: {0}::translate::clientbound_id,\n    }},\n",
+            module
+        ));
+    }
+
+    out.push_str("];\n");
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build");
+    println!("cargo:rerun-if-env-changed=MINECRAFT_DATA_DIR");
+    println!("cargo:rerun-if-env-changed=RUSTIC_MC_VERSION");
+    println!("cargo:rerun-if-env-changed=RUSTIC_MC_VERSIONS");
+    println!("cargo:rustc-check-cfg=cfg(minecraft_data)");
+
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+
+    let submodule = manifest_dir.join("minecraft-data").join("data");
+    let fixture = manifest_dir.join("fixtures").join("minecraft-data");
+
+    let root = match env::var_os("MINECRAFT_DATA_DIR") {
+        Some(root) => PathBuf::from(root),
+        None if submodule.join("dataPaths.json").exists() => submodule,
+        // Still build, so the rest of the workspace doesn't depend on the submodule being checked out. The fixture only
+        // has the packets and registry entries the tests use.
+        None => {
+            println!("cargo:warning=minecraft-data isn't checked out, generating definitions from the test fixture (run `git submodule update --init`)");
+            fixture.clone()
+        }
+    };
+    let version = env::var("RUSTIC_MC_VERSION").unwrap_or_else(|_| DEFAULT_VERSION.to_owned());
+
+    let data = VersionData::load(&root, &version);
+
+    match &data {
+        Some(_) => println!("cargo:rustc-cfg=minecraft_data"),
+        None => println!("cargo:warning=no minecraft-data for {} in {}, generating empty definitions", version, root.display()),
+    }
+
+    let versions = match env::var("RUSTIC_MC_VERSIONS") {
+        Ok(versions) => versions.split(',').map(str::trim).filter(|version| !version.is_empty()).map(str::to_owned).collect(),
+        Err(_) => DEFAULT_VERSIONS.iter().map(|version| version.to_string()).collect::<Vec<_>>(),
+    };
+
+    // Without the submodule there's no point in complaining about every version.
+    let others = match data {
+        Some(_) => versions
+            .iter()
+            .filter(|other| **other != version)
+            .filter_map(|other| {
+                let data = VersionData::load(&root, other);
+
+                // The fixture leaves most of them out on purpose.
+                if data.is_none() && root != fixture {
+                    println!("cargo:warning=no minecraft-data for {}, leaving it out", other);
+                }
+
+                Some((other.clone(), data?))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    fs::write(out_dir.join("protocol.rs"), generate_protocol(&version, data.as_ref(), &others)).unwrap();
+
+    let block_entities = read_json(&manifest_dir.join("data").join(&version).join("blockEntities.json"));
+
+    if block_entities.is_none() {
+        println!("cargo:warning=no block entity types for {} in data/, chunks will be loaded without block entities", version);
+    }
+
+    let registries = registries::generate(data.as_ref(), block_entities);
+    fs::write(out_dir.join("registries.rs"), registries).unwrap();
+}
@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use crate::VersionData;
+
+const STATES: &[(&str, &str)] = &[
+    ("handshaking", "Handshaking"),
+    ("status", "Status"),
+    ("login", "Login"),
+    ("play", "Play"),
+];
+
+const DIRECTIONS: &[(&str, &str, &str)] = &[
+    ("toServer", "serverbound", "Serverbound"),
+    ("toClient", "clientbound", "Clientbound"),
+];
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+    "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "super", "trait",
+    "true", "type", "unsafe", "use", "where", "while", "async", "await", "dyn", "abstract", "become", "box", "do",
+    "final", "macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+pub fn camel_case(name: &str) -> String {
+    name.split(['_', '.', ':', '/'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let after_lower = i > 0 && !chars[i - 1].is_ascii_uppercase() && chars[i - 1] != '_';
+            // The last capital of an acronym starts a new word if a lowercase letter follows, like in `UUIDString`.
+            let ends_acronym = i > 0 && chars[i - 1].is_ascii_uppercase() && chars.get(i + 1).is_some_and(char::is_ascii_lowercase);
+
+            if after_lower || ends_acronym {
+                snake.push('_');
+            }
+
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(*c);
+        }
+    }
+
+    if KEYWORDS.contains(&snake.as_str()) {
+        format!("r#{}", snake)
+    } else {
+        snake
+    }
+}
+
+// A field type that `#[derive(Packet)]` knows how to read.
+struct Field {
+    ty: String,
+    rest: bool,
+}
+
+impl Field {
+    fn new(ty: impl Into<String>) -> Self {
+        Self { ty: ty.into(), rest: false }
+    }
+}
+
+struct Generator<'a> {
+    global_types: &'a Map<String, Value>,
+    // Named types from the top level `types`, by name. `None` if they can't be generated.
+    shared: BTreeMap<String, Option<String>>,
+    shared_items: String,
+    // Prepended to the names of generated structs, so types generated into the `types` module can be used anywhere.
+    prefix: &'static str,
+    // Structs generated into the current module, so named types used by several packets are only generated once.
+    emitted: BTreeSet<String>,
+}
+
+impl<'a> Generator<'a> {
+    // Turns a protocol.json type into a Rust type, generating structs for containers into `items`.
+    // Returns `None` for types the derive can't handle (switches, NBT, entity metadata, ...).
+    fn resolve(&mut self, ty: &Value, local_types: &Map<String, Value>, hint: &str, items: &mut String) -> Option<Field> {
+        match ty {
+            Value::String(name) => self.resolve_named(name, local_types, items),
+            Value::Array(def) => {
+                let kind = def.first()?.as_str()?;
+                let options = def.get(1)?;
+
+                match kind {
+                    "pstring" if options["countType"] == "varint" => Some(Field::new("String")),
+                    "buffer" if options["countType"] == "varint" => Some(Field::new("Vec<u8>")),
+                    "option" => {
+                        let inner = self.resolve(options, local_types, hint, items)?;
+
+                        Some(Field { ty: format!("Option<{}>", inner.ty), rest: inner.rest })
+                    }
+                    "array" if options["countType"] == "varint" => {
+                        let inner = self.resolve(&options["type"], local_types, hint, items)?;
+
+                        (!inner.rest).then(|| Field::new(format!("Vec<{}>", inner.ty)))
+                    }
+                    "mapper" => self.resolve(&options["type"], local_types, hint, items),
+                    "bitfield" if is_position(options) => Some(Field::new("rustic_io::datatypes::position::Position")),
+                    "container" => {
+                        let emitted = self.emitted.clone();
+                        let resolved = self.resolve_container(options.as_array()?, local_types, hint, items);
+
+                        // Forget about the structs generated for fields of a container that didn't work out.
+                        if resolved.is_none() {
+                            self.emitted = emitted;
+                        }
+
+                        resolved
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve_container(&mut self, fields: &[Value], local_types: &Map<String, Value>, hint: &str, items: &mut String) -> Option<Field> {
+        let mut body = String::new();
+        let mut nested = String::new();
+
+        for field in fields {
+            let name = field["name"].as_str()?;
+            let hint = format!("{}{}", hint, camel_case(name));
+            let resolved = self.resolve(&field["type"], local_types, &hint, &mut nested)?;
+
+            // The rest of the packet only works at the top level.
+            if resolved.rest {
+                return None;
+            }
+
+            writeln!(body, "    pub {}: {},", snake_case(name), resolved.ty).unwrap();
+        }
+
+        if self.emitted.insert(hint.to_owned()) {
+            items.push_str(&nested);
+            write_struct(items, hint, None, &body);
+        }
+
+        Some(Field::new(format!("{}{}", self.prefix, hint)))
+    }
+
+    fn resolve_named(&mut self, name: &str, local_types: &Map<String, Value>, items: &mut String) -> Option<Field> {
+        let primitive = match name {
+            "varint" | "optvarint" => "VarInt",
+            "varlong" => "VarLong",
+            "bool" => "bool",
+            "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" | "f32" | "f64" => name,
+            "UUID" => "rustic_io::datatypes::numbers::Uuid",
+            "string" => "String",
+            "position" => "rustic_io::datatypes::position::Position",
+            "restBuffer" => return Some(Field { ty: "Vec<u8>".to_owned(), rest: true }),
+            _ => "",
+        };
+
+        if !primitive.is_empty() {
+            return Some(Field::new(primitive));
+        }
+
+        if let Some(def) = local_types.get(name) {
+            if def == "native" {
+                return None;
+            }
+
+            let def = def.clone();
+            return self.resolve(&def, local_types, &camel_case(name), items);
+        }
+
+        if let Some(shared) = self.shared.get(name) {
+            return shared.clone().map(Field::new);
+        }
+
+        let def = self.global_types.get(name)?;
+
+        if def == "native" {
+            return None;
+        }
+
+        // Mark it as in progress, so recursive types fall back instead of looping.
+        self.shared.insert(name.to_owned(), None);
+
+        // Shared types are only generated once anyway, so they don't need to be deduplicated.
+        let mut shared_items = String::new();
+        let module_emitted = std::mem::take(&mut self.emitted);
+        let module_prefix = std::mem::replace(&mut self.prefix, "types::");
+
+        let resolved = self.resolve(def, &Map::new(), &camel_case(name), &mut shared_items).filter(|field| !field.rest);
+
+        self.prefix = module_prefix;
+        self.emitted = module_emitted;
+
+        let resolved = resolved.map(|field| {
+            self.shared_items.push_str(&shared_items);
+            field.ty
+        });
+
+        self.shared.insert(name.to_owned(), resolved.clone());
+
+        resolved.map(Field::new)
+    }
+}
+
+fn is_position(fields: &Value) -> bool {
+    let layout: Vec<_> = fields
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|field| (field["name"].as_str().unwrap_or(""), field["size"].as_u64().unwrap_or(0)))
+        .collect();
+
+    layout == [("x", 26), ("z", 26), ("y", 12)]
+}
+
+fn write_struct(out: &mut String, name: &str, packet: Option<(i64, &str)>, body: &str) {
+    out.push_str("#[derive(Debug, PartialEq, Packet)]\n");
+
+    if let Some((id, state)) = packet {
+        writeln!(out, "#[packet(id = {:#04x}, state = {})]", id, state).unwrap();
+    }
+
+    writeln!(out, "pub struct {} {{\n{}}}\n", name, body).unwrap();
+}
+
+// Packet ids and names from the `packet` container's mapper, in id order.
+fn packet_ids(types: &Map<String, Value>) -> Vec<(i64, String)> {
+    let mut ids: Vec<_> = types
+        .get("packet")
+        .and_then(|packet| packet[1].as_array())
+        .and_then(|fields| fields.iter().find(|field| field["name"] == "name"))
+        .and_then(|field| field["type"][1]["mappings"].as_object())
+        .into_iter()
+        .flatten()
+        .filter_map(|(id, name)| {
+            let id = i64::from_str_radix(id.trim_start_matches("0x"), 16).ok()?;
+
+            Some((id, name.as_str()?.to_owned()))
+        })
+        .collect();
+
+    ids.sort();
+    ids
+}
+
+// What was generated for a packet, so events can be translated from and to it.
+pub struct PacketInfo {
+    pub id: i64,
+    pub struct_name: String,
+    // `(name in protocol.json, field name, type)` of every field that was decoded.
+    pub fields: Vec<(String, String, String)>,
+    // Whether some of the packet was left as raw bytes.
+    pub partial: bool,
+}
+
+// Packets by state, direction (`toServer` or `toClient`) and their name in protocol.json.
+pub type Packets = BTreeMap<(&'static str, &'static str, String), PacketInfo>;
+
+fn generate_direction(
+    generator: &mut Generator,
+    (state, state_variant): (&'static str, &str),
+    (key, enum_name): (&'static str, &str),
+    types: &Map<String, Value>,
+    packets: &mut Packets,
+) -> String {
+    let mut items = String::new();
+    let mut variants = Vec::new();
+
+    generator.emitted.clear();
+
+    for (id, name) in packet_ids(types) {
+        let Some(Value::Array(def)) = types.get(&format!("packet_{}", name)) else { continue };
+        let Some(fields) = def.get(1).and_then(Value::as_array) else { continue };
+
+        let struct_name = camel_case(&name);
+        let mut body = String::new();
+        let mut info = PacketInfo { id, struct_name: struct_name.clone(), fields: Vec::new(), partial: false };
+
+        for field in fields {
+            let field_name = field["name"].as_str().unwrap_or("anonymous");
+            let hint = format!("{}{}", struct_name, camel_case(field_name));
+
+            match generator.resolve(&field["type"], types, &hint, &mut items) {
+                Some(Field { ty, rest }) => {
+                    if rest {
+                        body.push_str("    #[packet(rest)]\n");
+                    }
+
+                    writeln!(body, "    pub {}: {},", snake_case(field_name), ty).unwrap();
+                    info.fields.push((field_name.to_owned(), snake_case(field_name), ty));
+                }
+                None => {
+                    // Everything from the first field we can't describe on is kept as raw bytes.
+                    writeln!(body, "    // Not decoded: {}", remaining_fields(fields, field_name)).unwrap();
+                    body.push_str("    #[packet(rest)]\n    pub rest: Vec<u8>,\n");
+                    info.partial = true;
+                    break;
+                }
+            }
+        }
+
+        write_struct(&mut items, &struct_name, Some((id, state_variant)), &body);
+        variants.push(struct_name);
+        packets.insert((state, key, name), info);
+    }
+
+    write_dispatch(&mut items, enum_name, &variants);
+    items
+}
+
+fn remaining_fields(fields: &[Value], from: &str) -> String {
+    fields
+        .iter()
+        .map(|field| field["name"].as_str().unwrap_or("anonymous"))
+        .skip_while(|name| *name != from)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// An enum of every packet in one state and direction, to decode a frame by its id.
+fn write_dispatch(out: &mut String, name: &str, variants: &[String]) {
+    out.push_str("#[derive(Debug, PartialEq)]\n#[allow(clippy::large_enum_variant)]\n");
+    writeln!(out, "pub enum {} {{", name).unwrap();
+
+    for variant in variants {
+        writeln!(out, "    {}({}),", variant, variant).unwrap();
+    }
+
+    out.push_str("}\n\n");
+    writeln!(out, "impl {} {{", name).unwrap();
+
+    if variants.is_empty() {
+        // Nothing to match on, and the usual bodies would be unreachable code.
+        out.push_str("    pub fn decode(_id: i32, _data: &[u8]) -> Result<Option<Self>, scroll::Error> {\n        Ok(None)\n    }\n\n");
+        out.push_str("    pub fn id(&self) -> i32 {\n        match *self {}\n    }\n\n");
+        out.push_str("    pub fn encode(self) -> Result<(i32, Bytes), scroll::Error> {\n        match self {}\n    }\n}\n\n");
+
+        return;
+    }
+
+    out.push_str("    pub fn decode(id: i32, data: &[u8]) -> Result<Option<Self>, scroll::Error> {\n");
+    out.push_str("        Ok(Some(match id {\n");
+
+    for variant in variants {
+        writeln!(out, "            {0}::ID => Self::{0}({0}::decode(data)?),", variant).unwrap();
+    }
+
+    out.push_str("            _ => return Ok(None),\n        }))\n    }\n\n");
+
+    out.push_str("    pub fn id(&self) -> i32 {\n        match *self {\n");
+
+    for variant in variants {
+        writeln!(out, "            Self::{0}(_) => {0}::ID,", variant).unwrap();
+    }
+
+    out.push_str("        }\n    }\n\n");
+
+    out.push_str("    pub fn encode(self) -> Result<(i32, Bytes), scroll::Error> {\n");
+    out.push_str("        let id = self.id();\n\n        let data = match self {\n");
+
+    for variant in variants {
+        writeln!(out, "            Self::{}(packet) => packet.encode()?,", variant).unwrap();
+    }
+
+    out.push_str("        };\n\n        Ok((id, data))\n    }\n}\n\n");
+
+    for variant in variants {
+        writeln!(
+            out,
+            "impl From<{1}> for {0} {{\n    fn from(packet: {1}) -> Self {{\n        Self::{1}(packet)\n    }}\n}}\n",
+            name, variant
+        )
+        .unwrap();
+    }
+}
+
+const PRELUDE: &str = "#[allow(unused_imports)]
+use super::super::types;
+#[allow(unused_imports)]
+use bytes::Bytes;
+#[allow(unused_imports)]
+use rustic_io::datatypes::var::{VarInt, VarLong};
+#[allow(unused_imports)]
+use rustic_io::packet::Packet;
+#[allow(unused_imports)]
+use rustic_io::scroll;
+";
+
+// The module a version's definitions are generated into, like `v1_18_2`.
+pub fn module_name(version: &str) -> String {
+    format!("v{}", version.replace(['.', '-', ' '], "_"))
+}
+
+pub fn protocol_version(data: Option<&VersionData>) -> i32 {
+    data.and_then(|data| data.json("version", "version.json"))
+        .and_then(|version| version["version"].as_i64())
+        .unwrap_or(-1) as i32
+}
+
+// The contents of one version's module, and the packets generated into it.
+pub fn generate(version: &str, data: Option<&VersionData>) -> (String, Packets) {
+    let protocol = data.and_then(|data| data.json("protocol", "protocol.json")).unwrap_or(Value::Null);
+
+    let empty = Map::new();
+    let mut generator = Generator {
+        global_types: protocol["types"].as_object().unwrap_or(&empty),
+        shared: BTreeMap::new(),
+        shared_items: String::new(),
+        prefix: "",
+        emitted: BTreeSet::new(),
+    };
+
+    let mut out = String::new();
+    let mut packets = Packets::new();
+
+    writeln!(out, "pub const MINECRAFT_VERSION: &str = {:?};", version).unwrap();
+    writeln!(out, "pub const PROTOCOL_VERSION: i32 = {};\n", protocol_version(data)).unwrap();
+
+    let mut states = String::new();
+
+    for &(state, state_variant) in STATES {
+        writeln!(states, "pub mod {} {{", state).unwrap();
+
+        for &(key, module, enum_name) in DIRECTIONS {
+            let types = protocol[state][key]["types"].as_object().unwrap_or(&empty);
+            let items = generate_direction(&mut generator, (state, state_variant), (key, enum_name), types, &mut packets);
+
+            writeln!(states, "pub mod {} {{\n{}\n{}}}\n", module, PRELUDE, items).unwrap();
+        }
+
+        states.push_str("}\n\n");
+    }
+
+    let types_prelude = PRELUDE.replace("super::super::types", "super::types");
+    writeln!(out, "pub mod types {{\n{}\n{}}}\n", types_prelude, generator.shared_items).unwrap();
+    out.push_str(&states);
+
+    (out, packets)
+}
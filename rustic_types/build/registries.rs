@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::protocol::camel_case;
+use crate::VersionData;
+
+// The values of every enum property, under the property's name. A property like `facing` only has four values on
+// most blocks but six on some, so the enum gets all of them and every block lists the ones it uses.
+#[derive(Default)]
+struct PropertyEnums {
+    values: BTreeMap<String, Vec<String>>,
+}
+
+impl PropertyEnums {
+    fn add(&mut self, name: &str, values: &[Value]) {
+        let known = self.values.entry(name.to_owned()).or_default();
+
+        for value in values.iter().filter_map(Value::as_str) {
+            if !known.iter().any(|known| known == value) {
+                known.push(value.to_owned());
+            }
+        }
+    }
+}
+
+fn variant(value: &str) -> String {
+    let variant = camel_case(value);
+
+    if variant.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("V{}", variant)
+    } else {
+        variant
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    format!("{:?}", value[key].as_str().unwrap_or(""))
+}
+
+fn f32_field(value: &Value, key: &str) -> String {
+    format!("{:?}", value[key].as_f64().unwrap_or(0.0) as f32)
+}
+
+fn u32_field(value: &Value, key: &str) -> u64 {
+    value[key].as_u64().unwrap_or(0)
+}
+
+// A sorted `(name, id)` table to look entries up by name with a binary search.
+fn write_names(out: &mut String, table: &str, entries: &[Value]) {
+    let mut names: Vec<_> = entries
+        .iter()
+        .filter_map(|entry| Some((entry["name"].as_str()?, entry["id"].as_u64()?)))
+        .collect();
+
+    names.sort();
+
+    writeln!(out, "static {}: &[(&str, u32)] = &[", table).unwrap();
+
+    for (name, id) in names {
+        writeln!(out, "    ({:?}, {}),", name, id).unwrap();
+    }
+
+    out.push_str("];\n\n");
+}
+
+fn sorted_by_id(data: Option<&VersionData>, kind: &str) -> Vec<Value> {
+    let mut entries = data
+        .and_then(|data| data.json(kind, &format!("{}.json", kind)))
+        .and_then(|json| json.as_array().cloned())
+        .unwrap_or_default();
+
+    entries.sort_by_key(|entry| entry["id"].as_u64());
+    entries
+}
+
+fn write_blocks(out: &mut String, blocks: &[Value], enums: &mut PropertyEnums) {
+    let mut body = String::new();
+
+    for block in blocks {
+        let mut properties = String::new();
+
+        for state in block["states"].as_array().into_iter().flatten() {
+            let name = state["name"].as_str().unwrap_or("");
+            let count = state["num_values"].as_u64().unwrap_or(0);
+
+            let (values, names): (Vec<String>, Vec<String>) = match state["type"].as_str() {
+                // Boolean properties are always true first, then false.
+                Some("bool") => ["true", "false"].iter().map(|value| (format!("PropertyValue::Bool({})", value), value.to_string())).unzip(),
+                Some("int") => state["values"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|value| (format!("PropertyValue::Int({})", value), value.to_owned()))
+                    .unzip(),
+                _ => {
+                    let values = state["values"].as_array().cloned().unwrap_or_default();
+                    enums.add(name, &values);
+
+                    values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(|value| (format!("PropertyValue::{}({}::{})", camel_case(name), camel_case(name), variant(value)), value.to_owned()))
+                        .unzip()
+                }
+            };
+
+            assert_eq!(values.len() as u64, count, "{} of {} has the wrong amount of values", name, block["name"]);
+
+            writeln!(
+                properties,
+                "            Property {{ name: {:?}, values: &[{}], names: &{:?} }},",
+                name,
+                values.join(", "),
+                names
+            )
+            .unwrap();
+        }
+
+        // Older versions list drops as objects, newer ones just as item ids.
+        let drops: Vec<u64> = block["drops"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|drop| drop.as_u64().or_else(|| drop["drop"].as_u64()).or_else(|| drop["drop"]["id"].as_u64()))
+            .collect();
+
+        writeln!(
+            body,
+            "    Block {{\n        id: {},\n        name: {},\n        display_name: {},\n        hardness: {},\n        resistance: {},\n        \
+             diggable: {},\n        transparent: {},\n        emit_light: {},\n        filter_light: {},\n        \
+             default_state: {},\n        min_state_id: {},\n        max_state_id: {},\n        properties: &[\n{}        ],\n        drops: &{:?},\n    }},",
+            u32_field(block, "id"),
+            str_field(block, "name"),
+            str_field(block, "displayName"),
+            block["hardness"].as_f64().map(|hardness| format!("Some({:?})", hardness as f32)).unwrap_or("None".to_owned()),
+            f32_field(block, "resistance"),
+            block["diggable"].as_bool().unwrap_or(false),
+            block["transparent"].as_bool().unwrap_or(false),
+            u32_field(block, "emitLight"),
+            u32_field(block, "filterLight"),
+            u32_field(block, "defaultState"),
+            u32_field(block, "minStateId"),
+            u32_field(block, "maxStateId"),
+            properties,
+            drops
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "pub static BLOCKS: &[Block] = &[\n{}];\n", body).unwrap();
+    write_names(out, "BLOCK_NAMES", blocks);
+
+    // The global palette's size, which chunk sections need at compile time.
+    let states = blocks.iter().map(|block| block["maxStateId"].as_u64().unwrap() + 1).max().unwrap_or(0);
+    writeln!(out, "pub const BLOCK_STATE_COUNT: u32 = {};\n", states).unwrap();
+}
+
+fn write_items(out: &mut String, items: &[Value]) {
+    out.push_str("pub static ITEMS: &[Item] = &[\n");
+
+    for item in items {
+        writeln!(
+            out,
+            "    Item {{ id: {}, name: {}, display_name: {}, stack_size: {} }},",
+            u32_field(item, "id"),
+            str_field(item, "name"),
+            str_field(item, "displayName"),
+            u32_field(item, "stackSize")
+        )
+        .unwrap();
+    }
+
+    out.push_str("];\n\n");
+    write_names(out, "ITEM_NAMES", items);
+}
+
+fn write_entities(out: &mut String, entities: &[Value]) {
+    out.push_str("pub static ENTITIES: &[EntityType] = &[\n");
+
+    for entity in entities {
+        writeln!(
+            out,
+            "    EntityType {{ id: {}, name: {}, display_name: {}, width: {}, height: {}, kind: {}, category: {} }},",
+            u32_field(entity, "id"),
+            str_field(entity, "name"),
+            str_field(entity, "displayName"),
+            f32_field(entity, "width"),
+            f32_field(entity, "height"),
+            str_field(entity, "type"),
+            str_field(entity, "category")
+        )
+        .unwrap();
+    }
+
+    out.push_str("];\n\n");
+    write_names(out, "ENTITY_NAMES", entities);
+}
+
+fn write_biomes(out: &mut String, biomes: &[Value]) {
+    out.push_str("pub static BIOMES: &[Biome] = &[\n");
+
+    for biome in biomes {
+        writeln!(
+            out,
+            "    Biome {{ id: {}, name: {}, display_name: {}, category: {}, temperature: {}, rainfall: {}, precipitation: {}, dimension: {}, color: {} }},",
+            u32_field(biome, "id"),
+            str_field(biome, "name"),
+            str_field(biome, "displayName"),
+            str_field(biome, "category"),
+            f32_field(biome, "temperature"),
+            f32_field(biome, "rainfall"),
+            str_field(biome, "precipitation"),
+            str_field(biome, "dimension"),
+            u32_field(biome, "color")
+        )
+        .unwrap();
+    }
+
+    out.push_str("];\n\n");
+    write_names(out, "BIOME_NAMES", biomes);
+
+    let count = biomes.iter().map(|biome| biome["id"].as_u64().unwrap() + 1).max().unwrap_or(0);
+    writeln!(out, "pub const BIOME_COUNT: u32 = {};\n", count).unwrap();
+}
+
+fn write_block_entities(out: &mut String, block_entities: &[Value]) {
+    out.push_str("pub static BLOCK_ENTITIES: &[BlockEntityType] = &[\n");
+
+    for block_entity in block_entities {
+        writeln!(
+            out,
+            "    BlockEntityType {{ id: {}, name: {} }},",
+            u32_field(block_entity, "id"),
+            str_field(block_entity, "name")
+        )
+        .unwrap();
+    }
+
+    out.push_str("];\n\n");
+    write_names(out, "BLOCK_ENTITY_NAMES", block_entities);
+}
+
+fn write_enchantments(out: &mut String, enchantments: &[Value]) {
+    out.push_str("pub static ENCHANTMENTS: &[Enchantment] = &[\n");
+
+    for enchantment in enchantments {
+        let exclude: Vec<&str> = enchantment["exclude"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+
+        writeln!(
+            out,
+            "    Enchantment {{ id: {}, name: {}, display_name: {}, max_level: {}, category: {}, weight: {}, treasure_only: {}, curse: {}, exclude: &{:?} }},",
+            u32_field(enchantment, "id"),
+            str_field(enchantment, "name"),
+            str_field(enchantment, "displayName"),
+            u32_field(enchantment, "maxLevel"),
+            str_field(enchantment, "category"),
+            u32_field(enchantment, "weight"),
+            enchantment["treasureOnly"].as_bool().unwrap_or(false),
+            enchantment["curse"].as_bool().unwrap_or(false),
+            exclude
+        )
+        .unwrap();
+    }
+
+    out.push_str("];\n\n");
+    write_names(out, "ENCHANTMENT_NAMES", enchantments);
+}
+
+fn write_property_enums(out: &mut String, enums: &PropertyEnums) {
+    for (name, values) in &enums.values {
+        let name = camel_case(name);
+
+        writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\npub enum {} {{", name).unwrap();
+
+        for value in values {
+            writeln!(out, "    {},", variant(value)).unwrap();
+        }
+
+        writeln!(out, "}}\n\nimpl {} {{\n    pub fn as_str(self) -> &'static str {{\n        match self {{", name).unwrap();
+
+        for value in values {
+            writeln!(out, "            Self::{} => {:?},", variant(value), value).unwrap();
+        }
+
+        out.push_str("        }\n    }\n}\n\n");
+    }
+
+    out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\npub enum PropertyValue {\n    Bool(bool),\n    Int(u8),\n");
+
+    for name in enums.values.keys() {
+        writeln!(out, "    {0}({0}),", camel_case(name)).unwrap();
+    }
+
+    out.push_str("}\n\n");
+    out.push_str("impl std::fmt::Display for PropertyValue {\n    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n        match self {\n");
+    out.push_str("            Self::Bool(value) => write!(f, \"{}\", value),\n            Self::Int(value) => write!(f, \"{}\", value),\n");
+
+    for name in enums.values.keys() {
+        writeln!(out, "            Self::{}(value) => f.write_str(value.as_str()),", camel_case(name)).unwrap();
+    }
+
+    out.push_str("        }\n    }\n}\n\n");
+}
+
+// Block entity types come from the crate's own `data` directory instead, minecraft-data doesn't have them.
+pub fn generate(data: Option<&VersionData>, block_entities: Option<Value>) -> String {
+    let mut out = String::new();
+    let mut tables = String::new();
+    let mut enums = PropertyEnums::default();
+
+    out.push_str("// Generated by build/registries.rs from minecraft-data and data/, do not edit.\n\n");
+
+    write_blocks(&mut tables, &sorted_by_id(data, "blocks"), &mut enums);
+    write_items(&mut tables, &sorted_by_id(data, "items"));
+    write_entities(&mut tables, &sorted_by_id(data, "entities"));
+    write_biomes(&mut tables, &sorted_by_id(data, "biomes"));
+    write_enchantments(&mut tables, &sorted_by_id(data, "enchantments"));
+
+    let mut block_entities = block_entities.and_then(|json| json.as_array().cloned()).unwrap_or_default();
+    block_entities.sort_by_key(|entry| entry["id"].as_u64());
+    write_block_entities(&mut tables, &block_entities);
+
+    write_property_enums(&mut out, &enums);
+    out.push_str(&tables);
+
+    out
+}
@@ -1,8 +1,11 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }
-}
+// Packet definitions generated from minecraft-data's protocol.json
+pub mod protocol;
+
+// Blocks, items, entities, biomes and enchantments, also generated from minecraft-data
+pub mod registry;
+
+// JSON text components, for chat, disconnect reasons, the MOTD and titles
+pub mod text;
+
+// Chunks and what they're made of
+pub mod world;
@@ -0,0 +1,298 @@
+use bytes::Bytes;
+use derive_more::{Display, Error, From};
+use rustic_io::datatypes::numbers::Uuid;
+use rustic_io::datatypes::var::{VarInt, VarLong};
+use rustic_io::packet::ConnectionState;
+use rustic_io::scroll;
+
+include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+
+#[derive(Debug, Display, From, Error)]
+pub enum TranslateError {
+    #[display(fmt = "couldn't read or write the packet: {}", _0)]
+    Scroll(scroll::Error),
+    #[display(fmt = "{} can't be sent to {} clients", event, version)]
+    #[from(ignore)]
+    Unsupported { event: &'static str, version: &'static str },
+    #[display(fmt = "{} of {} doesn't fit into the packet", field, event)]
+    #[from(ignore)]
+    InvalidField { event: &'static str, field: &'static str },
+}
+
+// Converts a field between the type a version's packet has and the one its event has. `None` if the value doesn't fit.
+pub trait Translate<T> {
+    fn translate(self) -> Option<T>;
+}
+
+impl<T> Translate<T> for T {
+    fn translate(self) -> Option<T> {
+        Some(self)
+    }
+}
+
+impl Translate<i32> for VarInt {
+    fn translate(self) -> Option<i32> {
+        Some(self.0)
+    }
+}
+
+impl Translate<VarInt> for i32 {
+    fn translate(self) -> Option<VarInt> {
+        Some(VarInt(self))
+    }
+}
+
+// Keep alive ids used to be VarInts.
+impl Translate<i64> for VarInt {
+    fn translate(self) -> Option<i64> {
+        Some(self.0.into())
+    }
+}
+
+impl Translate<VarInt> for i64 {
+    fn translate(self) -> Option<VarInt> {
+        i32::try_from(self).ok().map(VarInt)
+    }
+}
+
+impl Translate<i64> for VarLong {
+    fn translate(self) -> Option<i64> {
+        Some(self.0)
+    }
+}
+
+impl Translate<VarLong> for i64 {
+    fn translate(self) -> Option<VarLong> {
+        Some(VarLong(self))
+    }
+}
+
+// Before 1.16, Login Success had the UUID as a string with hyphens.
+impl Translate<Uuid> for String {
+    fn translate(self) -> Option<Uuid> {
+        let hex = self.replace('-', "");
+
+        if hex.len() != 32 {
+            return None;
+        }
+
+        u128::from_str_radix(&hex, 16).ok().map(Uuid)
+    }
+}
+
+impl Translate<String> for Uuid {
+    fn translate(self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+// Used by the generated translations, of which there are none without minecraft-data.
+#[cfg_attr(not(minecraft_data), allow(dead_code))]
+fn translate_field<T: Translate<U>, U>(value: T, event: &'static str, field: &'static str) -> Result<U, TranslateError> {
+    value.translate().ok_or(TranslateError::InvalidField { event, field })
+}
+
+type Decode<E> = fn(ConnectionState, i32, &[u8]) -> Result<Option<E>, TranslateError>;
+type Encode<E> = fn(E) -> Result<(i32, Bytes), TranslateError>;
+type Id = fn(ConnectionState, &str) -> Option<i32>;
+
+// The packets of one protocol version, looked up with the protocol number a client sends in its handshake.
+// Packets that have an event are decoded into it, everything else is left to the version's own module.
+pub struct Version {
+    name: &'static str,
+    protocol: i32,
+    decode_serverbound: Decode<ServerboundEvent>,
+    encode_serverbound: Encode<ServerboundEvent>,
+    decode_clientbound: Decode<ClientboundEvent>,
+    encode_clientbound: Encode<ClientboundEvent>,
+    serverbound_id: Id,
+    clientbound_id: Id,
+}
+
+impl Version {
+    // Every version there are definitions for, oldest first.
+    pub fn all() -> &'static [Version] {
+        VERSIONS
+    }
+
+    pub fn by_protocol(protocol: i32) -> Option<&'static Version> {
+        VERSIONS.binary_search_by_key(&protocol, |version| version.protocol).ok().map(|i| &VERSIONS[i])
+    }
+
+    pub fn latest() -> Option<&'static Version> {
+        VERSIONS.last()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn protocol(&self) -> i32 {
+        self.protocol
+    }
+
+    // `Ok(None)` if the packet doesn't have an event, or this version doesn't know the id.
+    pub fn decode_serverbound(&self, state: ConnectionState, id: i32, data: &[u8]) -> Result<Option<ServerboundEvent>, TranslateError> {
+        (self.decode_serverbound)(state, id, data)
+    }
+
+    pub fn encode_serverbound(&self, event: ServerboundEvent) -> Result<(i32, Bytes), TranslateError> {
+        (self.encode_serverbound)(event)
+    }
+
+    pub fn decode_clientbound(&self, state: ConnectionState, id: i32, data: &[u8]) -> Result<Option<ClientboundEvent>, TranslateError> {
+        (self.decode_clientbound)(state, id, data)
+    }
+
+    pub fn encode_clientbound(&self, event: ClientboundEvent) -> Result<(i32, Bytes), TranslateError> {
+        (self.encode_clientbound)(event)
+    }
+
+    // A packet's id by its name in minecraft-data, like `map_chunk`, for the packets that are put together by hand.
+    pub fn serverbound_id(&self, state: ConnectionState, name: &str) -> Option<i32> {
+        (self.serverbound_id)(state, name)
+    }
+
+    pub fn clientbound_id(&self, state: ConnectionState, name: &str) -> Option<i32> {
+        (self.clientbound_id)(state, name)
+    }
+}
+
+impl std::fmt::Debug for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Version").field("name", &self.name).field("protocol", &self.protocol).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Most of these need the generated definitions, the rest doesn't.
+    #[cfg(minecraft_data)]
+    use anyhow::Result;
+    #[cfg(minecraft_data)]
+    use rustic_io::packet::Packet;
+
+    use super::*;
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn handshake_round_trip_test() -> Result<()> {
+        use handshaking::serverbound::{Serverbound, SetProtocol};
+
+        assert_eq!(SetProtocol::ID, 0x00);
+        assert_eq!(SetProtocol::STATE, ConnectionState::Handshaking);
+
+        let handshake = SetProtocol {
+            protocol_version: VarInt(PROTOCOL_VERSION),
+            server_host: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(2),
+        };
+
+        let (id, data) = Serverbound::from(handshake).encode()?;
+        assert_eq!(id, 0x00);
+
+        let Some(Serverbound::SetProtocol(decoded)) = Serverbound::decode(id, &data)? else {
+            panic!("expected a handshake");
+        };
+
+        assert_eq!(decoded.server_host, "localhost");
+        assert_eq!(decoded.next_state, VarInt(2));
+
+        Ok(())
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn unknown_packet_test() -> Result<()> {
+        assert!(status::serverbound::Serverbound::decode(0x7f, &[])?.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn login_packets_test() -> Result<()> {
+        use login::clientbound::{Clientbound, Success};
+
+        let success = Success { uuid: Uuid(0x0123456789abcdef0123456789abcdef), username: "Notch".to_owned() };
+        let (id, data) = Clientbound::from(success).encode()?;
+
+        assert_eq!(data.len(), 16 + 1 + 5);
+        assert!(matches!(Clientbound::decode(id, &data)?, Some(Clientbound::Success(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn uuid_translate_test() {
+        let uuid = Uuid(0x0123456789abcdef0123456789abcdef);
+        let string: String = uuid.translate().unwrap();
+
+        assert_eq!(string, "01234567-89ab-cdef-0123-456789abcdef");
+        assert_eq!(string.translate(), Some(uuid));
+        assert_eq!(Translate::<Uuid>::translate("not a uuid".to_owned()), None);
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn version_registry_test() {
+        let latest = Version::latest().unwrap();
+
+        assert_eq!(latest.protocol(), PROTOCOL_VERSION);
+        assert_eq!(Version::by_protocol(PROTOCOL_VERSION).unwrap().name(), MINECRAFT_VERSION);
+        assert!(Version::by_protocol(1).is_none());
+        assert!(Version::all().windows(2).all(|versions| versions[0].protocol() < versions[1].protocol()));
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn event_round_trip_test() -> Result<()> {
+        let version = Version::latest().unwrap();
+
+        let handshake = ServerboundEvent::Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            server_address: "localhost".to_owned(),
+            server_port: 25565,
+            next_state: 2,
+        };
+
+        let (id, data) = version.encode_serverbound(handshake.clone())?;
+        assert_eq!(version.decode_serverbound(ConnectionState::Handshaking, id, &data)?, Some(handshake));
+
+        // Same id, but a different state.
+        assert!(!matches!(version.decode_serverbound(ConnectionState::Status, id, &data), Ok(Some(_))));
+
+        let success = ClientboundEvent::LoginSuccess { uuid: Uuid(42), username: "Notch".to_owned() };
+        let (id, data) = version.encode_clientbound(success.clone())?;
+
+        assert_eq!(version.decode_clientbound(ConnectionState::Login, id, &data)?, Some(success));
+
+        Ok(())
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn old_version_test() -> Result<()> {
+        // Only there if minecraft-data has 1.15.2 and it wasn't left out with `RUSTIC_MC_VERSIONS`.
+        let Some(old) = Version::by_protocol(578) else { return Ok(()) };
+        let latest = Version::latest().unwrap();
+
+        let success = ClientboundEvent::LoginSuccess { uuid: Uuid(0x0123456789abcdef0123456789abcdef), username: "Notch".to_owned() };
+        let (old_id, old_data) = old.encode_clientbound(success.clone())?;
+        let (_, data) = latest.encode_clientbound(success.clone())?;
+
+        // The UUID is a string there.
+        assert_eq!(old_data.len(), 1 + 36 + 1 + 5);
+        assert_ne!(old_data, data);
+        assert_eq!(old.decode_clientbound(ConnectionState::Login, old_id, &old_data)?, Some(success));
+
+        let disconnect = ClientboundEvent::Disconnect { reason: "{\"text\":\"Bye\"}".to_owned() };
+        let (old_id, _) = old.encode_clientbound(disconnect.clone())?;
+        let (id, _) = latest.encode_clientbound(disconnect)?;
+
+        assert_eq!((old_id, id), (0x1b, 0x1a));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+// Blocks, items, entity types, biomes, enchantments and block entity types of the version the crate was built for.
+// The tables themselves are generated from minecraft-data by build/registries.rs.
+
+include!(concat!(env!("OUT_DIR"), "/registries.rs"));
+
+// Lets lookups by name take both `stone` and `minecraft:stone`.
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn by_id<T>(table: &'static [T], id: u32, key: impl Fn(&T) -> u32) -> Option<&'static T> {
+    // The ids are almost always contiguous, so try indexing before searching.
+    match table.get(id as usize) {
+        Some(entry) if key(entry) == id => Some(entry),
+        _ => table.binary_search_by_key(&id, key).ok().map(|i| &table[i]),
+    }
+}
+
+fn by_name<T>(table: &'static [T], names: &[(&str, u32)], name: &str, key: impl Fn(&T) -> u32) -> Option<&'static T> {
+    let name = strip_namespace(name);
+    let i = names.binary_search_by_key(&name, |(name, _)| name).ok()?;
+
+    by_id(table, names[i].1, key)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    // In the order they're numbered in block states.
+    pub values: &'static [PropertyValue],
+    pub names: &'static [&'static str],
+}
+
+impl Property {
+    pub fn index_of(&self, value: &str) -> Option<usize> {
+        self.names.iter().position(|name| *name == value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub id: u32,
+    pub name: &'static str,
+    pub display_name: &'static str,
+    // `None` for unbreakable blocks like bedrock.
+    pub hardness: Option<f32>,
+    pub resistance: f32,
+    pub diggable: bool,
+    pub transparent: bool,
+    pub emit_light: u8,
+    pub filter_light: u8,
+    pub default_state: u32,
+    pub min_state_id: u32,
+    pub max_state_id: u32,
+    pub properties: &'static [Property],
+    // Ids of the items the block drops when broken.
+    pub drops: &'static [u32],
+}
+
+impl Block {
+    pub fn all() -> &'static [Block] {
+        BLOCKS
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static Block> {
+        by_id(BLOCKS, id, |block| block.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Block> {
+        by_name(BLOCKS, BLOCK_NAMES, name, |block| block.id)
+    }
+
+    // The block a global block state id belongs to.
+    pub fn from_state(state: u32) -> Option<&'static Block> {
+        let i = BLOCKS.partition_point(|block| block.max_state_id < state);
+
+        BLOCKS.get(i).filter(|block| block.min_state_id <= state)
+    }
+
+    pub fn states(&self) -> std::ops::RangeInclusive<u32> {
+        self.min_state_id..=self.max_state_id
+    }
+
+    // The property values of one of this block's states. The last property changes the fastest between state ids.
+    pub fn state_properties(&self, state: u32) -> Option<Vec<(&'static str, PropertyValue)>> {
+        if !self.states().contains(&state) {
+            return None;
+        }
+
+        let mut index = (state - self.min_state_id) as usize;
+        let mut properties = Vec::with_capacity(self.properties.len());
+
+        for property in self.properties.iter().rev() {
+            properties.push((property.name, property.values[index % property.values.len()]));
+            index /= property.values.len();
+        }
+
+        properties.reverse();
+        Some(properties)
+    }
+
+    // The state with the given property values, like they appear in commands and world files.
+    // Properties that aren't given keep their value from the default state.
+    pub fn state_id(&self, properties: &[(&str, &str)]) -> Option<u32> {
+        let default = self.state_properties(self.default_state)?;
+        let mut index = 0;
+
+        for (property, (_, default)) in self.properties.iter().zip(default) {
+            let value = match properties.iter().find(|(name, _)| *name == property.name) {
+                Some((_, value)) => property.index_of(value)?,
+                None => property.values.iter().position(|value| *value == default)?,
+            };
+
+            index = index * property.values.len() + value;
+        }
+
+        Some(self.min_state_id + index as u32)
+    }
+
+    pub fn drops(&self) -> impl Iterator<Item = &'static Item> + '_ {
+        self.drops.iter().filter_map(|id| Item::by_id(*id))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    pub id: u32,
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub stack_size: u8,
+}
+
+impl Item {
+    pub fn all() -> &'static [Item] {
+        ITEMS
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static Item> {
+        by_id(ITEMS, id, |item| item.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Item> {
+        by_name(ITEMS, ITEM_NAMES, name, |item| item.id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EntityType {
+    pub id: u32,
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub width: f32,
+    pub height: f32,
+    // `mob`, `player`, `projectile`, ...
+    pub kind: &'static str,
+    pub category: &'static str,
+}
+
+impl EntityType {
+    pub fn all() -> &'static [EntityType] {
+        ENTITIES
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static EntityType> {
+        by_id(ENTITIES, id, |entity| entity.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static EntityType> {
+        by_name(ENTITIES, ENTITY_NAMES, name, |entity| entity.id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Biome {
+    pub id: u32,
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub category: &'static str,
+    pub temperature: f32,
+    pub rainfall: f32,
+    pub precipitation: &'static str,
+    pub dimension: &'static str,
+    pub color: u32,
+}
+
+impl Biome {
+    pub fn all() -> &'static [Biome] {
+        BIOMES
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static Biome> {
+        by_id(BIOMES, id, |biome| biome.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Biome> {
+        by_name(BIOMES, BIOME_NAMES, name, |biome| biome.id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Enchantment {
+    pub id: u32,
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub max_level: u8,
+    pub category: &'static str,
+    pub weight: u32,
+    pub treasure_only: bool,
+    pub curse: bool,
+    // Names of the enchantments this one can't be combined with.
+    pub exclude: &'static [&'static str],
+}
+
+impl Enchantment {
+    pub fn all() -> &'static [Enchantment] {
+        ENCHANTMENTS
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static Enchantment> {
+        by_id(ENCHANTMENTS, id, |enchantment| enchantment.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Enchantment> {
+        by_name(ENCHANTMENTS, ENCHANTMENT_NAMES, name, |enchantment| enchantment.id)
+    }
+
+    pub fn is_compatible_with(&self, other: &Enchantment) -> bool {
+        self.id != other.id && !self.exclude.contains(&other.name) && !other.exclude.contains(&self.name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BlockEntityType {
+    pub id: u32,
+    pub name: &'static str,
+}
+
+impl BlockEntityType {
+    pub fn all() -> &'static [BlockEntityType] {
+        BLOCK_ENTITIES
+    }
+
+    pub fn by_id(id: u32) -> Option<&'static BlockEntityType> {
+        by_id(BLOCK_ENTITIES, id, |block_entity| block_entity.id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static BlockEntityType> {
+        by_name(BLOCK_ENTITIES, BLOCK_ENTITY_NAMES, name, |block_entity| block_entity.id)
+    }
+}
+
+#[cfg(all(test, minecraft_data))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_lookup_test() {
+        let stone = Block::by_name("stone").unwrap();
+
+        assert_eq!(stone.id, 1);
+        assert_eq!(Block::by_name("minecraft:stone"), Some(stone));
+        assert_eq!(Block::by_id(1), Some(stone));
+        assert_eq!(Block::from_state(stone.default_state), Some(stone));
+        assert_eq!(Block::by_name("air").unwrap().hardness, Some(0.0));
+        assert_eq!(Block::by_name("bedrock").unwrap().hardness, None);
+        assert!(Block::by_name("not_a_block").is_none());
+
+        assert!(stone.drops().any(|item| item.name == "cobblestone"));
+    }
+
+    #[test]
+    fn block_state_test() {
+        let stairs = Block::by_name("oak_stairs").unwrap();
+
+        for state in stairs.states() {
+            assert_eq!(Block::from_state(state), Some(stairs));
+
+            let properties = stairs.state_properties(state).unwrap();
+            let names: Vec<_> = properties.iter().map(|(name, value)| (*name, value.to_string())).collect();
+            let names: Vec<_> = names.iter().map(|(name, value)| (*name, value.as_str())).collect();
+
+            assert_eq!(stairs.state_id(&names), Some(state));
+        }
+
+        let state = stairs.state_id(&[("facing", "east"), ("half", "top")]).unwrap();
+        let properties = stairs.state_properties(state).unwrap();
+
+        assert!(properties.contains(&("facing", PropertyValue::Facing(Facing::East))));
+        assert!(properties.contains(&("waterlogged", PropertyValue::Bool(false))));
+        assert_eq!(stairs.state_id(&[("facing", "up")]), None);
+        assert_eq!(stairs.state_id(&[]), Some(stairs.default_state));
+    }
+
+    #[test]
+    fn other_registries_test() {
+        assert_eq!(Item::by_name("minecraft:diamond").unwrap().stack_size, 64);
+        assert_eq!(EntityType::by_name("zombie").unwrap().kind, "mob");
+        assert!(Biome::by_name("plains").is_some());
+
+        let sharpness = Enchantment::by_name("sharpness").unwrap();
+        let smite = Enchantment::by_name("smite").unwrap();
+
+        assert_eq!(sharpness.max_level, 5);
+        assert!(!sharpness.is_compatible_with(smite));
+        assert!(sharpness.is_compatible_with(Enchantment::by_name("unbreaking").unwrap()));
+
+        assert_eq!(BlockEntityType::by_name("minecraft:sign").unwrap().id, 7);
+        assert_eq!(BlockEntityType::by_id(0).unwrap().name, "furnace");
+    }
+}
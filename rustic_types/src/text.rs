@@ -0,0 +1,628 @@
+use std::fmt;
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+use serde::{Deserialize, Deserializer, Serialize};
+
+// The legacy formatting codes start with this, like `§aGreen`.
+pub const SECTION_SIGN: char = '§';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+    // Any colour, written as `#rrggbb`. Clients before 1.16 don't know these.
+    Rgb(u8, u8, u8),
+}
+
+// The named colours with their name, legacy code and what they look like, in the order of their codes.
+const NAMED_COLORS: [(Color, &str, char, u32); 16] = [
+    (Color::Black, "black", '0', 0x000000),
+    (Color::DarkBlue, "dark_blue", '1', 0x0000aa),
+    (Color::DarkGreen, "dark_green", '2', 0x00aa00),
+    (Color::DarkAqua, "dark_aqua", '3', 0x00aaaa),
+    (Color::DarkRed, "dark_red", '4', 0xaa0000),
+    (Color::DarkPurple, "dark_purple", '5', 0xaa00aa),
+    (Color::Gold, "gold", '6', 0xffaa00),
+    (Color::Gray, "gray", '7', 0xaaaaaa),
+    (Color::DarkGray, "dark_gray", '8', 0x555555),
+    (Color::Blue, "blue", '9', 0x5555ff),
+    (Color::Green, "green", 'a', 0x55ff55),
+    (Color::Aqua, "aqua", 'b', 0x55ffff),
+    (Color::Red, "red", 'c', 0xff5555),
+    (Color::LightPurple, "light_purple", 'd', 0xff55ff),
+    (Color::Yellow, "yellow", 'e', 0xffff55),
+    (Color::White, "white", 'f', 0xffffff),
+];
+
+impl Color {
+    pub fn from_code(code: char) -> Option<Self> {
+        let code = code.to_ascii_lowercase();
+
+        NAMED_COLORS.iter().find(|(_, _, c, _)| *c == code).map(|(color, ..)| *color)
+    }
+
+    pub fn rgb(&self) -> u32 {
+        match self {
+            Color::Rgb(r, g, b) => u32::from_be_bytes([0, *r, *g, *b]),
+            named => NAMED_COLORS.iter().find(|(color, ..)| color == named).unwrap().3,
+        }
+    }
+
+    // The legacy code of the named colour that looks the most like this one.
+    pub fn code(&self) -> char {
+        let [_, r, g, b] = self.rgb().to_be_bytes();
+        let distance = |rgb: u32| {
+            let [_, r2, g2, b2] = rgb.to_be_bytes();
+            [(r, r2), (g, g2), (b, b2)].iter().map(|(a, b)| (*a as i32 - *b as i32).pow(2)).sum::<i32>()
+        };
+
+        NAMED_COLORS.iter().min_by_key(|(.., rgb)| distance(*rgb)).unwrap().2
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Rgb(..) => write!(f, "#{:06x}", self.rgb()),
+            named => write!(f, "{}", NAMED_COLORS.iter().find(|(color, ..)| color == named).unwrap().1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Display, Error)]
+#[display(fmt = "unknown color {:?}", _0)]
+pub struct UnknownColor(#[error(not(source))] pub String);
+
+impl FromStr for Color {
+    type Err = UnknownColor;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = name.strip_prefix('#') {
+            return match u32::from_str_radix(hex, 16) {
+                Ok(rgb) if hex.len() == 6 => {
+                    let [_, r, g, b] = rgb.to_be_bytes();
+                    Ok(Color::Rgb(r, g, b))
+                }
+                _ => Err(UnknownColor(name.to_owned())),
+            };
+        }
+
+        NAMED_COLORS.iter().find(|(_, n, ..)| *n == name).map(|(color, ..)| *color).ok_or_else(|| UnknownColor(name.to_owned()))
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "value", rename_all = "snake_case")]
+pub enum ClickEvent {
+    OpenUrl(String),
+    RunCommand(String),
+    SuggestCommand(String),
+    // In books, the page number as a string.
+    ChangePage(String),
+    CopyToClipboard(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HoverItem {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<i32>,
+    // The item's NBT as SNBT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HoverEntity {
+    #[serde(rename = "type")]
+    pub kind: String,
+    // The UUID, with hyphens.
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Box<TextComponent>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "contents", rename_all = "snake_case")]
+pub enum HoverEvent {
+    ShowText(Box<TextComponent>),
+    ShowItem(HoverItem),
+    ShowEntity(HoverEntity),
+}
+
+// Everything is optional, so children can inherit what their parent has. Unset fields aren't sent at all.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Style {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    // Put into the chat box when the text is shift-clicked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insertion: Option<String>,
+    #[serde(default, rename = "clickEvent", skip_serializing_if = "Option::is_none")]
+    pub click_event: Option<ClickEvent>,
+    #[serde(default, rename = "hoverEvent", skip_serializing_if = "Option::is_none")]
+    pub hover_event: Option<HoverEvent>,
+}
+
+impl Style {
+    // What's set here, and the parent's style for what isn't.
+    pub fn inherit(&self, parent: &Style) -> Style {
+        Style {
+            color: self.color.or(parent.color),
+            bold: self.bold.or(parent.bold),
+            italic: self.italic.or(parent.italic),
+            underlined: self.underlined.or(parent.underlined),
+            strikethrough: self.strikethrough.or(parent.strikethrough),
+            obfuscated: self.obfuscated.or(parent.obfuscated),
+            font: self.font.clone().or_else(|| parent.font.clone()),
+            insertion: self.insertion.clone().or_else(|| parent.insertion.clone()),
+            click_event: self.click_event.clone().or_else(|| parent.click_event.clone()),
+            hover_event: self.hover_event.clone().or_else(|| parent.hover_event.clone()),
+        }
+    }
+
+    // The formatting legacy codes can express, with their codes.
+    fn formats(&self) -> [(Option<bool>, char); 5] {
+        [(self.obfuscated, 'k'), (self.bold, 'l'), (self.strikethrough, 'm'), (self.underlined, 'n'), (self.italic, 'o')]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    // A player name or a selector.
+    pub name: String,
+    pub objective: String,
+    // Filled in by the server before it's sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+// Which key of the JSON object a component has decides what it shows, in the order vanilla checks for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text {
+        text: String,
+    },
+    Translate {
+        translate: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        with: Vec<TextComponent>,
+    },
+    Score {
+        score: Score,
+    },
+    Selector {
+        selector: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        separator: Option<Box<TextComponent>>,
+    },
+    Keybind {
+        keybind: String,
+    },
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Text { text: String::new() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TextComponent {
+    #[serde(flatten)]
+    pub content: Content,
+    #[serde(flatten)]
+    pub style: Style,
+    // Shown after the content, with this component's style unless they have their own.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<TextComponent>,
+}
+
+impl TextComponent {
+    fn new(content: Content) -> Self {
+        Self { content, style: Style::default(), extra: Vec::new() }
+    }
+
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(Content::Text { text: text.into() })
+    }
+
+    // A key from the client's language file, like `multiplayer.disconnect.kicked`, with the `%s`s in it filled by `with`.
+    pub fn translate(key: impl Into<String>, with: Vec<TextComponent>) -> Self {
+        Self::new(Content::Translate { translate: key.into(), with })
+    }
+
+    pub fn score(name: impl Into<String>, objective: impl Into<String>) -> Self {
+        Self::new(Content::Score { score: Score { name: name.into(), objective: objective.into(), value: None } })
+    }
+
+    pub fn selector(selector: impl Into<String>) -> Self {
+        Self::new(Content::Selector { selector: selector.into(), separator: None })
+    }
+
+    // Whatever key the player bound to something, like `key.jump`.
+    pub fn keybind(keybind: impl Into<String>) -> Self {
+        Self::new(Content::Keybind { keybind: keybind.into() })
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.style.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.style.italic = Some(italic);
+        self
+    }
+
+    pub fn underlined(mut self, underlined: bool) -> Self {
+        self.style.underlined = Some(underlined);
+        self
+    }
+
+    pub fn strikethrough(mut self, strikethrough: bool) -> Self {
+        self.style.strikethrough = Some(strikethrough);
+        self
+    }
+
+    pub fn obfuscated(mut self, obfuscated: bool) -> Self {
+        self.style.obfuscated = Some(obfuscated);
+        self
+    }
+
+    pub fn font(mut self, font: impl Into<String>) -> Self {
+        self.style.font = Some(font.into());
+        self
+    }
+
+    pub fn insertion(mut self, insertion: impl Into<String>) -> Self {
+        self.style.insertion = Some(insertion.into());
+        self
+    }
+
+    pub fn on_click(mut self, event: ClickEvent) -> Self {
+        self.style.click_event = Some(event);
+        self
+    }
+
+    pub fn on_hover(mut self, event: HoverEvent) -> Self {
+        self.style.hover_event = Some(event);
+        self
+    }
+
+    pub fn append(mut self, child: TextComponent) -> Self {
+        self.extra.push(child);
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        // There's nothing in a component JSON can't have.
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    // Calls `f` with the text of every component in order, and the style it's shown with.
+    fn walk(&self, parent: &Style, f: &mut impl FnMut(&str, &Style)) {
+        let style = self.style.inherit(parent);
+
+        match &self.content {
+            Content::Text { text } => f(text, &style),
+            // The client would translate it, there's no language file here.
+            Content::Translate { translate, with } => {
+                f(translate, &style);
+                with.iter().for_each(|arg| arg.walk(&style, f));
+            }
+            Content::Score { score } => f(score.value.as_deref().unwrap_or_default(), &style),
+            Content::Selector { selector, .. } => f(selector, &style),
+            Content::Keybind { keybind } => f(keybind, &style),
+        }
+
+        self.extra.iter().for_each(|child| child.walk(&style, f));
+    }
+
+    // Just the text, for logs and the console.
+    pub fn to_plain(&self) -> String {
+        let mut plain = String::new();
+        self.walk(&Style::default(), &mut |text, _| plain.push_str(text));
+
+        plain
+    }
+
+    // Reads text with `§` codes, where a colour code also turns off the formatting before it, like old clients did.
+    pub fn from_legacy(legacy: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut style = Style::default();
+        let mut text = String::new();
+        let mut chars = legacy.chars();
+
+        while let Some(c) = chars.next() {
+            if c != SECTION_SIGN {
+                text.push(c);
+                continue;
+            }
+
+            let code = match chars.next() {
+                Some(code) => code.to_ascii_lowercase(),
+                None => break,
+            };
+
+            if !text.is_empty() {
+                parts.push(TextComponent { style: style.clone(), ..TextComponent::text(std::mem::take(&mut text)) });
+            }
+
+            match code {
+                'k' => style.obfuscated = Some(true),
+                'l' => style.bold = Some(true),
+                'm' => style.strikethrough = Some(true),
+                'n' => style.underlined = Some(true),
+                'o' => style.italic = Some(true),
+                'r' => style = Style::default(),
+                code => {
+                    // Codes that don't mean anything are skipped.
+                    if let Some(color) = Color::from_code(code) {
+                        style = Style { color: Some(color), ..Style::default() };
+                    }
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TextComponent { style, ..TextComponent::text(text) });
+        }
+
+        match parts.len() {
+            0 => TextComponent::text(""),
+            1 if parts[0].style == Style::default() => parts.remove(0),
+            _ => TextComponent { extra: parts, ..TextComponent::text("") },
+        }
+    }
+
+    // The other way around, for clients and consoles that only know `§` codes. RGB colours become the closest named
+    // one, and everything codes can't express is lost.
+    pub fn to_legacy(&self) -> String {
+        let mut legacy = String::new();
+        let mut last = Style::default();
+
+        self.walk(&Style::default(), &mut |text, style| {
+            if text.is_empty() {
+                return;
+            }
+
+            let formats = |style: &Style| (style.color.map(|color| color.code()), style.formats().map(|(on, _)| on == Some(true)));
+
+            if formats(style) != formats(&last) {
+                match style.color {
+                    Some(color) => legacy.extend([SECTION_SIGN, color.code()]),
+                    None if last != Style::default() => legacy.extend([SECTION_SIGN, 'r']),
+                    None => {}
+                }
+
+                for (_, code) in style.formats().iter().filter(|(on, _)| *on == Some(true)) {
+                    legacy.extend([SECTION_SIGN, *code]);
+                }
+
+                last = style.clone();
+            }
+
+            legacy.push_str(text);
+        });
+
+        legacy
+    }
+}
+
+impl From<&str> for TextComponent {
+    fn from(text: &str) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+impl From<String> for TextComponent {
+    fn from(text: String) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+#[derive(Deserialize)]
+struct Object {
+    #[serde(flatten)]
+    content: Content,
+    #[serde(flatten)]
+    style: Style,
+    #[serde(default)]
+    extra: Vec<TextComponent>,
+}
+
+// Besides objects, clients take plain strings and arrays, where the first element is the parent of the rest.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Json {
+    Text(String),
+    Array(Vec<TextComponent>),
+    Object(Box<Object>),
+}
+
+impl<'de> Deserialize<'de> for TextComponent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Json::deserialize(deserializer)? {
+            Json::Text(text) => Ok(TextComponent::text(text)),
+            Json::Array(components) => {
+                let mut components = components.into_iter();
+                let mut first = components.next().ok_or_else(|| serde::de::Error::custom("a text component array can't be empty"))?;
+                first.extra.extend(components);
+
+                Ok(first)
+            }
+            Json::Object(object) => Ok(TextComponent { content: object.content, style: object.style, extra: object.extra }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn json_test() -> Result<()> {
+        assert_eq!(TextComponent::text("Kicked").to_json(), r#"{"text":"Kicked"}"#);
+
+        let message = TextComponent::text("Hello ")
+            .color(Color::Gold)
+            .bold(true)
+            .on_click(ClickEvent::RunCommand("/spawn".to_owned()))
+            .on_hover(HoverEvent::ShowText(Box::new("Teleport".into())))
+            .append(TextComponent::text("world").color(Color::Rgb(0x12, 0xab, 0xff)).bold(false));
+
+        let json = concat!(
+            r#"{"text":"Hello ","color":"gold","bold":true,"#,
+            r#""clickEvent":{"action":"run_command","value":"/spawn"},"#,
+            r#""hoverEvent":{"action":"show_text","contents":{"text":"Teleport"}},"#,
+            r##""extra":[{"text":"world","color":"#12abff","bold":false}]}"##,
+        );
+
+        assert_eq!(message.to_json(), json);
+        assert_eq!(TextComponent::from_json(json)?, message);
+
+        Ok(())
+    }
+
+    #[test]
+    fn contents_test() -> Result<()> {
+        let vals = [
+            (
+                TextComponent::translate("chat.type.text", vec!["Notch".into(), "hi".into()]),
+                r#"{"translate":"chat.type.text","with":[{"text":"Notch"},{"text":"hi"}]}"#,
+            ),
+            (TextComponent::translate("menu.quit", vec![]), r#"{"translate":"menu.quit"}"#),
+            (TextComponent::score("@p", "kills"), r#"{"score":{"name":"@p","objective":"kills"}}"#),
+            (TextComponent::selector("@a[distance=..5]"), r#"{"selector":"@a[distance=..5]"}"#),
+            (TextComponent::keybind("key.jump").italic(true), r#"{"keybind":"key.jump","italic":true}"#),
+        ];
+
+        for (component, json) in vals {
+            assert_eq!(component.to_json(), json);
+            assert_eq!(TextComponent::from_json(json)?, component);
+        }
+
+        let entity = HoverEvent::ShowEntity(HoverEntity {
+            kind: "minecraft:pig".to_owned(),
+            id: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_owned(),
+            name: None,
+        });
+        let json = concat!(
+            r#"{"text":"","hoverEvent":{"action":"show_entity","#,
+            r#""contents":{"type":"minecraft:pig","id":"069a79f4-44e9-4726-a5be-fca90e38aaf5"}}}"#,
+        );
+        assert_eq!(TextComponent::text("").on_hover(entity).to_json(), json);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_json_test() -> Result<()> {
+        assert_eq!(TextComponent::from_json(r#""plain""#)?, TextComponent::text("plain"));
+        assert_eq!(
+            TextComponent::from_json(r#"[{"text":"a","color":"red"},"b"]"#)?,
+            TextComponent::text("a").color(Color::Red).append("b".into())
+        );
+
+        assert!(TextComponent::from_json("[]").is_err());
+        assert!(TextComponent::from_json(r#"{"color":"red"}"#).is_err());
+        assert!(TextComponent::from_json(r#"{"text":"a","color":"pink"}"#).is_err());
+        assert!(TextComponent::from_json(r##"{"text":"a","color":"#12345"}"##).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn colors_test() -> Result<()> {
+        assert_eq!("dark_aqua".parse::<Color>()?, Color::DarkAqua);
+        assert_eq!(Color::DarkAqua.to_string(), "dark_aqua");
+        assert_eq!(Color::from_code('A'), Some(Color::Green));
+        assert_eq!(Color::from_code('g'), None);
+
+        assert_eq!(Color::Rgb(0xff, 0xaa, 0x00).to_string(), "#ffaa00");
+        assert_eq!(Color::Gold.rgb(), 0xffaa00);
+        assert_eq!(Color::Rgb(0xf0, 0x10, 0x10).code(), '4');
+        assert_eq!(Color::Rgb(0xff, 0x60, 0x60).code(), 'c');
+        assert_eq!(Color::White.code(), 'f');
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_test() {
+        assert_eq!(TextComponent::from_legacy("plain"), TextComponent::text("plain"));
+        assert_eq!(TextComponent::from_legacy(""), TextComponent::text(""));
+
+        let component = TextComponent::from_legacy("§6§lRustic §rserver §cbroken§§");
+        let expected = TextComponent::text("")
+            .append(TextComponent::text("Rustic ").color(Color::Gold).bold(true))
+            .append(TextComponent::text("server "))
+            .append(TextComponent::text("broken").color(Color::Red));
+        assert_eq!(component, expected);
+
+        // A colour turns the bold off again.
+        let component = TextComponent::from_legacy("§lbold§aGREEN");
+        assert_eq!(component.extra[1], TextComponent::text("GREEN").color(Color::Green));
+
+        assert_eq!(component.to_plain(), "boldGREEN");
+        assert_eq!(component.to_legacy(), "§lbold§aGREEN");
+        assert_eq!(TextComponent::from_legacy("§6§lRustic §rserver §cbroken").to_legacy(), "§6§lRustic §rserver §cbroken");
+
+        // Children inherit the style of their parent.
+        let nested = TextComponent::text("a").color(Color::Rgb(0, 0, 0xa0)).append(TextComponent::text("b").underlined(true));
+        assert_eq!(nested.to_legacy(), "§1a§1§nb");
+        assert_eq!(TextComponent::translate("key", vec!["arg".into()]).to_plain(), "keyarg");
+    }
+}
@@ -0,0 +1,440 @@
+use derive_more::{Display, Error};
+use rustic_io::nbt::{Compound, Tag};
+
+use crate::registry::{Biome, Block, BlockEntityType};
+use crate::world::chunk::{BlockEntity, Chunk, LightArray, SECTION_HEIGHT};
+use crate::world::palette::{bits_for, Kind, PackedArray, PalettedContainer, BIOMES, BLOCK_STATES};
+
+// 1.18.2, which is the chunk format this reads and writes.
+pub const DATA_VERSION: i32 = 2975;
+
+#[derive(Debug, Display, Error)]
+pub enum ChunkError {
+    #[display(fmt = "{} is missing or has the wrong type", _0)]
+    Missing(#[error(not(source))] &'static str),
+    #[display(fmt = "section {} is outside of the world", _0)]
+    SectionOutside(#[error(not(source))] i32),
+    #[display(fmt = "unknown block state {}", _0)]
+    UnknownBlock(#[error(not(source))] String),
+    #[display(fmt = "unknown biome {}", _0)]
+    UnknownBiome(#[error(not(source))] String),
+    #[display(fmt = "{} data doesn't fit its palette", _0)]
+    InvalidData(#[error(not(source))] &'static str),
+    #[display(fmt = "light array of {} bytes instead of 2048", _0)]
+    InvalidLight(#[error(not(source))] usize),
+}
+
+// Field names are what the error says is missing, so they're static.
+fn get<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a Tag, ChunkError> {
+    compound.get(name).ok_or(ChunkError::Missing(name))
+}
+
+fn get_int(compound: &Compound, name: &'static str) -> Result<i32, ChunkError> {
+    match get(compound, name)? {
+        Tag::Int(value) => Ok(*value),
+        _ => Err(ChunkError::Missing(name)),
+    }
+}
+
+// Lists that aren't there are just empty.
+fn get_list<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a [Tag], ChunkError> {
+    match compound.get(name) {
+        Some(Tag::List(list)) => Ok(list),
+        None => Ok(&[]),
+        _ => Err(ChunkError::Missing(name)),
+    }
+}
+
+fn as_compound<'a>(tag: &'a Tag, name: &'static str) -> Result<&'a Compound, ChunkError> {
+    match tag {
+        Tag::Compound(compound) => Ok(compound),
+        _ => Err(ChunkError::Missing(name)),
+    }
+}
+
+fn as_string<'a>(tag: &'a Tag, name: &'static str) -> Result<&'a str, ChunkError> {
+    match tag {
+        Tag::String(string) => Ok(string),
+        _ => Err(ChunkError::Missing(name)),
+    }
+}
+
+// Like `{Name: "minecraft:oak_stairs", Properties: {facing: "east", half: "top", ..}}`.
+fn read_block_state(tag: &Tag) -> Result<u32, ChunkError> {
+    let state = as_compound(tag, "block state")?;
+    let name = as_string(get(state, "Name")?, "Name")?;
+
+    let mut properties = Vec::new();
+
+    if let Some(tag) = state.get("Properties") {
+        for (property, value) in as_compound(tag, "Properties")?.iter() {
+            properties.push((property, as_string(value, "Properties")?));
+        }
+    }
+
+    Block::by_name(name)
+        .and_then(|block| block.state_id(&properties))
+        .ok_or_else(|| ChunkError::UnknownBlock(format!("{}{:?}", name, properties)))
+}
+
+fn write_block_state(state: u32) -> Result<Tag, ChunkError> {
+    let block = Block::from_state(state).ok_or_else(|| ChunkError::UnknownBlock(state.to_string()))?;
+    let properties = block.state_properties(state).unwrap_or_default();
+
+    let mut compound = Compound::new();
+    compound.insert("Name", Tag::String(format!("minecraft:{}", block.name)));
+
+    if !properties.is_empty() {
+        let properties = properties.into_iter().map(|(name, value)| (name.to_owned(), Tag::String(value.to_string()))).collect();
+        compound.insert("Properties", Tag::Compound(properties));
+    }
+
+    Ok(Tag::Compound(compound))
+}
+
+fn read_biome(tag: &Tag) -> Result<u32, ChunkError> {
+    let name = as_string(tag, "biome")?;
+
+    Biome::by_name(name).map(|biome| biome.id).ok_or_else(|| ChunkError::UnknownBiome(name.to_owned()))
+}
+
+fn write_biome(biome: u32) -> Result<Tag, ChunkError> {
+    let biome = Biome::by_id(biome).ok_or_else(|| ChunkError::UnknownBiome(biome.to_string()))?;
+
+    Ok(Tag::String(format!("minecraft:{}", biome.name)))
+}
+
+// Saved containers always have a palette, with as few bits as it needs. There's no data if it has just one value.
+fn read_container(
+    tag: &Tag,
+    kind: Kind,
+    name: &'static str,
+    read_value: impl Fn(&Tag) -> Result<u32, ChunkError>,
+) -> Result<PalettedContainer, ChunkError> {
+    let container = as_compound(tag, name)?;
+
+    let palette = match container.get("palette") {
+        Some(Tag::List(palette)) if !palette.is_empty() => palette.iter().map(read_value).collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(ChunkError::Missing("palette")),
+    };
+
+    if palette.len() == 1 {
+        return Ok(PalettedContainer::new(kind, palette[0]));
+    }
+
+    let longs = match container.get("data") {
+        Some(Tag::LongArray(longs)) => longs.iter().map(|long| *long as u64).collect(),
+        _ => return Err(ChunkError::Missing("data")),
+    };
+
+    let bits = bits_for(palette.len()).max(kind.min_bits);
+    let data = PackedArray::from_longs(bits, kind.entries(), longs).ok_or(ChunkError::InvalidData(name))?;
+    let values = data.iter().map(|index| palette.get(index as usize).copied()).collect::<Option<Vec<_>>>();
+
+    Ok(PalettedContainer::from_values(kind, &values.ok_or(ChunkError::InvalidData(name))?).unwrap())
+}
+
+fn write_container(container: &PalettedContainer, write_value: impl Fn(u32) -> Result<Tag, ChunkError>) -> Result<Tag, ChunkError> {
+    let values: Vec<_> = container.values().collect();
+    let mut palette = Vec::new();
+
+    for value in &values {
+        if !palette.contains(value) {
+            palette.push(*value);
+        }
+    }
+
+    let mut compound = Compound::new();
+    compound.insert("palette", Tag::List(palette.iter().map(|value| write_value(*value)).collect::<Result<_, _>>()?));
+
+    if palette.len() > 1 {
+        let mut data = PackedArray::new(bits_for(palette.len()).max(container.kind().min_bits), values.len());
+
+        for (i, value) in values.iter().enumerate() {
+            data.set(i, palette.iter().position(|entry| entry == value).unwrap() as u32);
+        }
+
+        compound.insert("data", Tag::LongArray(data.longs().iter().map(|long| *long as i64).collect()));
+    }
+
+    Ok(Tag::Compound(compound))
+}
+
+fn read_light(tag: &Tag) -> Result<LightArray, ChunkError> {
+    match tag {
+        Tag::ByteArray(bytes) => {
+            let bytes: Vec<u8> = bytes.iter().map(|byte| *byte as u8).collect();
+
+            LightArray::from_bytes(&bytes).ok_or(ChunkError::InvalidLight(bytes.len()))
+        }
+        _ => Err(ChunkError::Missing("light")),
+    }
+}
+
+fn write_light(light: &LightArray) -> Tag {
+    Tag::ByteArray(light.bytes().iter().map(|byte| *byte as i8).collect())
+}
+
+// `None` for block entities the client doesn't know about, which vanilla leaves out too.
+fn read_block_entity(tag: &Tag, chunk_x: i32, chunk_z: i32) -> Result<Option<BlockEntity>, ChunkError> {
+    let mut data = as_compound(tag, "block entity")?.clone();
+
+    let id = as_string(get(&data, "id")?, "id")?;
+    let Some(kind) = BlockEntityType::by_name(id).map(|kind| kind.id) else { return Ok(None) };
+
+    let x = get_int(&data, "x")? - chunk_x * 16;
+    let y = get_int(&data, "y")?;
+    let z = get_int(&data, "z")? - chunk_z * 16;
+
+    if !(0..16).contains(&x) || !(0..16).contains(&z) {
+        return Ok(None);
+    }
+
+    for name in ["id", "x", "y", "z", "keepPacked"] {
+        data.remove(name);
+    }
+
+    Ok(Some(BlockEntity { x: x as u8, y, z: z as u8, kind, data }))
+}
+
+fn write_block_entity(block_entity: &BlockEntity, chunk_x: i32, chunk_z: i32) -> Tag {
+    let kind = BlockEntityType::by_id(block_entity.kind).map_or("unknown", |kind| kind.name);
+
+    let mut compound = Compound::new();
+    compound.insert("id", Tag::String(format!("minecraft:{}", kind)));
+    compound.insert("x", Tag::Int(chunk_x * 16 + block_entity.x as i32));
+    compound.insert("y", Tag::Int(block_entity.y));
+    compound.insert("z", Tag::Int(chunk_z * 16 + block_entity.z as i32));
+    compound.insert("keepPacked", Tag::Byte(0));
+
+    for (name, tag) in block_entity.data.iter() {
+        compound.insert(name, tag.clone());
+    }
+
+    Tag::Compound(compound)
+}
+
+impl Chunk {
+    // Reads a chunk like 1.18 saves them in region files. How high the world is comes from its dimension type.
+    // Entities, scheduled ticks and structures aren't kept.
+    pub fn from_nbt(nbt: &Compound, min_y: i32, height: i32) -> Result<Self, ChunkError> {
+        let mut chunk = Chunk::new(get_int(nbt, "xPos")?, get_int(nbt, "zPos")?, min_y, height, 0);
+        let min_section = min_y / SECTION_HEIGHT;
+
+        for section in get_list(nbt, "sections")? {
+            let section = as_compound(section, "sections")?;
+
+            let y = match get(section, "Y")? {
+                Tag::Byte(y) => *y as i32,
+                _ => return Err(ChunkError::Missing("Y")),
+            };
+
+            // Light goes one section further than the blocks on both ends.
+            let light = y - min_section + 1;
+
+            if light < 0 || light as usize >= chunk.sky_light().len() {
+                return Err(ChunkError::SectionOutside(y));
+            }
+
+            if let Some(tag) = section.get("SkyLight") {
+                chunk.sky_light_mut()[light as usize] = Some(read_light(tag)?);
+            }
+
+            if let Some(tag) = section.get("BlockLight") {
+                chunk.block_light_mut()[light as usize] = Some(read_light(tag)?);
+            }
+
+            let Some(blocks) = chunk.sections_mut().get_mut((y - min_section) as usize) else { continue };
+
+            if let Some(tag) = section.get("block_states") {
+                blocks.blocks = read_container(tag, BLOCK_STATES, "block_states", read_block_state)?;
+            }
+
+            if let Some(tag) = section.get("biomes") {
+                blocks.biomes = read_container(tag, BIOMES, "biomes", read_biome)?;
+            }
+        }
+
+        for block_entity in get_list(nbt, "block_entities")? {
+            if let Some(block_entity) = read_block_entity(block_entity, chunk.x, chunk.z)? {
+                chunk.block_entities.push(block_entity);
+            }
+        }
+
+        Ok(chunk)
+    }
+
+    // A fully generated chunk with its light, which vanilla loads without touching it.
+    pub fn to_nbt(&self) -> Result<Compound, ChunkError> {
+        let min_section = self.min_y() / SECTION_HEIGHT;
+        let mut sections = Vec::with_capacity(self.sky_light().len());
+
+        for light in 0..self.sky_light().len() {
+            let mut section = Compound::new();
+            section.insert("Y", Tag::Byte((min_section + light as i32 - 1) as i8));
+
+            if let Some(blocks) = light.checked_sub(1).and_then(|i| self.sections().get(i)) {
+                section.insert("block_states", write_container(&blocks.blocks, write_block_state)?);
+                section.insert("biomes", write_container(&blocks.biomes, write_biome)?);
+            }
+
+            if let Some(array) = &self.block_light()[light] {
+                section.insert("BlockLight", write_light(array));
+            }
+
+            if let Some(array) = &self.sky_light()[light] {
+                section.insert("SkyLight", write_light(array));
+            }
+
+            // Vanilla doesn't save the sections past the ends that don't have light either.
+            if section.len() > 1 {
+                sections.push(Tag::Compound(section));
+            }
+        }
+
+        let block_entities = self.block_entities.iter().map(|block_entity| write_block_entity(block_entity, self.x, self.z)).collect();
+
+        let mut nbt = Compound::new();
+        nbt.insert("DataVersion", Tag::Int(DATA_VERSION));
+        nbt.insert("xPos", Tag::Int(self.x));
+        nbt.insert("yPos", Tag::Int(min_section));
+        nbt.insert("zPos", Tag::Int(self.z));
+        nbt.insert("Status", Tag::String("full".to_owned()));
+        nbt.insert("isLightOn", Tag::Byte(1));
+        nbt.insert("sections", Tag::List(sections));
+        nbt.insert("block_entities", Tag::List(block_entities));
+        nbt.insert("Heightmaps", Tag::Compound(self.heightmaps()));
+
+        Ok(nbt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn invalid_test() {
+        let mut nbt = Compound::new();
+        assert!(matches!(Chunk::from_nbt(&nbt, 0, 16), Err(ChunkError::Missing("xPos"))));
+
+        nbt.insert("xPos", Tag::Int(0));
+        nbt.insert("zPos", Tag::Int(0));
+        assert!(Chunk::from_nbt(&nbt, 0, 16).is_ok());
+
+        let mut section = Compound::new();
+        section.insert("Y", Tag::Byte(2));
+        nbt.insert("sections", Tag::List(vec![Tag::Compound(section.clone())]));
+        assert!(matches!(Chunk::from_nbt(&nbt, 0, 16), Err(ChunkError::SectionOutside(2))));
+
+        section.insert("Y", Tag::Byte(1));
+        section.insert("SkyLight", Tag::ByteArray(vec![0; 100]));
+        nbt.insert("sections", Tag::List(vec![Tag::Compound(section)]));
+        assert!(matches!(Chunk::from_nbt(&nbt, 0, 16), Err(ChunkError::InvalidLight(100))));
+    }
+
+    #[test]
+    fn block_entity_test() -> Result<()> {
+        let mut data = Compound::new();
+        data.insert("Text1", Tag::String("\"hi\"".to_owned()));
+        let sign = BlockEntity { x: 3, y: -10, z: 15, kind: BlockEntityType::by_name("sign").unwrap().id, data };
+
+        let tag = write_block_entity(&sign, -2, 5);
+        let Tag::Compound(compound) = &tag else { panic!() };
+        assert_eq!(compound.get("id"), Some(&Tag::String("minecraft:sign".to_owned())));
+        assert_eq!(compound.get("x"), Some(&Tag::Int(-29)));
+        assert_eq!(compound.get("z"), Some(&Tag::Int(95)));
+
+        assert_eq!(read_block_entity(&tag, -2, 5)?, Some(sign));
+        // Not in this chunk.
+        assert_eq!(read_block_entity(&tag, -1, 5)?, None);
+
+        Ok(())
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn round_trip_test() -> Result<()> {
+        let stone = Block::by_name("stone").unwrap().default_state;
+        let stairs = Block::by_name("oak_stairs").unwrap().state_id(&[("facing", "east"), ("half", "top")]).unwrap();
+        let desert = Biome::by_name("desert").unwrap().id;
+
+        let mut chunk = Chunk::new(4, -7, -64, 384, Biome::by_name("plains").unwrap().id);
+        for i in 0..4096 {
+            chunk.set_block(i % 16, -64 + (i / 256) as i32, i / 16 % 16, stone);
+        }
+        chunk.set_block(1, 100, 2, stairs);
+        chunk.set_biome(0, 0, 0, desert);
+        chunk.sky_light_mut()[25] = Some(LightArray::new(15));
+        chunk.block_light_mut()[0] = Some(LightArray::new(0));
+
+        let nbt = chunk.to_nbt()?;
+        let Some(Tag::List(sections)) = nbt.get("sections") else { panic!() };
+        // Every section with blocks, and the ones below and above them for their light.
+        assert_eq!(sections.len(), 26);
+
+        let read = Chunk::from_nbt(&nbt, -64, 384)?;
+        assert_eq!(read.block(1, 100, 2), stairs);
+        assert_eq!(read.block(15, -49, 15), stone);
+        assert_eq!(read.block(0, -48, 0), 0);
+        assert_eq!(read.biome(0, 0, 0), desert);
+        assert_eq!(read.sky_light(), chunk.sky_light());
+        assert_eq!(read.block_light(), chunk.block_light());
+
+        for (read, section) in read.sections().iter().zip(chunk.sections()) {
+            assert!(read.blocks.values().eq(section.blocks.values()));
+            assert!(read.biomes.values().eq(section.biomes.values()));
+        }
+
+        Ok(())
+    }
+
+    // A chunk like 1.18.2 saves it, with everything this doesn't keep still in there.
+    #[cfg(minecraft_data)]
+    #[test]
+    fn fixture_test() -> Result<()> {
+        let nbt: Compound = include_str!("../../fixtures/chunk.snbt").parse()?;
+        let chunk = Chunk::from_nbt(&nbt, -64, 384)?;
+
+        let bedrock = Block::by_name("bedrock").unwrap().default_state;
+        let stone = Block::by_name("stone").unwrap().default_state;
+        let stairs = Block::by_name("oak_stairs").unwrap().state_id(&[("facing", "east"), ("half", "top")]).unwrap();
+
+        assert_eq!((chunk.x, chunk.z), (4, -7));
+        assert_eq!(chunk.block(0, -64, 0), bedrock);
+        assert_eq!(chunk.block(15, -64, 15), bedrock);
+        assert_eq!(chunk.block(0, -63, 0), stone);
+        assert_eq!(chunk.block(1, -59, 2), stairs);
+        assert_eq!(chunk.block(2, -59, 2), stone);
+        assert_eq!(chunk.block(0, -48, 0), 0);
+        assert_eq!(chunk.block(0, 10, 0), 0);
+
+        assert_eq!(chunk.biome(0, -64, 0), Biome::by_name("plains").unwrap().id);
+        assert_eq!(chunk.biome(3, -52, 3), Biome::by_name("desert").unwrap().id);
+
+        assert!(chunk.sky_light()[0].is_some());
+        assert!(chunk.sky_light()[1].is_none());
+        assert!(chunk.block_light()[5].is_some());
+
+        // The sign is kept, the block entity nobody knows about isn't.
+        assert_eq!(chunk.block_entities.len(), 1);
+        let sign = &chunk.block_entities[0];
+        assert_eq!((sign.x, sign.y, sign.z), (1, -59, 2));
+        assert_eq!(sign.kind, BlockEntityType::by_name("sign").unwrap().id);
+        assert_eq!(sign.data.get("Color"), Some(&Tag::String("black".to_owned())));
+
+        // And it's still the same chunk after saving it again.
+        let read = Chunk::from_nbt(&chunk.to_nbt()?, -64, 384)?;
+
+        for (read, section) in read.sections().iter().zip(chunk.sections()) {
+            assert!(read.blocks.values().eq(section.blocks.values()));
+            assert!(read.biomes.values().eq(section.biomes.values()));
+        }
+
+        assert_eq!(read.block_entities, chunk.block_entities);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashSet;
+
+use bytes::{Bytes, BytesMut};
+use rustic_io::datatypes::arrays::BitSet;
+use rustic_io::encode::PacketWriter;
+use rustic_io::nbt::{Compound, Flavor, LongArray, Nbt, NbtError, Tag};
+use rustic_io::scroll;
+
+use crate::registry::Block;
+use crate::world::palette::{PackedArray, PalettedContainer, BIOMES, BLOCK_STATES};
+
+// The packets' names in minecraft-data, `Version::clientbound_id` has their ids.
+pub const CHUNK_DATA: &str = "map_chunk";
+pub const UPDATE_LIGHT: &str = "update_light";
+
+pub const SECTION_HEIGHT: i32 = 16;
+
+// Air is state 0 in every version since the flattening, the registry knows the other kinds.
+fn is_air(state: u32) -> bool {
+    state == 0 || Block::from_state(state).is_some_and(|block| matches!(block.name, "cave_air" | "void_air"))
+}
+
+// 16x16x16 blocks, and the biomes of its 4x4x4 cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub blocks: PalettedContainer,
+    pub biomes: PalettedContainer,
+}
+
+impl Section {
+    pub fn new(block: u32, biome: u32) -> Self {
+        Self { blocks: PalettedContainer::new(BLOCK_STATES, block), biomes: PalettedContainer::new(BIOMES, biome) }
+    }
+
+    // What the client wants to know to skip empty sections, anything that isn't air.
+    pub fn block_count(&self) -> usize {
+        self.blocks.counts().filter(|(state, _)| !is_air(*state)).map(|(_, count)| count).sum()
+    }
+}
+
+// Light levels of a section, two to a byte with the first one in the low bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightArray(Box<[u8; 2048]>);
+
+impl LightArray {
+    pub fn new(level: u8) -> Self {
+        assert!(level <= 15, "light level {}", level);
+
+        Self(Box::new([level | level << 4; 2048]))
+    }
+
+    // `None` unless there are exactly 2048 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self(Box::new(bytes.try_into().ok()?)))
+    }
+
+    pub fn bytes(&self) -> &[u8; 2048] {
+        &self.0
+    }
+
+    // Same order as the blocks, x first, then z, then y.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = (y * 16 + z) * 16 + x;
+
+        self.0[index / 2] >> (index % 2 * 4) & 0x0f
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        assert!(level <= 15, "light level {}", level);
+
+        let index = (y * 16 + z) * 16 + x;
+        let shift = index % 2 * 4;
+
+        self.0[index / 2] = self.0[index / 2] & !(0x0f << shift) | level << shift;
+    }
+
+    // Sent in the empty masks instead of as an array.
+    pub fn is_dark(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+    // Inside the chunk.
+    pub x: u8,
+    pub y: i32,
+    pub z: u8,
+    // The id of the block entity type, not of the block.
+    pub kind: u32,
+    // Only what the client renders, like sign text or banner patterns.
+    pub data: Compound,
+}
+
+// A column of sections, with light for one more section below and above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    min_y: i32,
+    sections: Vec<Section>,
+    pub block_entities: Vec<BlockEntity>,
+    // `None` for sections the client should keep whatever light it has for.
+    sky_light: Vec<Option<LightArray>>,
+    block_light: Vec<Option<LightArray>>,
+}
+
+impl Chunk {
+    // Filled with air and one biome. `min_y` and `height` come from the dimension type, and are multiples of 16.
+    pub fn new(x: i32, z: i32, min_y: i32, height: i32, biome: u32) -> Self {
+        assert!(min_y % SECTION_HEIGHT == 0 && height % SECTION_HEIGHT == 0 && height > 0, "{} blocks from y {}", height, min_y);
+
+        let sections = (height / SECTION_HEIGHT) as usize;
+
+        Self {
+            x,
+            z,
+            min_y,
+            sections: vec![Section::new(0, biome); sections],
+            block_entities: Vec::new(),
+            sky_light: vec![None; sections + 2],
+            block_light: vec![None; sections + 2],
+        }
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    pub fn height(&self) -> i32 {
+        self.sections.len() as i32 * SECTION_HEIGHT
+    }
+
+    // Lowest first.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut [Section] {
+        &mut self.sections
+    }
+
+    // The section and the y inside it.
+    fn locate(&self, y: i32) -> (usize, usize) {
+        assert!(y >= self.min_y && y < self.min_y + self.height(), "y {} is outside the chunk", y);
+
+        let y = (y - self.min_y) as usize;
+
+        (y / SECTION_HEIGHT as usize, y % SECTION_HEIGHT as usize)
+    }
+
+    // x and z are inside the chunk, y is the world's.
+    pub fn block(&self, x: usize, y: i32, z: usize) -> u32 {
+        let (section, y) = self.locate(y);
+
+        self.sections[section].blocks.get(x, y, z)
+    }
+
+    // Gives back the state that was there.
+    pub fn set_block(&mut self, x: usize, y: i32, z: usize, state: u32) -> u32 {
+        let (section, y) = self.locate(y);
+
+        self.sections[section].blocks.set(x, y, z, state)
+    }
+
+    // Biomes are stored per 4x4x4 blocks, this takes block coordinates.
+    pub fn biome(&self, x: usize, y: i32, z: usize) -> u32 {
+        let (section, y) = self.locate(y);
+
+        self.sections[section].biomes.get(x / 4, y / 4, z / 4)
+    }
+
+    pub fn set_biome(&mut self, x: usize, y: i32, z: usize, biome: u32) -> u32 {
+        let (section, y) = self.locate(y);
+
+        self.sections[section].biomes.set(x / 4, y / 4, z / 4, biome)
+    }
+
+    // The section below the chunk first, the one above it last.
+    pub fn sky_light(&self) -> &[Option<LightArray>] {
+        &self.sky_light
+    }
+
+    pub fn sky_light_mut(&mut self) -> &mut [Option<LightArray>] {
+        &mut self.sky_light
+    }
+
+    pub fn block_light(&self) -> &[Option<LightArray>] {
+        &self.block_light
+    }
+
+    pub fn block_light_mut(&mut self) -> &mut [Option<LightArray>] {
+        &mut self.block_light
+    }
+
+    // For every column, the number of blocks from the bottom of the chunk up to and including the highest one that
+    // isn't air. Columns of just air are 0. Index is x + z * 16.
+    pub fn heights(&self) -> [u32; 256] {
+        let mut heights = [0; 256];
+        let mut missing = 256;
+
+        for (i, section) in self.sections.iter().enumerate().rev() {
+            let air: HashSet<_> = section.blocks.counts().map(|(state, _)| state).filter(|state| is_air(*state)).collect();
+
+            if air.len() == section.blocks.counts().count() {
+                continue;
+            }
+
+            for (column, height) in heights.iter_mut().enumerate().filter(|(_, height)| **height == 0) {
+                let top = (0..16).rev().find(|y| !air.contains(&section.blocks.get(column % 16, *y, column / 16)));
+
+                if let Some(y) = top {
+                    *height = (i * 16 + y + 1) as u32;
+                    missing -= 1;
+                }
+            }
+
+            if missing == 0 {
+                break;
+            }
+        }
+
+        heights
+    }
+
+    // The heightmaps the client wants, as longs with just enough bits for the chunk's height.
+    pub fn heightmaps(&self) -> Compound {
+        let mut heightmap = PackedArray::new(bits_for_height(self.height()), 256);
+
+        for (column, height) in self.heights().into_iter().enumerate() {
+            heightmap.set(column, height);
+        }
+
+        let longs = LongArray(heightmap.longs().iter().map(|long| *long as i64).collect());
+
+        // Without collision shapes every block that isn't air counts as blocking motion, which is close enough for
+        // the client, that only uses them to decide where rain and snow fall.
+        let mut heightmaps = Compound::new();
+        heightmaps.insert("MOTION_BLOCKING", Tag::LongArray(longs.0.clone()));
+        heightmaps.insert("WORLD_SURFACE", Tag::LongArray(longs.0));
+
+        heightmaps
+    }
+
+    // The payload of Chunk Data and Update Light.
+    pub fn encode(&self) -> Result<Bytes, NbtError> {
+        let mut writer = PacketWriter::new(BytesMut::new());
+        writer.i32(self.x).i32(self.z).bytes(&Nbt::new(self.heightmaps()).write(Flavor::File)?);
+
+        let mut sections = PacketWriter::new(Vec::new());
+
+        for section in &self.sections {
+            sections.i16(section.block_count() as i16).value(&section.blocks)?.value(&section.biomes)?;
+        }
+
+        writer.byte_array(&sections.into_inner());
+
+        writer.length(self.block_entities.len());
+
+        for block_entity in &self.block_entities {
+            writer
+                .u8((block_entity.x & 0x0f) << 4 | block_entity.z & 0x0f)
+                .i16(block_entity.y as i16)
+                .var_int(block_entity.kind as i32)
+                .bytes(&Nbt::new(block_entity.data.clone()).write(Flavor::File)?);
+        }
+
+        self.encode_light(&mut writer)?;
+
+        Ok(writer.into_inner().freeze())
+    }
+
+    // The payload of Update Light, for when only the light changed.
+    pub fn encode_update_light(&self) -> Result<Bytes, scroll::Error> {
+        let mut writer = PacketWriter::new(BytesMut::new());
+        writer.var_int(self.x).var_int(self.z);
+        self.encode_light(&mut writer)?;
+
+        Ok(writer.into_inner().freeze())
+    }
+
+    fn encode_light(&self, writer: &mut PacketWriter<BytesMut>) -> Result<(), scroll::Error> {
+        let (sky_mask, empty_sky_mask, sky_arrays) = light_masks(&self.sky_light);
+        let (block_mask, empty_block_mask, block_arrays) = light_masks(&self.block_light);
+
+        // Trust edges, so the client doesn't light the borders of the chunk itself.
+        writer.bool(true);
+        writer.value(sky_mask)?.value(block_mask)?.value(empty_sky_mask)?.value(empty_block_mask)?;
+
+        for arrays in [sky_arrays, block_arrays] {
+            writer.array(arrays.into_iter(), |writer, array| { writer.byte_array(array); });
+        }
+
+        Ok(())
+    }
+}
+
+// Heights go from 0 to the chunk's height, both included.
+fn bits_for_height(height: i32) -> u8 {
+    (u32::BITS - (height as u32).leading_zeros()) as u8
+}
+
+// Which sections have light, which have none at all, and the arrays of the ones that have light, in that order.
+fn light_masks(light: &[Option<LightArray>]) -> (BitSet, BitSet, Vec<&[u8]>) {
+    let mut mask = BitSet::new();
+    let mut empty_mask = BitSet::new();
+    let mut arrays = Vec::new();
+
+    for (i, array) in light.iter().enumerate() {
+        match array {
+            Some(array) if array.is_dark() => empty_mask.set(i, true),
+            Some(array) => {
+                mask.set(i, true);
+                arrays.push(&array.bytes()[..]);
+            }
+            None => {}
+        }
+    }
+
+    (mask, empty_mask, arrays)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    fn heightmap_bytes(name: &str, first: u64, longs: usize) -> Vec<u8> {
+        let mut bytes = vec![0x0c, 0x00, name.len() as u8];
+        bytes.extend(name.as_bytes());
+        bytes.extend((longs as i32).to_be_bytes());
+        bytes.extend(first.to_be_bytes());
+        bytes.extend(vec![0; (longs - 1) * 8]);
+        bytes
+    }
+
+    #[test]
+    fn chunk_data_test() -> Result<()> {
+        let mut chunk = Chunk::new(1, -2, 0, 16, 0);
+        assert_eq!(chunk.set_block(0, 0, 0, 1), 0);
+        chunk.block_entities.push(BlockEntity { x: 15, y: 1, z: 2, kind: 2, data: Compound::new() });
+        chunk.sky_light_mut()[1] = Some(LightArray::new(15));
+        chunk.sky_light_mut()[2] = Some(LightArray::new(0));
+
+        let mut expected = vec![0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfe];
+
+        // 17 heights fit into 5 bits, that's 12 in a long.
+        expected.extend([0x0a, 0x00, 0x00]);
+        expected.extend(heightmap_bytes("MOTION_BLOCKING", 1, 22));
+        expected.extend(heightmap_bytes("WORLD_SURFACE", 1, 22));
+        expected.push(0x00);
+
+        // Section size, block count, then the blocks with a palette and the biomes without.
+        expected.extend([0x8b, 0x10, 0x00, 0x01]);
+        expected.extend([0x04, 0x02, 0x00, 0x01, 0x80, 0x02]);
+        expected.extend(1u64.to_be_bytes());
+        expected.extend(vec![0; 255 * 8]);
+        expected.extend([0x00, 0x00, 0x00]);
+
+        // The block entity, with an empty compound.
+        expected.extend([0x01, 0xf2, 0x00, 0x01, 0x02, 0x0a, 0x00, 0x00, 0x00]);
+
+        // Trusted edges, light in the section itself, and none above it.
+        expected.extend([0x01, 0x01]);
+        expected.extend(2u64.to_be_bytes());
+        expected.extend([0x00, 0x01]);
+        expected.extend(4u64.to_be_bytes());
+        expected.extend([0x00, 0x01, 0x80, 0x10]);
+        expected.extend([0xff; 2048]);
+        expected.push(0x00);
+
+        assert_eq!(&chunk.encode()?[..], &expected[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_light_test() -> Result<()> {
+        let mut chunk = Chunk::new(-1, 300, -64, 384, 0);
+        chunk.block_light_mut()[25] = Some(LightArray::new(0));
+
+        let mut light = LightArray::new(0);
+        light.set(1, 0, 0, 14);
+        assert_eq!(light.get(1, 0, 0), 14);
+        assert_eq!(light.get(0, 0, 0), 0);
+        chunk.block_light_mut()[0] = Some(light);
+
+        let mut expected = vec![0xff, 0xff, 0xff, 0xff, 0x0f, 0xac, 0x02, 0x01];
+        expected.extend([0x00, 0x01]);
+        expected.extend(1u64.to_be_bytes());
+        expected.extend([0x00, 0x01]);
+        expected.extend((1u64 << 25).to_be_bytes());
+        expected.extend([0x00, 0x01, 0x80, 0x10, 0xe0]);
+        expected.extend([0x00; 2047]);
+
+        assert_eq!(&chunk.encode_update_light()?[..], &expected[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn heights_test() {
+        let mut chunk = Chunk::new(0, 0, -64, 384, 0);
+        assert_eq!(chunk.heights(), [0; 256]);
+        assert_eq!(bits_for_height(chunk.height()), 9);
+
+        chunk.set_block(3, -64, 1, 1);
+        chunk.set_block(3, 100, 1, 1);
+        chunk.set_block(15, 319, 15, 1);
+        chunk.set_block(0, 0, 0, 1);
+        assert_eq!(chunk.block(3, 100, 1), 1);
+
+        let heights = chunk.heights();
+        assert_eq!(heights[3 + 16], 165);
+        assert_eq!(heights[255], 384);
+        assert_eq!(heights[0], 65);
+        assert_eq!(heights.iter().filter(|height| **height != 0).count(), 3);
+
+        // 9 bits for 0 to 384, so 7 in a long.
+        let heightmaps = chunk.heightmaps();
+        let Some(Tag::LongArray(longs)) = heightmaps.get("MOTION_BLOCKING") else { panic!() };
+        assert_eq!(longs.len(), 37);
+        assert_eq!(longs[0], 65);
+        assert_eq!(longs[36], 384 << 27);
+
+        assert_eq!(chunk.sections()[4].block_count(), 1);
+        assert_eq!(chunk.sections()[5].block_count(), 0);
+
+        chunk.set_biome(5, 70, 9, 3);
+        assert_eq!(chunk.biome(4, 71, 8), 3);
+        assert_eq!(chunk.biome(8, 71, 8), 0);
+    }
+
+    #[cfg(minecraft_data)]
+    #[test]
+    fn packet_ids_test() {
+        use rustic_io::packet::ConnectionState;
+
+        use crate::protocol::Version;
+
+        let version = Version::by_protocol(758).expect("1.18.2 is always generated");
+
+        assert_eq!(version.clientbound_id(ConnectionState::Play, CHUNK_DATA), Some(0x22));
+        assert_eq!(version.clientbound_id(ConnectionState::Play, UPDATE_LIGHT), Some(0x25));
+        assert_eq!(version.clientbound_id(ConnectionState::Status, CHUNK_DATA), None);
+    }
+}
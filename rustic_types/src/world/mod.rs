@@ -0,0 +1,11 @@
+// Paletted containers for the block states and biomes of chunk sections, and the long arrays they're packed into
+pub mod palette;
+
+// Columns of sections, and the packets that send them to the client
+pub mod chunk;
+
+// Anvil region files, which store 32x32 chunks
+pub mod region;
+
+// Reading and writing chunks in the format they're saved in
+pub mod anvil;
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+
+use rustic_io::datatypes::var::VarInt;
+use rustic_io::packet::{read_length, write_length};
+use rustic_io::scroll::{self, ctx, Pread, Pwrite, BE};
+
+use crate::registry::{BIOME_COUNT, BLOCK_STATE_COUNT};
+
+// Numbers of the same size packed into longs, lowest bits first. Since 1.16 a number never spans two longs, the
+// bits left over at the top of each long are unused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedArray {
+    bits: u8,
+    len: usize,
+    data: Vec<u64>,
+}
+
+fn longs_for(bits: u8, len: usize) -> usize {
+    match bits {
+        0 => 0,
+        bits => len.div_ceil(64 / bits as usize),
+    }
+}
+
+impl PackedArray {
+    pub fn new(bits: u8, len: usize) -> Self {
+        assert!((1..=32).contains(&bits), "{} bits per entry", bits);
+
+        Self { bits, len, data: vec![0; longs_for(bits, len)] }
+    }
+
+    // `None` if there aren't as many longs as the entries need.
+    pub fn from_longs(bits: u8, len: usize, data: Vec<u64>) -> Option<Self> {
+        match (1..=32).contains(&bits) && data.len() == longs_for(bits, len) {
+            true => Some(Self { bits, len, data }),
+            false => None,
+        }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn longs(&self) -> &[u64] {
+        &self.data
+    }
+
+    fn position(&self, index: usize) -> (usize, usize) {
+        assert!(index < self.len, "index {} out of {} entries", index, self.len);
+
+        let per_long = 64 / self.bits as usize;
+
+        (index / per_long, (index % per_long) * self.bits as usize)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        let (long, shift) = self.position(index);
+
+        ((self.data[long] >> shift) & self.mask()) as u32
+    }
+
+    // Gives back what was there before. The value has to fit into the bits, cutting it off would silently change it.
+    pub fn set(&mut self, index: usize, value: u32) -> u32 {
+        assert!((value as u64) <= self.mask(), "{} doesn't fit into {} bits", value, self.bits);
+
+        let (long, shift) = self.position(index);
+        let old = (self.data[long] >> shift) & self.mask();
+
+        self.data[long] = self.data[long] & !(self.mask() << shift) | (value as u64 & self.mask()) << shift;
+
+        old as u32
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+}
+
+// What a container holds, and the parameters the client decodes it with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kind {
+    // The container is a cube with this many entries along each side.
+    pub edge: usize,
+    // Indirect palettes use at least `min_bits`, and more than `max_bits` means the global palette.
+    pub min_bits: u8,
+    pub max_bits: u8,
+    // Enough bits for every id in the global palette, which is what the client expects for direct containers.
+    pub direct_bits: u8,
+}
+
+impl Kind {
+    pub fn entries(&self) -> usize {
+        self.edge.pow(3)
+    }
+}
+
+// Direct containers use as many bits as the version's registries need, e.g. 15 and 6 for 1.18.2's 20342 block states
+// and 61 biomes.
+pub const BLOCK_STATES: Kind = Kind { edge: 16, min_bits: 4, max_bits: 8, direct_bits: direct_bits(BLOCK_STATE_COUNT) };
+pub const BIOMES: Kind = Kind { edge: 4, min_bits: 1, max_bits: 3, direct_bits: direct_bits(BIOME_COUNT) };
+
+// Without any data there's nothing to store, but a container still needs at least a bit.
+const fn direct_bits(ids: u32) -> u8 {
+    match bits_for(ids as usize) {
+        0 => 1,
+        bits => bits,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Palette {
+    // Every entry is the same, so there's no data at all.
+    Single(u32),
+    // The data has indices into this list.
+    Indirect(Vec<u32>),
+    // The data has the global ids themselves.
+    Direct,
+}
+
+// Block states of a chunk section or its biomes, which picks the smallest palette for what's in it as it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PalettedContainer {
+    kind: Kind,
+    palette: Palette,
+    // Empty for single-valued containers.
+    data: Option<PackedArray>,
+    // How many entries have each value, so we know when a value is gone.
+    counts: HashMap<u32, usize>,
+}
+
+// Bits needed for this many different values.
+pub(crate) const fn bits_for(values: usize) -> u8 {
+    (usize::BITS - values.saturating_sub(1).leading_zeros()) as u8
+}
+
+impl PalettedContainer {
+    pub fn new(kind: Kind, value: u32) -> Self {
+        Self { kind, palette: Palette::Single(value), data: None, counts: HashMap::from([(value, kind.entries())]) }
+    }
+
+    // `None` if there isn't a value for every entry, or one of them isn't in the global palette.
+    pub fn from_values(kind: Kind, values: &[u32]) -> Option<Self> {
+        if values.len() != kind.entries() || values.iter().any(|value| *value >> kind.direct_bits != 0) {
+            return None;
+        }
+
+        let mut counts = HashMap::new();
+        // In the order they first appear in, so the same values always give the same palette.
+        let mut palette = Vec::new();
+
+        for value in values {
+            *counts.entry(*value).or_insert_with(|| {
+                palette.push(*value);
+                0
+            }) += 1;
+        }
+
+        if palette.len() == 1 {
+            return Some(Self::new(kind, palette[0]));
+        }
+
+        let mut container = Self { kind, palette: Palette::Direct, data: None, counts };
+
+        if palette.len() <= 1 << kind.max_bits {
+            let bits = bits_for(palette.len()).max(kind.min_bits);
+            let indices: HashMap<_, _> = palette.iter().enumerate().map(|(i, value)| (*value, i as u32)).collect();
+
+            let mut data = PackedArray::new(bits, values.len());
+            values.iter().enumerate().for_each(|(i, value)| {
+                data.set(i, indices[value]);
+            });
+
+            container.palette = Palette::Indirect(palette);
+            container.data = Some(data);
+        } else {
+            let mut data = PackedArray::new(kind.direct_bits, values.len());
+            values.iter().enumerate().for_each(|(i, value)| {
+                data.set(i, *value);
+            });
+
+            container.data = Some(data);
+        }
+
+        Some(container)
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    // What's sent as the bits per entry.
+    pub fn bits(&self) -> u8 {
+        self.data.as_ref().map_or(0, PackedArray::bits)
+    }
+
+    pub fn data(&self) -> Option<&PackedArray> {
+        self.data.as_ref()
+    }
+
+    // Entries go along x first, then z, then y.
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        let edge = self.kind.edge;
+        assert!(x < edge && y < edge && z < edge, "({}, {}, {}) isn't in the container", x, y, z);
+
+        (y * edge + z) * edge + x
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.get_index(self.index(x, y, z))
+    }
+
+    pub fn get_index(&self, index: usize) -> u32 {
+        match (&self.palette, &self.data) {
+            (Palette::Single(value), _) => *value,
+            (Palette::Indirect(palette), Some(data)) => palette[data.get(index) as usize],
+            (_, data) => data.as_ref().unwrap().get(index),
+        }
+    }
+
+    // Gives back the value that was there.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u32) -> u32 {
+        self.set_index(self.index(x, y, z), value)
+    }
+
+    pub fn set_index(&mut self, index: usize, value: u32) -> u32 {
+        let old = self.get_index(index);
+
+        if old == value {
+            return old;
+        }
+
+        let raw = self.raw(value);
+        self.data.as_mut().unwrap().set(index, raw);
+
+        *self.counts.entry(value).or_insert(0) += 1;
+        let old_count = self.counts.get_mut(&old).unwrap();
+        *old_count -= 1;
+
+        if *old_count == 0 {
+            self.counts.remove(&old);
+            self.forget(old);
+        }
+
+        old
+    }
+
+    // Every entry is the value now.
+    pub fn fill(&mut self, value: u32) {
+        *self = Self::new(self.kind, value);
+    }
+
+    pub fn count(&self, value: u32) -> usize {
+        self.counts.get(&value).copied().unwrap_or(0)
+    }
+
+    // Every value that's in the container, with how many entries have it.
+    pub fn counts(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+        self.counts.iter().map(|(value, count)| (*value, *count))
+    }
+
+    // The values of all entries, in the order of their indices.
+    pub fn values(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.kind.entries()).map(|index| self.get_index(index))
+    }
+
+    // What to store for the value, after making room for it in the palette.
+    fn raw(&mut self, value: u32) -> u32 {
+        if let Palette::Single(single) = self.palette {
+            self.palette = Palette::Indirect(vec![single]);
+            self.data = Some(PackedArray::new(self.kind.min_bits, self.kind.entries()));
+        }
+
+        let palette = match &mut self.palette {
+            Palette::Indirect(palette) => palette,
+            _ => return value,
+        };
+
+        if let Some(i) = palette.iter().position(|v| *v == value) {
+            return i as u32;
+        }
+
+        palette.push(value);
+        let len = palette.len();
+
+        if len > 1 << self.bits() {
+            self.resize(self.bits() + 1);
+        }
+
+        match self.palette {
+            Palette::Indirect(_) => len as u32 - 1,
+            _ => value,
+        }
+    }
+
+    // Repacks the data with the bits, or into the global palette if they're too many for an indirect one.
+    fn resize(&mut self, bits: u8) {
+        let values: Vec<_> = self.values().collect();
+
+        if bits > self.kind.max_bits {
+            let mut data = PackedArray::new(self.kind.direct_bits, values.len());
+            values.iter().enumerate().for_each(|(i, value)| {
+                data.set(i, *value);
+            });
+
+            self.palette = Palette::Direct;
+            self.data = Some(data);
+            return;
+        }
+
+        let palette = match &self.palette {
+            Palette::Indirect(palette) => palette,
+            _ => unreachable!(),
+        };
+        let indices: HashMap<_, _> = palette.iter().enumerate().map(|(i, value)| (*value, i as u32)).collect();
+
+        let mut data = PackedArray::new(bits, values.len());
+        values.iter().enumerate().for_each(|(i, value)| {
+            data.set(i, indices[value]);
+        });
+
+        self.data = Some(data);
+    }
+
+    // Takes a value that's no longer used out of the palette. Shrinking only happens once a bit less would still be half
+    // empty, so setting blocks back and forth doesn't repack every time.
+    fn forget(&mut self, value: u32) {
+        if self.counts.len() == 1 {
+            let remaining = *self.counts.keys().next().unwrap();
+            *self = Self::new(self.kind, remaining);
+            return;
+        }
+
+        let wanted_bits = (bits_for(self.counts.len()) + 1).max(self.kind.min_bits);
+
+        match &mut self.palette {
+            Palette::Indirect(palette) => {
+                let i = palette.iter().position(|v| *v == value).unwrap();
+                let last = palette.len() - 1;
+                palette.swap_remove(i);
+
+                // The last value moved to where the forgotten one was.
+                if i != last {
+                    let data = self.data.as_mut().unwrap();
+
+                    for index in 0..data.len() {
+                        if data.get(index) == last as u32 {
+                            data.set(index, i as u32);
+                        }
+                    }
+                }
+
+                if wanted_bits < self.bits() {
+                    self.resize(wanted_bits);
+                }
+            }
+            Palette::Direct if wanted_bits <= self.kind.max_bits => {
+                let values: Vec<_> = self.values().collect();
+                *self = Self::from_values(self.kind, &values).unwrap();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> ctx::TryFromCtx<'a, Kind> for PalettedContainer {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(src: &'a [u8], kind: Kind) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let bits: u8 = src.gread(&mut offset)?;
+
+        let read_id = |offset: &mut usize| -> Result<u32, scroll::Error> {
+            let VarInt(id) = src.gread(offset)?;
+
+            u32::try_from(id).map_err(|_| scroll::Error::BadInput { size: *offset, msg: "negative palette entry" })
+        };
+
+        let (palette, bits) = match bits {
+            0 => (Palette::Single(read_id(&mut offset)?), 0),
+            bits if bits <= kind.max_bits => {
+                let len = read_length(src, &mut offset)?;
+                let mut palette = Vec::with_capacity(len.min(src.len() - offset));
+
+                for _ in 0..len {
+                    palette.push(read_id(&mut offset)?);
+                }
+
+                (Palette::Indirect(palette), bits.max(kind.min_bits))
+            }
+            // The client doesn't look at how many bits it says either.
+            _ => (Palette::Direct, kind.direct_bits),
+        };
+
+        let len = read_length(src, &mut offset)?;
+
+        if len != longs_for(bits, kind.entries()) {
+            return Err(scroll::Error::BadInput { size: len, msg: "wrong number of longs for the bits per entry" });
+        }
+
+        let mut longs = Vec::with_capacity(len);
+
+        for _ in 0..len {
+            longs.push(src.gread_with(&mut offset, BE)?);
+        }
+
+        let container = match palette {
+            Palette::Single(value) => PalettedContainer::new(kind, value),
+            palette => {
+                let data = PackedArray::from_longs(bits, kind.entries(), longs).unwrap();
+                let mut values = Vec::with_capacity(data.len());
+
+                for raw in data.iter() {
+                    values.push(match &palette {
+                        Palette::Indirect(palette) => *palette
+                            .get(raw as usize)
+                            .ok_or(scroll::Error::BadInput { size: raw as usize, msg: "index past the end of the palette" })?,
+                        _ => raw,
+                    });
+                }
+
+                // Servers also send values that aren't used anymore, this leaves them out.
+                PalettedContainer::from_values(kind, &values)
+                    .ok_or(scroll::Error::BadInput { size: values.len(), msg: "value isn't in the global palette" })?
+            }
+        };
+
+        Ok((container, offset))
+    }
+}
+
+impl ctx::TryIntoCtx for &PalettedContainer {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        dst.gwrite(self.bits(), &mut offset)?;
+
+        match &self.palette {
+            Palette::Single(value) => {
+                dst.gwrite(VarInt(*value as i32), &mut offset)?;
+            }
+            Palette::Indirect(palette) => {
+                write_length(dst, &mut offset, palette.len())?;
+
+                for value in palette {
+                    dst.gwrite(VarInt(*value as i32), &mut offset)?;
+                }
+            }
+            Palette::Direct => {}
+        }
+
+        let longs = self.data.as_ref().map_or(&[][..], PackedArray::longs);
+        write_length(dst, &mut offset, longs.len())?;
+
+        for long in longs {
+            dst.gwrite_with(*long, &mut offset, BE)?;
+        }
+
+        Ok(offset)
+    }
+}
+
+impl ctx::TryIntoCtx for PalettedContainer {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dst: &mut [u8], ctx: ()) -> Result<usize, Self::Error> {
+        (&self).try_into_ctx(dst, ctx)
+    }
+}
+
+impl ctx::MeasureWith<()> for &PalettedContainer {
+    fn measure_with(&self, ctx: &()) -> usize {
+        (*self).measure_with(ctx)
+    }
+}
+
+impl ctx::MeasureWith<()> for PalettedContainer {
+    fn measure_with(&self, _: &()) -> usize {
+        let var_int = |value: u32| VarInt(value as i32).encoded_len();
+
+        let palette = match &self.palette {
+            Palette::Single(value) => var_int(*value),
+            Palette::Indirect(palette) => var_int(palette.len() as u32) + palette.iter().map(|value| var_int(*value)).sum::<usize>(),
+            Palette::Direct => 0,
+        };
+        let longs = self.data.as_ref().map_or(0, |data| data.longs().len());
+
+        1 + palette + var_int(longs as u32) + longs * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rustic_io::scroll::ctx::{MeasureWith, TryIntoCtx};
+
+    use super::*;
+
+    // 1.18.2's, whatever the registries the crate was built with have.
+    const BLOCK_STATES: Kind = Kind { direct_bits: 15, ..super::BLOCK_STATES };
+    const BIOMES: Kind = Kind { direct_bits: 6, ..super::BIOMES };
+
+    fn encode(container: &PalettedContainer) -> Result<Vec<u8>> {
+        let mut bytes = vec![0; container.measure_with(&())];
+        container.try_into_ctx(&mut bytes, ())?;
+
+        Ok(bytes)
+    }
+
+    #[test]
+    fn packed_array_test() {
+        // 12 five-bit entries in a long, and four bits unused at the top of each.
+        let mut array = PackedArray::new(5, 4096);
+        assert_eq!(array.longs().len(), 342);
+
+        assert_eq!(array.set(0, 31), 0);
+        array.set(11, 1);
+        array.set(12, 3);
+
+        assert_eq!(array.longs()[0], 31 | 1 << 55);
+        assert_eq!(array.longs()[1], 3);
+        assert_eq!(array.set(0, 2), 31);
+        assert_eq!(array.iter().take(13).collect::<Vec<_>>(), [2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 3]);
+
+        assert!(PackedArray::from_longs(5, 4096, vec![0; 341]).is_none());
+        assert_eq!(PackedArray::from_longs(9, 256, vec![0; 37]).unwrap().len(), 256);
+    }
+
+    #[test]
+    #[should_panic(expected = "32 doesn't fit into 5 bits")]
+    fn packed_array_overflow_test() {
+        PackedArray::new(5, 64).set(0, 32);
+    }
+
+    #[test]
+    fn direct_bits_test() {
+        assert_eq!(super::BLOCK_STATES.direct_bits, bits_for(BLOCK_STATE_COUNT as usize).max(1));
+        assert_eq!(super::BIOMES.direct_bits, bits_for(BIOME_COUNT as usize).max(1));
+
+        // Anything that doesn't fit is no block state or biome at all.
+        let mut values = vec![0; 64];
+        values[1] = 1 << 6;
+        assert!(PalettedContainer::from_values(BIOMES, &values).is_none());
+    }
+
+    #[test]
+    fn grow_and_shrink_test() {
+        let mut section = PalettedContainer::new(BLOCK_STATES, 0);
+        assert_eq!(section.bits(), 0);
+
+        assert_eq!(section.set(1, 2, 3, 1), 0);
+        assert_eq!(section.get(1, 2, 3), 1);
+        assert_eq!(section.palette(), &Palette::Indirect(vec![0, 1]));
+        assert_eq!(section.bits(), 4);
+
+        // 17 different values don't fit into 4 bits.
+        for i in 0..16 {
+            section.set_index(i, i as u32 + 1);
+        }
+        assert_eq!(section.bits(), 5);
+
+        // 300 don't fit into an indirect palette at all.
+        for i in 0..300 {
+            section.set_index(i, i as u32 + 1000);
+        }
+        assert_eq!(section.palette(), &Palette::Direct);
+        assert_eq!(section.bits(), 15);
+        assert_eq!(section.get_index(299), 1299);
+        assert_eq!(section.count(0), 4096 - 301);
+
+        // Back to a few values, with a bit to spare, and then just one.
+        for i in 0..290 {
+            section.set_index(i, 0);
+        }
+        assert!(matches!(section.palette(), Palette::Indirect(palette) if palette.len() == 12));
+        assert_eq!(section.bits(), 5);
+        assert_eq!(section.values().filter(|value| *value != 0).count(), 11);
+
+        for i in 290..300 {
+            section.set_index(i, 0);
+        }
+        section.set(1, 2, 3, 0);
+        assert_eq!(section, PalettedContainer::new(BLOCK_STATES, 0));
+    }
+
+    #[test]
+    fn forget_test() {
+        let mut biomes = PalettedContainer::new(BIOMES, 7);
+        biomes.set(0, 0, 0, 8);
+        biomes.set(1, 0, 0, 9);
+
+        // 8 isn't used anymore, so 9 takes its place in the palette.
+        biomes.set(0, 0, 0, 7);
+        assert_eq!(biomes.palette(), &Palette::Indirect(vec![7, 9]));
+        assert_eq!(biomes.get(1, 0, 0), 9);
+        assert_eq!(biomes.bits(), 2);
+        assert_eq!(biomes.count(9), 1);
+        assert_eq!(biomes.count(8), 0);
+    }
+
+    #[test]
+    fn single_read_write_test() -> Result<()> {
+        let biomes = PalettedContainer::new(BIOMES, 300);
+        let bytes = encode(&biomes)?;
+
+        assert_eq!(bytes, [0x00, 0xac, 0x02, 0x00]);
+        assert_eq!(bytes.pread_with::<PalettedContainer>(0, BIOMES)?, biomes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn indirect_read_write_test() -> Result<()> {
+        let mut biomes = PalettedContainer::new(BIOMES, 1);
+        biomes.set(0, 0, 0, 2);
+        biomes.set(3, 3, 3, 2);
+
+        let mut expected = vec![0x01, 0x02, 0x01, 0x02, 0x01];
+        expected.extend((1u64 | 1 << 63).to_be_bytes());
+
+        let bytes = encode(&biomes)?;
+        assert_eq!(bytes, expected);
+        // Reading puts the palette in the order the values first appear in.
+        let read: PalettedContainer = bytes.pread_with(0, BIOMES)?;
+        assert_eq!(read.palette(), &Palette::Indirect(vec![2, 1]));
+        assert!(read.values().eq(biomes.values()));
+
+        // Fewer bits than the minimum are read as the minimum.
+        let mut section = PalettedContainer::new(BLOCK_STATES, 0);
+        section.set_index(4095, 9);
+        let mut bytes = encode(&section)?;
+        assert_eq!(bytes[0], 4);
+
+        bytes[0] = 2;
+        assert_eq!(bytes.pread_with::<PalettedContainer>(0, BLOCK_STATES)?, section);
+
+        Ok(())
+    }
+
+    #[test]
+    fn direct_read_write_test() -> Result<()> {
+        let values: Vec<_> = (0..4096).collect();
+        let section = PalettedContainer::from_values(BLOCK_STATES, &values).unwrap();
+        assert_eq!(section.palette(), &Palette::Direct);
+
+        let bytes = encode(&section)?;
+        assert_eq!(bytes.len(), 1 + 2 + 1024 * 8);
+        assert_eq!(bytes.pread_with::<PalettedContainer>(0, BLOCK_STATES)?, section);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_test() {
+        // An index past the palette, and data that's too short for 1 bit.
+        let mut bytes = vec![0x01, 0x01, 0x05, 0x01];
+        bytes.extend(1u64.to_be_bytes());
+        assert!(bytes.pread_with::<PalettedContainer>(0, BIOMES).is_err());
+
+        assert!([0x01, 0x01, 0x05, 0x00].pread_with::<PalettedContainer>(0, BIOMES).is_err());
+        assert!([0x00, 0x7f, 0x01].pread_with::<PalettedContainer>(0, BIOMES).is_err());
+        assert!([0x00, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00].pread_with::<PalettedContainer>(0, BIOMES).is_err());
+        assert!(PalettedContainer::from_values(BIOMES, &[0; 63]).is_none());
+    }
+}
@@ -0,0 +1,528 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use derive_more::{Display, Error, From};
+use rustic_io::nbt::{FileCompression, Nbt, NbtError};
+
+// Region files are made of 4 KiB sectors. The first two are the header: where each chunk is, then when it was saved.
+pub const SECTOR: usize = 4096;
+const HEADER_SECTORS: usize = 2;
+
+// A region is 32x32 chunks.
+pub const REGION_CHUNKS: i32 = 32;
+
+// The header only has a byte for how many sectors a chunk takes, bigger chunks go into a file of their own.
+const MAX_SECTORS: usize = 255;
+const EXTERNAL: u8 = 0x80;
+
+#[derive(Debug, Display, From, Error)]
+pub enum RegionError {
+    #[display(fmt = "{}", _0)]
+    Io(io::Error),
+    #[display(fmt = "{}", _0)]
+    Nbt(NbtError),
+    #[display(fmt = "{} isn't named like a region file", "_0.display()")]
+    #[from(ignore)]
+    InvalidName(#[error(not(source))] PathBuf),
+    #[display(fmt = "the header is cut off after {} bytes", _0)]
+    #[from(ignore)]
+    TruncatedHeader(#[error(not(source))] u64),
+    #[display(fmt = "chunk at ({}, {}) has a length of {}, which doesn't fit into its sectors", x, z, len)]
+    #[from(ignore)]
+    InvalidLength { x: i32, z: i32, len: u32 },
+    #[display(fmt = "chunk at ({}, {}) has the unknown compression type {}", x, z, compression)]
+    #[from(ignore)]
+    UnknownCompression { x: i32, z: i32, compression: u8 },
+}
+
+// The region file a chunk is in, named after the region's coordinates.
+pub fn region_path(dir: &Path, chunk_x: i32, chunk_z: i32) -> PathBuf {
+    dir.join(format!("r.{}.{}.mca", chunk_x.div_euclid(REGION_CHUNKS), chunk_z.div_euclid(REGION_CHUNKS)))
+}
+
+fn compression_of(compression_type: u8) -> Option<FileCompression> {
+    match compression_type {
+        1 => Some(FileCompression::Gzip),
+        2 => Some(FileCompression::Zlib),
+        3 => Some(FileCompression::None),
+        _ => None,
+    }
+}
+
+fn compression_type(compression: FileCompression) -> u8 {
+    match compression {
+        FileCompression::Gzip => 1,
+        FileCompression::Zlib => 2,
+        FileCompression::None => 3,
+    }
+}
+
+// An Anvil `.mca` file. Chunks are never written over the sectors they're in, and the header only points to them
+// once they're on disk, so a crash loses at most the chunk that was being saved.
+pub struct Region {
+    file: File,
+    path: PathBuf,
+    // Region coordinates, external chunk files are named with the world's chunk coordinates.
+    x: i32,
+    z: i32,
+    // The sector a chunk starts at in the upper 3 bytes, how many it takes in the lowest one. 0 if there's no chunk.
+    locations: Box<[u32; 1024]>,
+    // Seconds since the epoch.
+    timestamps: Box<[u32; 1024]>,
+    // Which sectors are taken, by the header or by a chunk.
+    used: Vec<bool>,
+}
+
+impl Region {
+    // Creates the file if there isn't one yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RegionError> {
+        let path = path.as_ref().to_owned();
+        let (x, z) = parse_name(&path).ok_or_else(|| RegionError::InvalidName(path.clone()))?;
+
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let len = file.metadata()?.len();
+
+        let mut header = vec![0; HEADER_SECTORS * SECTOR];
+
+        match len {
+            0 => {
+                file.write_all(&header)?;
+                file.sync_all()?;
+            }
+            len if len < header.len() as u64 => return Err(RegionError::TruncatedHeader(len)),
+            _ => file.read_exact(&mut header)?,
+        }
+
+        let mut region = Self {
+            file,
+            path,
+            x,
+            z,
+            locations: Box::new([0; 1024]),
+            timestamps: Box::new([0; 1024]),
+            used: vec![true; HEADER_SECTORS],
+        };
+
+        let sectors = len.div_ceil(SECTOR as u64).max(HEADER_SECTORS as u64) as usize;
+        region.used.resize(sectors, false);
+
+        for i in 0..1024 {
+            let location = u32::from_be_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+            let (offset, count) = split(location);
+
+            // Like vanilla, chunks that point outside the file or into other chunks are treated as missing, and are
+            // written somewhere else the next time they're saved.
+            if location == 0 || count == 0 || offset < HEADER_SECTORS || offset + count > sectors {
+                continue;
+            }
+
+            if region.used[offset..offset + count].iter().any(|used| *used) {
+                continue;
+            }
+
+            region.used[offset..offset + count].fill(true);
+            region.locations[i] = location;
+            region.timestamps[i] = u32::from_be_bytes(header[SECTOR + i * 4..SECTOR + i * 4 + 4].try_into().unwrap());
+        }
+
+        Ok(region)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Chunk coordinates can be the world's or ones inside the region, only the lowest 5 bits count.
+    fn index(x: i32, z: i32) -> usize {
+        (x.rem_euclid(REGION_CHUNKS) + z.rem_euclid(REGION_CHUNKS) * REGION_CHUNKS) as usize
+    }
+
+    // The world's chunk coordinates.
+    fn chunk_position(&self, index: usize) -> (i32, i32) {
+        (self.x * REGION_CHUNKS + index as i32 % REGION_CHUNKS, self.z * REGION_CHUNKS + index as i32 / REGION_CHUNKS)
+    }
+
+    fn external_path(&self, index: usize) -> PathBuf {
+        let (x, z) = self.chunk_position(index);
+
+        self.path.with_file_name(format!("c.{}.{}.mcc", x, z))
+    }
+
+    pub fn contains(&self, x: i32, z: i32) -> bool {
+        self.locations[Self::index(x, z)] != 0
+    }
+
+    // When the chunk was last saved, in seconds since the epoch.
+    pub fn timestamp(&self, x: i32, z: i32) -> Option<u32> {
+        let index = Self::index(x, z);
+
+        match self.locations[index] {
+            0 => None,
+            _ => Some(self.timestamps[index]),
+        }
+    }
+
+    // The world's coordinates of every chunk in the region.
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..1024).filter(|i| self.locations[*i] != 0).map(|i| self.chunk_position(i))
+    }
+
+    pub fn read(&mut self, x: i32, z: i32) -> Result<Option<Nbt>, RegionError> {
+        let index = Self::index(x, z);
+        let (x, z) = self.chunk_position(index);
+        let (offset, count) = split(self.locations[index]);
+
+        if count == 0 {
+            return Ok(None);
+        }
+
+        // The last sector isn't always padded.
+        let mut sectors = Vec::with_capacity(count * SECTOR);
+        self.file.seek(SeekFrom::Start((offset * SECTOR) as u64))?;
+        (&mut self.file).take((count * SECTOR) as u64).read_to_end(&mut sectors)?;
+
+        if sectors.len() < 5 {
+            return Err(RegionError::InvalidLength { x, z, len: 0 });
+        }
+
+        // The length counts the compression type too.
+        let len = u32::from_be_bytes(sectors[..4].try_into().unwrap());
+
+        if len == 0 || len as usize > sectors.len() - 4 {
+            return Err(RegionError::InvalidLength { x, z, len });
+        }
+
+        let compression = sectors[4];
+        let external;
+
+        let data = match compression & EXTERNAL {
+            0 => &sectors[5..4 + len as usize],
+            _ => {
+                external = fs::read(self.external_path(index))?;
+                &external[..]
+            }
+        };
+
+        match compression_of(compression & !EXTERNAL) {
+            Some(kind) => Ok(Some(Nbt::read_compressed(data, kind)?)),
+            None => Err(RegionError::UnknownCompression { x, z, compression }),
+        }
+    }
+
+    pub fn write(&mut self, x: i32, z: i32, nbt: &Nbt, compression: FileCompression) -> Result<(), RegionError> {
+        let index = Self::index(x, z);
+        let data = nbt.write_file(compression)?;
+
+        let external = 5 + data.len() > MAX_SECTORS * SECTOR;
+        let external_path = self.external_path(index);
+
+        let mut sectors = Vec::with_capacity(5 + data.len());
+
+        if external {
+            // Renamed into place, so a crash doesn't leave half of it behind for the old header entry.
+            let temporary = external_path.with_extension("mcc.tmp");
+            let mut file = File::create(&temporary)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+            fs::rename(&temporary, &external_path)?;
+
+            sectors.extend(1u32.to_be_bytes());
+            sectors.push(compression_type(compression) | EXTERNAL);
+        } else {
+            sectors.extend((data.len() as u32 + 1).to_be_bytes());
+            sectors.push(compression_type(compression));
+            sectors.extend(data);
+        }
+
+        let count = sectors.len().div_ceil(SECTOR);
+        sectors.resize(count * SECTOR, 0);
+
+        let offset = self.allocate(count);
+        self.file.seek(SeekFrom::Start((offset * SECTOR) as u64))?;
+        self.file.write_all(&sectors)?;
+        self.file.sync_data()?;
+
+        let old = self.locations[index];
+        self.set_location(index, (offset as u32) << 8 | count as u32, now())?;
+        self.release(old);
+
+        if !external {
+            remove_if_there(&external_path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, x: i32, z: i32) -> Result<(), RegionError> {
+        let index = Self::index(x, z);
+        let old = self.locations[index];
+
+        if old == 0 {
+            return Ok(());
+        }
+
+        self.set_location(index, 0, 0)?;
+        self.release(old);
+        remove_if_there(&self.external_path(index))?;
+
+        Ok(())
+    }
+
+    // The first free sectors there are enough of in a row, or the end of the file. Doesn't mark them as used yet.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+
+        for (i, used) in self.used.iter().enumerate() {
+            run = match used {
+                true => 0,
+                false => run + 1,
+            };
+
+            if run == count {
+                return i + 1 - count;
+            }
+        }
+
+        self.used.len() - run
+    }
+
+    // Writes the header entry. Four bytes in one sector, which the disk writes all at once.
+    fn set_location(&mut self, index: usize, location: u32, timestamp: u32) -> Result<(), RegionError> {
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_all(&location.to_be_bytes())?;
+        self.file.seek(SeekFrom::Start((SECTOR + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        self.file.sync_data()?;
+
+        let (offset, count) = split(location);
+
+        if count != 0 {
+            if self.used.len() < offset + count {
+                self.used.resize(offset + count, false);
+            }
+
+            self.used[offset..offset + count].fill(true);
+        }
+
+        self.locations[index] = location;
+        self.timestamps[index] = timestamp;
+
+        Ok(())
+    }
+
+    // Lets other chunks have the sectors.
+    fn release(&mut self, location: u32) {
+        let (offset, count) = split(location);
+
+        self.used[offset..offset + count].fill(false);
+    }
+}
+
+fn split(location: u32) -> (usize, usize) {
+    ((location >> 8) as usize, (location & 0xff) as usize)
+}
+
+fn parse_name(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+
+    match (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()) {
+        (x, z, None) => Some((x, z)),
+        _ => None,
+    }
+}
+
+fn now() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() as u32)
+}
+
+fn remove_if_there(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rustic_io::nbt::{Compound, Tag};
+
+    use super::*;
+
+    // A directory of its own for every test, since they run at the same time.
+    fn test_dir(name: &str) -> Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("rustic-region-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+
+        Ok(dir)
+    }
+
+    fn chunk(x: i32, bytes: usize) -> Nbt {
+        let mut root = Compound::new();
+        root.insert("xPos", Tag::Int(x));
+        // Not the same byte over and over, so compression can't make it much smaller.
+        root.insert("data", Tag::ByteArray((0..bytes).map(|i| (i * 7919 % 251) as i8).collect()));
+
+        Nbt::new(root)
+    }
+
+    #[test]
+    fn read_write_test() -> Result<()> {
+        let dir = test_dir("read-write")?;
+        let path = region_path(&dir, -1, 33);
+        assert_eq!(path, dir.join("r.-1.1.mca"));
+
+        let mut region = Region::open(&path)?;
+        assert_eq!(fs::metadata(&path)?.len(), 8192);
+        assert_eq!(region.read(0, 0)?, None);
+
+        region.write(-1, 33, &chunk(1, 10), FileCompression::Zlib)?;
+        region.write(0, 0, &chunk(2, 10), FileCompression::Gzip)?;
+        region.write(31, 31, &chunk(3, 5000), FileCompression::None)?;
+
+        // Reopened, and with the coordinates inside the region.
+        let mut region = Region::open(&path)?;
+        assert_eq!(region.read(31, 1)?, Some(chunk(1, 10)));
+        assert_eq!(region.read(0, 0)?, Some(chunk(2, 10)));
+        assert_eq!(region.read(-1, -1)?, Some(chunk(3, 5000)));
+        assert!(region.timestamp(31, 1).is_some_and(|timestamp| timestamp > 0));
+        assert_eq!(region.timestamp(1, 1), None);
+
+        let mut chunks: Vec<_> = region.chunks().collect();
+        chunks.sort();
+        assert_eq!(chunks, [(-32, 32), (-1, 33), (-1, 63)]);
+
+        // Two sectors of header, one for each small chunk and two for the big one.
+        assert_eq!(fs::metadata(&path)?.len(), 6 * 4096);
+
+        region.remove(0, 0)?;
+        assert_eq!(region.read(0, 0)?, None);
+        assert_eq!(Region::open(&path)?.read(0, 0)?, None);
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn sector_reuse_test() -> Result<()> {
+        let dir = test_dir("sector-reuse")?;
+        let path = dir.join("r.0.0.mca");
+        let mut region = Region::open(&path)?;
+
+        region.write(0, 0, &chunk(0, 100), FileCompression::None)?;
+        region.write(1, 0, &chunk(1, 100), FileCompression::None)?;
+        assert_eq!(split(region.locations[0]), (2, 1));
+        assert_eq!(split(region.locations[1]), (3, 1));
+
+        // Saved somewhere else first, the old sector is only free after that.
+        region.write(0, 0, &chunk(0, 200), FileCompression::None)?;
+        assert_eq!(split(region.locations[0]), (4, 1));
+
+        // Which a chunk that grows out of its sector can have now.
+        region.write(1, 0, &chunk(1, 6000), FileCompression::None)?;
+        assert_eq!(split(region.locations[1]), (5, 2));
+
+        region.write(2, 0, &chunk(2, 100), FileCompression::None)?;
+        assert_eq!(split(region.locations[2]), (2, 1));
+        region.write(3, 0, &chunk(3, 100), FileCompression::None)?;
+        assert_eq!(split(region.locations[3]), (3, 1));
+
+        assert_eq!(fs::metadata(&path)?.len(), 7 * 4096);
+
+        let mut region = Region::open(&path)?;
+        assert_eq!(region.read(1, 0)?, Some(chunk(1, 6000)));
+        assert_eq!(region.read(3, 0)?, Some(chunk(3, 100)));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn external_test() -> Result<()> {
+        let dir = test_dir("external")?;
+        let path = dir.join("r.1.-1.mca");
+        let mut region = Region::open(&path)?;
+
+        let big = chunk(0, 1 << 20);
+        region.write(2, 3, &big, FileCompression::None)?;
+
+        let external = dir.join("c.34.-29.mcc");
+        assert!(external.exists());
+        assert_eq!(split(region.locations[2 + 3 * 32]).1, 1);
+        assert_eq!(Region::open(&path)?.read(2, 3)?, Some(big));
+
+        // Small enough again, so it's back in the region file.
+        region.write(2, 3, &chunk(0, 10), FileCompression::Zlib)?;
+        assert!(!external.exists());
+        assert_eq!(region.read(2, 3)?, Some(chunk(0, 10)));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_test() -> Result<()> {
+        let dir = test_dir("invalid")?;
+        assert!(matches!(Region::open(dir.join("region.mca")), Err(RegionError::InvalidName(_))));
+
+        let path = dir.join("r.0.0.mca");
+        fs::write(&path, [0; 100])?;
+        assert!(matches!(Region::open(&path), Err(RegionError::TruncatedHeader(100))));
+
+        // One chunk past the end of the file, one in the header, and two in the same sector.
+        let mut bytes = vec![0; 3 * 4096];
+        bytes[..4].copy_from_slice(&0x0301u32.to_be_bytes());
+        bytes[4..8].copy_from_slice(&0x0001u32.to_be_bytes());
+        bytes[8..12].copy_from_slice(&0x0201u32.to_be_bytes());
+        bytes[12..16].copy_from_slice(&0x0201u32.to_be_bytes());
+        // Claims to be longer than its sector, with compression type 9.
+        bytes[8192..8197].copy_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x09]);
+        fs::write(&path, &bytes)?;
+
+        let mut region = Region::open(&path)?;
+        assert_eq!(region.chunks().count(), 1);
+        assert_eq!(region.read(0, 0)?, None);
+        assert_eq!(region.read(1, 0)?, None);
+        assert_eq!(region.read(3, 0)?, None);
+        assert!(matches!(region.read(2, 0), Err(RegionError::InvalidLength { x: 2, z: 0, len: 4096 })));
+
+        bytes[8194] = 0x00;
+        bytes[8195] = 0x02;
+        fs::write(&path, &bytes)?;
+        assert!(matches!(Region::open(&path)?.read(2, 0), Err(RegionError::UnknownCompression { compression: 9, .. })));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn compression_type_test() -> Result<()> {
+        let dir = test_dir("compression-type")?;
+        let path = dir.join("r.0.0.mca");
+        let data = chunk(0, 100).write_file(FileCompression::Zlib)?;
+
+        let mut bytes = vec![0; 3 * 4096];
+        bytes[..4].copy_from_slice(&0x0201u32.to_be_bytes());
+        bytes[8192..8196].copy_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+        bytes[8197..8197 + data.len()].copy_from_slice(&data);
+
+        // Only read the way the chunk says it's compressed.
+        for (compression, ok) in [(1, false), (2, true), (3, false)] {
+            bytes[8196] = compression;
+            fs::write(&path, &bytes)?;
+            assert_eq!(Region::open(&path)?.read(0, 0).is_ok(), ok);
+        }
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,20 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod packet;
+
+// Generates scroll `TryFromCtx`/`TryIntoCtx`/`MeasureWith` implementations that read and write the fields in order,
+// plus a `rustic_io::packet::Packet` implementation if the struct has a `#[packet(id = .., state = ..)]` attribute.
+//
+// Fields can be any type scroll can read with a `()` context (`VarInt`, `VarLong`, other derived structs, ...),
+// big-endian numbers, `bool`, `String` (with an optional `#[packet(max_len = ..)]`),
+// `Option<T>` (prefixed by a bool, like `Optional`), `Vec<T>` (prefixed by a VarInt count, like `PrefixedArray`) and
+// `Vec<u8>` marked with `#[packet(rest)]`, which takes up the rest of the packet.
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    packet::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
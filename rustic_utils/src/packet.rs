@@ -0,0 +1,322 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Expr, Fields, GenericArgument, Ident, LitInt, PathArguments, Result, Type,
+};
+
+const NUMBERS: &[&str] = &["i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "i128", "u128", "f32", "f64"];
+
+// How a single field (or an element of one) is put on the wire.
+enum Kind<'a> {
+    String,
+    Bool,
+    Number,
+    Option(&'a Type),
+    ByteArray,
+    Array(&'a Type),
+    Other,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    max_len: Option<Expr>,
+    rest: bool,
+}
+
+#[derive(Default)]
+struct PacketAttrs {
+    id: Option<LitInt>,
+    state: Option<Ident>,
+}
+
+fn single_generic<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+
+    if segment.ident != name {
+        return None;
+    }
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+
+    match args.args.first() {
+        Some(GenericArgument::Type(inner)) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+fn is_ident(ty: &Type, names: &[&str]) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .get_ident()
+            .map(|ident| names.iter().any(|name| ident == name))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn kind(ty: &Type) -> Kind<'_> {
+    if is_ident(ty, &["String"]) {
+        Kind::String
+    } else if is_ident(ty, &["bool"]) {
+        Kind::Bool
+    } else if is_ident(ty, NUMBERS) {
+        Kind::Number
+    } else if let Some(inner) = single_generic(ty, "Option") {
+        Kind::Option(inner)
+    } else if let Some(inner) = single_generic(ty, "Vec") {
+        if is_ident(inner, &["u8"]) {
+            Kind::ByteArray
+        } else {
+            Kind::Array(inner)
+        }
+    } else {
+        Kind::Other
+    }
+}
+
+// An expression that reads a `ty` from `src` at `offset`.
+fn read(ty: &Type, attrs: &FieldAttrs) -> TokenStream {
+    match kind(ty) {
+        Kind::String => {
+            let max_len = match &attrs.max_len {
+                Some(max_len) => quote!(#max_len),
+                None => quote!(::rustic_io::packet::MAX_STRING_LEN),
+            };
+
+            quote!(::rustic_io::packet::read_string(src, &mut offset, #max_len)?)
+        }
+        Kind::Bool => quote!((src.gread_with::<u8>(&mut offset, ::rustic_io::scroll::BE)? != 0)),
+        Kind::Number => quote!(src.gread_with::<#ty>(&mut offset, ::rustic_io::scroll::BE)?),
+        Kind::Option(inner) => {
+            let inner = read_at(read(inner, attrs));
+
+            quote!(::rustic_io::datatypes::arrays::read_optional(src, &mut offset, #inner)?)
+        }
+        Kind::ByteArray if attrs.rest => quote!({
+            let rest = src[offset..].to_vec();
+            offset = src.len();
+            rest
+        }),
+        Kind::ByteArray => quote!(::rustic_io::packet::read_byte_array(src, &mut offset)?),
+        Kind::Array(inner) => {
+            let inner = read_at(read(inner, attrs));
+
+            quote!(::rustic_io::datatypes::arrays::read_prefixed(src, &mut offset, #inner)?)
+        }
+        Kind::Other => quote!(src.gread_with::<#ty>(&mut offset, ())?),
+    }
+}
+
+// The code above and below works with an `offset` of its own, these make closures out of it for the functions that
+// `Optional` and `PrefixedArray` are read and written with.
+fn read_at(read: TokenStream) -> TokenStream {
+    quote!(|src, at: &mut usize| {
+        let mut offset = *at;
+        let value = #read;
+        *at = offset;
+
+        ::core::result::Result::Ok(value)
+    })
+}
+
+fn write_at(item: &Ident, write: TokenStream) -> TokenStream {
+    quote!(|dst: &mut [u8], at: &mut usize, #item| {
+        let mut offset = *at;
+        #write
+        *at = offset;
+
+        ::core::result::Result::Ok(())
+    })
+}
+
+// Statements that write `value` (an owned `ty`) to `dst` at `offset`.
+fn write(ty: &Type, value: &Ident, attrs: &FieldAttrs, depth: usize) -> TokenStream {
+    match kind(ty) {
+        Kind::String => quote!(::rustic_io::packet::write_string(dst, &mut offset, &#value)?;),
+        Kind::Bool => quote!(dst.gwrite_with(#value as u8, &mut offset, ::rustic_io::scroll::BE)?;),
+        Kind::Number => quote!(dst.gwrite_with(#value, &mut offset, ::rustic_io::scroll::BE)?;),
+        Kind::Option(inner) => {
+            let item = format_ident!("item_{}", depth);
+            let inner = write_at(&item, write(inner, &item, attrs, depth + 1));
+
+            quote!(::rustic_io::datatypes::arrays::write_optional(dst, &mut offset, #value, #inner)?;)
+        }
+        Kind::ByteArray if attrs.rest => quote!(::rustic_io::packet::write_bytes(dst, &mut offset, &#value)?;),
+        Kind::ByteArray => quote!(::rustic_io::packet::write_byte_array(dst, &mut offset, &#value)?;),
+        Kind::Array(inner) => {
+            let item = format_ident!("item_{}", depth);
+            let inner = write_at(&item, write(inner, &item, attrs, depth + 1));
+
+            quote!(::rustic_io::datatypes::arrays::write_prefixed(dst, &mut offset, #value, #inner)?;)
+        }
+        Kind::Other => quote!(dst.gwrite_with(#value, &mut offset, ())?;),
+    }
+}
+
+// An expression for the amount of bytes `value` (a `&ty`) takes up when written.
+fn measure(ty: &Type, value: &Ident, attrs: &FieldAttrs, depth: usize) -> TokenStream {
+    match kind(ty) {
+        Kind::String => quote!(::rustic_io::packet::string_len(#value)),
+        Kind::Bool => quote!(1),
+        Kind::Number => quote!(::core::mem::size_of::<#ty>()),
+        Kind::Option(inner) => {
+            let item = format_ident!("item_{}", depth);
+            let inner = measure(inner, &item, attrs, depth + 1);
+
+            quote!(::rustic_io::datatypes::arrays::optional_len(#value.as_ref(), |#item| #inner))
+        }
+        Kind::ByteArray if attrs.rest => quote!(#value.len()),
+        Kind::ByteArray => quote!(::rustic_io::packet::byte_array_len(#value)),
+        Kind::Array(inner) => {
+            let item = format_ident!("item_{}", depth);
+            let inner = measure(inner, &item, attrs, depth + 1);
+
+            quote!(::rustic_io::datatypes::arrays::prefixed_len(#value, |#item| #inner))
+        }
+        Kind::Other => quote!(::rustic_io::scroll::ctx::MeasureWith::<()>::measure_with(#value, &())),
+    }
+}
+
+fn field_attrs(field: &syn::Field) -> Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("max_len") {
+                attrs.max_len = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("rest") {
+                attrs.rest = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `max_len` or `rest`"))
+            }
+        })?;
+    }
+
+    // An `Option` of the rest is prefixed by a bool, like Login Plugin Response's data.
+    let rest_type = match kind(&field.ty) {
+        Kind::ByteArray => true,
+        Kind::Option(inner) => matches!(kind(inner), Kind::ByteArray),
+        _ => false,
+    };
+
+    if attrs.rest && !rest_type {
+        return Err(syn::Error::new_spanned(&field.ty, "`rest` can only be used on a `Vec<u8>` or `Option<Vec<u8>>`"));
+    }
+
+    Ok(attrs)
+}
+
+fn packet_attrs(input: &DeriveInput) -> Result<PacketAttrs> {
+    let mut attrs = PacketAttrs::default();
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                attrs.id = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("state") {
+                attrs.state = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `id` or `state`"))
+            }
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "packets can't be generic"));
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(Span::call_site(), "only structs can be derived as packets"));
+    };
+
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields.named.iter().collect(),
+        Fields::Unit => Vec::new(),
+        Fields::Unnamed(_) => return Err(syn::Error::new_spanned(&data.fields, "packet fields have to be named")),
+    };
+
+    let mut names = Vec::new();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let mut measures = Vec::new();
+
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = field_attrs(field)?;
+        let field_name = field.ident.as_ref().unwrap();
+
+        if attrs.rest && i != fields.len() - 1 {
+            return Err(syn::Error::new_spanned(field, "only the last field can take up the rest of the packet"));
+        }
+
+        let read = read(&field.ty, &attrs);
+        reads.push(quote!(let #field_name = #read;));
+        writes.push(write(&field.ty, field_name, &attrs, 0));
+        measures.push(measure(&field.ty, field_name, &attrs, 0));
+        names.push(field_name);
+    }
+
+    let packet = match packet_attrs(&input)? {
+        PacketAttrs { id: Some(id), state: Some(state) } => quote!(
+            impl ::rustic_io::packet::Packet for #name {
+                const ID: i32 = #id;
+                const STATE: ::rustic_io::packet::ConnectionState = ::rustic_io::packet::ConnectionState::#state;
+            }
+        ),
+        PacketAttrs { id: None, state: None } => quote!(),
+        _ => return Err(syn::Error::new(Span::call_site(), "packets need both an `id` and a `state`")),
+    };
+
+    Ok(quote!(
+        impl<'a> ::rustic_io::scroll::ctx::TryFromCtx<'a> for #name {
+            type Error = ::rustic_io::scroll::Error;
+
+            #[allow(unused_imports, unused_mut)]
+            fn try_from_ctx(src: &'a [u8], _: ()) -> ::core::result::Result<(Self, usize), Self::Error> {
+                use ::rustic_io::scroll::Pread;
+
+                let mut offset = 0;
+                #(#reads)*
+
+                Ok((Self { #(#names),* }, offset))
+            }
+        }
+
+        impl ::rustic_io::scroll::ctx::TryIntoCtx for #name {
+            type Error = ::rustic_io::scroll::Error;
+
+            #[allow(unused_imports, unused_mut, unused_variables)]
+            fn try_into_ctx(self, dst: &mut [u8], _: ()) -> ::core::result::Result<usize, Self::Error> {
+                use ::rustic_io::scroll::Pwrite;
+
+                let mut offset = 0;
+                let Self { #(#names),* } = self;
+                #(#writes)*
+
+                Ok(offset)
+            }
+        }
+
+        impl ::rustic_io::scroll::ctx::MeasureWith<()> for #name {
+            #[allow(unused_variables)]
+            fn measure_with(&self, _: &()) -> usize {
+                let Self { #(#names),* } = self;
+
+                0 #(+ #measures)*
+            }
+        }
+
+        #packet
+    ))
+}